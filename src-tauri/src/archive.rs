@@ -0,0 +1,388 @@
+use crate::{
+    clean_history, crypto, data_dir, ensure_storage_layout, file_list_text, hash_bytes, images,
+    integrity, load_settings, lock_history, normalize_text, now_ms, open_history_db, storage,
+    storage_key, AppState, ClipboardItem,
+};
+use chrono::{Local, TimeZone};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Manager};
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+const MANIFEST_NAME: &str = "manifest.json";
+const ARCHIVE_IMAGE_DIR: &str = "images";
+const ARCHIVE_VERSION: u32 = 1;
+/// 清单文件的大小上限，防止导入时读入异常大的文件。
+const MAX_MANIFEST_BYTES: u64 = 256 * 1024 * 1024;
+
+const FORMAT_ARCHIVE: &str = "archive";
+const FORMAT_JSON: &str = "json";
+const FORMAT_CSV: &str = "csv";
+const FORMAT_MARKDOWN: &str = "markdown";
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ExportPayload {
+    path: String,
+    format: String,
+    #[serde(default)]
+    favorite_only: bool,
+    from: Option<u64>,
+    to: Option<u64>,
+    /// 敏感条目默认不导出，导出文件不受加密与自动过期保护。
+    #[serde(default)]
+    include_sensitive: bool,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ExportReport {
+    path: String,
+    items: usize,
+    images: usize,
+}
+
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ImportReport {
+    pub(crate) inserted: usize,
+    pub(crate) merged: usize,
+    pub(crate) skipped: usize,
+    /// 导入后超出历史上限而被移除的较旧未收藏条目数。
+    pub(crate) evicted: usize,
+}
+
+/// 压缩包内的清单；图片条目的 `imagePath` 指向包内 `images/` 下的明文 PNG。
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Manifest {
+    version: u32,
+    exported_at: u64,
+    items: Vec<ClipboardItem>,
+}
+
+/// 按收藏与时间范围导出历史；压缩包包含全部类型，其余格式只导出带文本的条目。
+/// 加密存储导出的是解密后的内容。先写入同目录的临时文件，完成后再替换目标文件。
+pub(crate) fn export(app: &AppHandle, payload: ExportPayload) -> Result<ExportReport, String> {
+    ensure_storage_layout(app)?;
+    let base = data_dir(app)?;
+    let key = storage_key(app)?;
+    let conn = open_history_db(app)?;
+    let now = now_ms();
+    let mut items = storage::load_filtered_items(
+        &conn,
+        key.as_ref(),
+        None,
+        payload.favorite_only,
+        payload.from,
+        payload.to,
+    )?;
+    items.retain(|item| item.expires_at.is_none_or(|at| at > now));
+    items.retain(|item| payload.include_sensitive || !item.is_sensitive);
+    for item in &mut items {
+        item.image_preview_data_url = None;
+    }
+
+    let path = Path::new(&payload.path);
+    let (count, image_count) = match payload.format.as_str() {
+        FORMAT_ARCHIVE => {
+            write_atomically(path, |file| write_archive(file, &base, key.as_ref(), items))?
+        }
+        format => {
+            items.retain(|item| item.item_type != "image" && item.text.is_some());
+            let bytes = match format {
+                FORMAT_JSON => {
+                    serde_json::to_vec_pretty(&items).map_err(|e| format!("序列化历史失败: {e}"))?
+                }
+                FORMAT_CSV => to_csv(&items).into_bytes(),
+                FORMAT_MARKDOWN => to_markdown(&items).into_bytes(),
+                other => return Err(format!("不支持的导出格式: {other}")),
+            };
+            write_atomically(path, |mut file| {
+                file.write_all(&bytes)
+                    .and_then(|_| file.sync_all())
+                    .map_err(|e| format!("写入导出文件失败: {e}"))
+            })?;
+            (items.len(), 0)
+        }
+    };
+
+    Ok(ExportReport {
+        path: payload.path,
+        items: count,
+        images: image_count,
+    })
+}
+
+/// 写入失败时删除临时文件，目标位置不会留下不完整的导出文件。
+fn write_atomically<T>(
+    path: &Path,
+    write: impl FnOnce(File) -> Result<T, String>,
+) -> Result<T, String> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    let tmp = PathBuf::from(tmp);
+    let result = File::create(&tmp)
+        .map_err(|e| format!("创建导出文件失败: {e}"))
+        .and_then(write)
+        .and_then(|value| {
+            fs::rename(&tmp, path)
+                .map(|_| value)
+                .map_err(|e| format!("写入导出文件失败: {e}"))
+        });
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp);
+    }
+    result
+}
+
+fn write_archive(
+    file: File,
+    base: &Path,
+    key: Option<&crypto::Key>,
+    items: Vec<ClipboardItem>,
+) -> Result<(usize, usize), String> {
+    let mut zip = ZipWriter::new(file);
+    // PNG 本身已压缩，图片直接存储；清单使用 deflate。
+    let stored = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+    let deflated = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+
+    let mut exported = Vec::with_capacity(items.len());
+    let mut written_images = HashSet::new();
+    for mut item in items {
+        if item.item_type == "image" {
+            // 图片文件缺失或无法解密时跳过该条目，不让整个导出失败。
+            let Some(bytes) = item
+                .image_path
+                .as_deref()
+                .and_then(|rel| images::resolve(base, rel))
+                .and_then(|full| crypto::read_file(key, &full).ok())
+            else {
+                continue;
+            };
+            let name = format!("{ARCHIVE_IMAGE_DIR}/{}.png", item.content_hash);
+            if written_images.insert(name.clone()) {
+                zip.start_file(name.as_str(), stored)
+                    .and_then(|_| zip.write_all(&bytes).map_err(Into::into))
+                    .map_err(|e| format!("写入导出文件失败: {e}"))?;
+            }
+            item.image_path = Some(name);
+        }
+        exported.push(item);
+    }
+
+    let manifest = Manifest {
+        version: ARCHIVE_VERSION,
+        exported_at: now_ms(),
+        items: exported,
+    };
+    let json = serde_json::to_vec_pretty(&manifest).map_err(|e| format!("序列化历史失败: {e}"))?;
+    zip.start_file(MANIFEST_NAME, deflated)
+        .and_then(|_| zip.write_all(&json).map_err(Into::into))
+        .map_err(|e| format!("写入导出文件失败: {e}"))?;
+    zip.finish()
+        .and_then(|file| file.sync_all().map_err(Into::into))
+        .map_err(|e| format!("写入导出文件失败: {e}"))?;
+    Ok((manifest.items.len(), written_images.len()))
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn to_csv(items: &[ClipboardItem]) -> String {
+    let mut out = String::from("id,type,createdAt,updatedAt,isFavorite,sourceApp,text\r\n");
+    for item in items {
+        let row = [
+            csv_field(&item.id),
+            csv_field(&item.item_type),
+            item.created_at.to_string(),
+            item.updated_at.to_string(),
+            item.is_favorite.to_string(),
+            csv_field(item.source_app.as_deref().unwrap_or_default()),
+            csv_field(item.text.as_deref().unwrap_or_default()),
+        ];
+        out.push_str(&row.join(","));
+        out.push_str("\r\n");
+    }
+    out
+}
+
+fn format_local_time(ms: u64) -> String {
+    Local
+        .timestamp_millis_opt(ms as i64)
+        .single()
+        .map(|time| time.format("%Y-%m-%d %H:%M:%S").to_string())
+        .unwrap_or_else(|| ms.to_string())
+}
+
+/// 每条一个小节，正文放进代码块；围栏比内容中最长的连续反引号多一个，避免被内容提前闭合。
+fn to_markdown(items: &[ClipboardItem]) -> String {
+    let mut out = String::from("# 剪贴板历史\n");
+    for item in items {
+        let text = item.text.as_deref().unwrap_or_default();
+        let longest_run = text.split(|ch| ch != '`').map(str::len).max().unwrap_or(0);
+        let fence = "`".repeat(longest_run.max(2) + 1);
+
+        out.push_str(&format!("\n## {}", format_local_time(item.updated_at)));
+        if item.is_favorite {
+            out.push_str(" ★");
+        }
+        out.push('\n');
+        if let Some(app) = item.source_app.as_deref() {
+            out.push_str(&format!("\n来源：{app}\n"));
+        }
+        out.push_str(&format!("\n{fence}\n{text}\n{fence}\n"));
+    }
+    out
+}
+
+/// 导入压缩包（`.zip`）或 JSON（导出的条目数组或压缩包清单），按内容哈希与现有历史合并。
+/// 格式错误、图片缺失或哈希不符的条目计入 `skipped`。
+pub(crate) fn import(app: &AppHandle, path: &Path) -> Result<ImportReport, String> {
+    ensure_storage_layout(app)?;
+    let state = app.state::<AppState>();
+    let _guard = lock_history(app, &state)?;
+
+    let base = data_dir(app)?;
+    let key = storage_key(app)?;
+    let is_archive = path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("zip"));
+
+    let mut report = ImportReport::default();
+    let mut accepted = Vec::new();
+    if is_archive {
+        let file = File::open(path).map_err(|e| format!("打开导入文件失败: {e}"))?;
+        let mut zip = ZipArchive::new(file).map_err(|e| format!("读取压缩包失败: {e}"))?;
+        let manifest: Manifest = serde_json::from_slice(&read_entry(&mut zip, MANIFEST_NAME)?)
+            .map_err(|e| format!("解析清单失败: {e}"))?;
+        if manifest.version > ARCHIVE_VERSION {
+            return Err(format!("不支持的压缩包版本: {}", manifest.version));
+        }
+        for mut item in manifest.items {
+            if item.item_type == "image" {
+                let bytes = item
+                    .image_path
+                    .as_deref()
+                    .and_then(|name| read_entry(&mut zip, name).ok());
+                let Some(bytes) = bytes.filter(|bytes| hash_bytes(bytes) == item.content_hash)
+                else {
+                    report.skipped += 1;
+                    continue;
+                };
+                let (rel, _) = images::store(&base, key.as_ref(), &item.content_hash, &bytes)?;
+                item.image_path = Some(rel);
+            }
+            accepted.push(item);
+        }
+    } else {
+        let raw = fs::read(path).map_err(|e| format!("读取导入文件失败: {e}"))?;
+        let items: Vec<ClipboardItem> = serde_json::from_slice::<Manifest>(&raw)
+            .map(|manifest| manifest.items)
+            .or_else(|_| serde_json::from_slice(&raw))
+            .map_err(|e| format!("解析导入文件失败: {e}"))?;
+        for item in items {
+            // 纯 JSON 不含图片文件，图片条目无法还原。
+            if item.item_type == "image" {
+                report.skipped += 1;
+                continue;
+            }
+            accepted.push(item);
+        }
+    }
+
+    merge_imported(app, &base, key.as_ref(), accepted, &mut report)?;
+    Ok(report)
+}
+
+/// 各类导入共用的收尾：丢弃格式错误或已过期的条目，按内容哈希合并进历史并按上限截断，
+/// 再清理不再被引用的图片。调用方需持有历史锁，图片须已写入存储目录。
+pub(crate) fn merge_imported(
+    app: &AppHandle,
+    base: &Path,
+    key: Option<&crypto::Key>,
+    items: Vec<ClipboardItem>,
+    report: &mut ImportReport,
+) -> Result<(), String> {
+    let settings = load_settings(app)?;
+    let now = now_ms();
+    let total = items.len();
+    let accepted: Vec<ClipboardItem> = items
+        .into_iter()
+        .map(|mut item| {
+            item.image_preview_data_url = None;
+            rehash_content(&mut item);
+            item
+        })
+        .filter(|item| integrity::validate_record(item, true).is_ok())
+        .filter(|item| item.expires_at.is_none_or(|at| at > now))
+        .collect();
+    report.skipped += total - accepted.len();
+
+    let accepted = clean_history(accepted, usize::MAX);
+    let mut conn = open_history_db(app)?;
+    let (merge, evicted) = storage::merge_items(
+        &mut conn,
+        key,
+        &accepted,
+        settings.history_limit,
+        settings.favorite_limit,
+    )?;
+    images::release_all(&conn, base, &evicted.image_paths)?;
+    images::remove_orphans(&conn, base)?;
+
+    report.inserted += merge.inserted;
+    report.merged += merge.merged;
+    report.evicted += evicted.count;
+    Ok(())
+}
+
+/// 内容哈希决定去重与合并，不信任导入文件中的值：文本、富文本与文件列表按采集时的规则重新计算，
+/// 图片已在读取时核对过文件哈希。
+fn rehash_content(item: &mut ClipboardItem) {
+    match item.item_type.as_str() {
+        "text" => {
+            if let Some(text) = item.text.as_deref() {
+                let text = normalize_text(text);
+                item.content_hash = hash_bytes(text.as_bytes());
+                item.text = Some(text);
+            }
+        }
+        "html" => {
+            if let Some(html) = item.html.as_deref() {
+                item.content_hash = hash_bytes(html.as_bytes());
+            }
+        }
+        "files" => {
+            if let Some(files) = item.files.as_deref() {
+                let paths: Vec<PathBuf> =
+                    files.iter().map(|file| PathBuf::from(&file.path)).collect();
+                let text = file_list_text(&paths);
+                item.content_hash = hash_bytes(text.as_bytes());
+                item.text = Some(text);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn read_entry(zip: &mut ZipArchive<File>, name: &str) -> Result<Vec<u8>, String> {
+    let entry = zip
+        .by_name(name)
+        .map_err(|e| format!("压缩包缺少 {name}: {e}"))?;
+    let mut bytes = Vec::new();
+    entry
+        .take(MAX_MANIFEST_BYTES)
+        .read_to_end(&mut bytes)
+        .map_err(|e| format!("读取压缩包失败: {e}"))?;
+    Ok(bytes)
+}
@@ -0,0 +1,345 @@
+use crate::source::ActiveWindow;
+
+/// 回填后光标回退的上限，避免超长文本里的 `{{cursor}}` 连续按上千次方向键。
+const MAX_CURSOR_MOVES: usize = 500;
+
+/// 目标程序在列表中（通常是终端）时改用 Ctrl+Shift+V 粘贴，程序名不区分大小写比较。
+pub(crate) fn needs_shift(target: Option<&ActiveWindow>, shift_paste_apps: &[String]) -> bool {
+    let Some(app) = target.and_then(|target| target.app.as_deref()) else {
+        return false;
+    };
+    shift_paste_apps
+        .iter()
+        .any(|name| name.eq_ignore_ascii_case(app))
+}
+
+/// 把焦点还给 `target` 并模拟一次粘贴按键；`cursor_from_end` 为模板中 `{{cursor}}` 的位置，
+/// 粘贴后按相应次数的左方向键把光标移回去。
+pub(crate) fn paste(
+    target: Option<&ActiveWindow>,
+    shift: bool,
+    cursor_from_end: Option<usize>,
+) -> Result<(), String> {
+    let moves = cursor_from_end.unwrap_or(0).min(MAX_CURSOR_MOVES);
+    platform::paste(target.map(|target| target.id), shift, moves)
+}
+
+#[cfg(target_os = "linux")]
+mod platform {
+    use std::thread;
+    use std::time::Duration;
+    use x11rb::connection::Connection;
+    use x11rb::protocol::xproto::{
+        ClientMessageEvent, ConnectionExt as _, EventMask, InputFocus, Keycode, Keysym, Window,
+        KEY_PRESS_EVENT, KEY_RELEASE_EVENT,
+    };
+    use x11rb::protocol::xtest::ConnectionExt as _;
+    use x11rb::rust_connection::RustConnection;
+    use x11rb::{CURRENT_TIME, NONE};
+
+    const XK_CONTROL_L: Keysym = 0xffe3;
+    const XK_SHIFT_L: Keysym = 0xffe1;
+    const XK_LEFT: Keysym = 0xff51;
+    const XK_V: Keysym = 0x0076;
+
+    const KEY_LEFTCTRL: u16 = 29;
+    const KEY_LEFTSHIFT: u16 = 42;
+    const KEY_V: u16 = 47;
+    const KEY_LEFT: u16 = 105;
+
+    /// 激活窗口请求发出后，等窗口管理器切换焦点再发送按键。
+    const FOCUS_DELAY: Duration = Duration::from_millis(80);
+    /// 新建的 uinput 设备需要一点时间才会被合成器识别，过早发送的按键会丢失。
+    const UINPUT_SETTLE: Duration = Duration::from_millis(200);
+
+    /// Wayland 下 XTest 只能作用于 XWayland 窗口，优先用 uinput；其余情况用 XTest。
+    pub(super) fn paste(target: Option<u64>, shift: bool, moves: usize) -> Result<(), String> {
+        let wayland = std::env::var_os("WAYLAND_DISPLAY").is_some();
+        let x11 = std::env::var_os("DISPLAY").is_some();
+        if wayland {
+            match uinput_paste(shift, moves) {
+                Ok(()) => return Ok(()),
+                Err(err) if !x11 => return Err(err),
+                Err(_) => {}
+            }
+        }
+        if !x11 {
+            return Err("未找到可用的显示服务，无法模拟粘贴".to_string());
+        }
+        xtest_paste(target, shift, moves)
+    }
+
+    /// Ctrl(+Shift)+V，之后按 `moves` 次左方向键；返回按下 / 松开的按键序列。
+    fn key_sequence<K: Copy>(
+        ctrl: K,
+        shift: Option<K>,
+        v: K,
+        left: K,
+        moves: usize,
+    ) -> Vec<(K, bool)> {
+        let mut keys = vec![(ctrl, true)];
+        keys.extend(shift.map(|key| (key, true)));
+        keys.extend([(v, true), (v, false)]);
+        keys.extend(shift.map(|key| (key, false)));
+        keys.push((ctrl, false));
+        for _ in 0..moves {
+            keys.extend([(left, true), (left, false)]);
+        }
+        keys
+    }
+
+    fn xtest_paste(target: Option<u64>, shift: bool, moves: usize) -> Result<(), String> {
+        let (conn, screen_num) = x11rb::connect(None).map_err(|e| format!("连接 X11 失败: {e}"))?;
+        let root = conn.setup().roots[screen_num].root;
+        conn.xtest_get_version(2, 2)
+            .map_err(|e| format!("查询 XTest 扩展失败: {e}"))?
+            .reply()
+            .map_err(|_| "X 服务器不支持 XTest 扩展".to_string())?;
+
+        if let Some(window) = target.and_then(|id| Window::try_from(id).ok()) {
+            activate(&conn, root, window)?;
+            thread::sleep(FOCUS_DELAY);
+        }
+
+        let keymap = Keymap::load(&conn)?;
+        let ctrl = keymap.keycode(XK_CONTROL_L)?;
+        let shift_key = keymap.keycode(XK_SHIFT_L)?;
+        let v = keymap.keycode(XK_V)?;
+        let left = keymap.keycode(XK_LEFT)?;
+
+        let shift_key = shift.then_some(shift_key);
+        for (keycode, pressed) in key_sequence(ctrl, shift_key, v, left, moves) {
+            let kind = if pressed {
+                KEY_PRESS_EVENT
+            } else {
+                KEY_RELEASE_EVENT
+            };
+            conn.xtest_fake_input(kind, keycode, CURRENT_TIME, root, 0, 0, 0)
+                .map_err(|e| format!("发送按键失败: {e}"))?;
+        }
+        // 往返一次，确保按键在断开连接前已被服务器处理。
+        conn.get_input_focus()
+            .map_err(|e| format!("发送按键失败: {e}"))?
+            .reply()
+            .map_err(|e| format!("发送按键失败: {e}"))?;
+        Ok(())
+    }
+
+    /// 按 EWMH 请求窗口管理器激活窗口；不支持 EWMH 时直接设置输入焦点。
+    fn activate(conn: &RustConnection, root: Window, window: Window) -> Result<(), String> {
+        let atom = conn
+            .intern_atom(false, b"_NET_ACTIVE_WINDOW")
+            .map_err(|e| format!("激活窗口失败: {e}"))?
+            .reply()
+            .map_err(|e| format!("激活窗口失败: {e}"))?
+            .atom;
+        if atom != NONE {
+            // 来源 2 表示由分页器类工具发起，窗口管理器不会按抢焦点处理。
+            let event = ClientMessageEvent::new(32, window, atom, [2, CURRENT_TIME, 0, 0, 0]);
+            conn.send_event(
+                false,
+                root,
+                EventMask::SUBSTRUCTURE_REDIRECT | EventMask::SUBSTRUCTURE_NOTIFY,
+                event,
+            )
+            .map_err(|e| format!("激活窗口失败: {e}"))?;
+        }
+        // 窗口已关闭或不可见时会返回错误，此时退回到当前焦点窗口粘贴。
+        let _ = conn.set_input_focus(InputFocus::PARENT, window, CURRENT_TIME);
+        conn.flush().map_err(|e| format!("激活窗口失败: {e}"))
+    }
+
+    struct Keymap {
+        min_keycode: Keycode,
+        per_keycode: usize,
+        keysyms: Vec<Keysym>,
+    }
+
+    impl Keymap {
+        fn load(conn: &RustConnection) -> Result<Self, String> {
+            let setup = conn.setup();
+            let min_keycode = setup.min_keycode;
+            let count = setup.max_keycode - min_keycode + 1;
+            let reply = conn
+                .get_keyboard_mapping(min_keycode, count)
+                .map_err(|e| format!("读取键盘映射失败: {e}"))?
+                .reply()
+                .map_err(|e| format!("读取键盘映射失败: {e}"))?;
+            Ok(Self {
+                min_keycode,
+                per_keycode: usize::from(reply.keysyms_per_keycode).max(1),
+                keysyms: reply.keysyms,
+            })
+        }
+
+        fn keycode(&self, keysym: Keysym) -> Result<Keycode, String> {
+            let index = self
+                .keysyms
+                .iter()
+                .position(|sym| *sym == keysym)
+                .ok_or_else(|| format!("键盘映射中没有按键 0x{keysym:04x}"))?;
+            Keycode::try_from(usize::from(self.min_keycode) + index / self.per_keycode)
+                .map_err(|_| format!("键盘映射中没有按键 0x{keysym:04x}"))
+        }
+    }
+
+    /// 通过 `/dev/uinput` 临时创建一个虚拟键盘发送按键，需要对该设备有写权限。
+    /// Wayland 不允许程序切换其他窗口的焦点，依赖本窗口隐藏后合成器把焦点还给之前的窗口。
+    fn uinput_paste(shift: bool, moves: usize) -> Result<(), String> {
+        let device = uinput::Device::create(&[KEY_LEFTCTRL, KEY_LEFTSHIFT, KEY_V, KEY_LEFT])?;
+        thread::sleep(UINPUT_SETTLE);
+        let shift_key = shift.then_some(KEY_LEFTSHIFT);
+        for (code, pressed) in key_sequence(KEY_LEFTCTRL, shift_key, KEY_V, KEY_LEFT, moves) {
+            device.key(code, pressed)?;
+        }
+        // 留出时间让合成器读完事件，设备销毁后未处理的按键会被丢弃。
+        thread::sleep(UINPUT_SETTLE);
+        Ok(())
+    }
+
+    mod uinput {
+        use std::fs::{File, OpenOptions};
+        use std::io::Write;
+        use std::mem::{size_of, zeroed};
+        use std::os::unix::fs::OpenOptionsExt;
+        use std::os::unix::io::AsRawFd;
+
+        const DEVICE_PATH: &str = "/dev/uinput";
+        const DEVICE_NAME: &[u8] = b"clipboard-history-paste";
+        const UI_SET_EVBIT: u64 = 0x4004_5564;
+        const UI_SET_KEYBIT: u64 = 0x4004_5565;
+        const UI_DEV_CREATE: u64 = 0x5501;
+        const UI_DEV_DESTROY: u64 = 0x5502;
+        const EV_SYN: u16 = 0x00;
+        const EV_KEY: u16 = 0x01;
+        const SYN_REPORT: u16 = 0;
+        const BUS_VIRTUAL: u16 = 0x06;
+
+        pub(super) struct Device {
+            file: File,
+        }
+
+        impl Device {
+            pub(super) fn create(keys: &[u16]) -> Result<Self, String> {
+                let file = OpenOptions::new()
+                    .write(true)
+                    .custom_flags(libc::O_NONBLOCK)
+                    .open(DEVICE_PATH)
+                    .map_err(|e| format!("打开 {DEVICE_PATH} 失败（需要写权限）: {e}"))?;
+                let device = Self { file };
+                device.ioctl(UI_SET_EVBIT, libc::c_int::from(EV_KEY))?;
+                for key in keys {
+                    device.ioctl(UI_SET_KEYBIT, libc::c_int::from(*key))?;
+                }
+
+                let mut setup: libc::uinput_user_dev = unsafe { zeroed() };
+                for (dst, src) in setup.name.iter_mut().zip(DEVICE_NAME) {
+                    *dst = *src as libc::c_char;
+                }
+                setup.id.bustype = BUS_VIRTUAL;
+                setup.id.vendor = 0x1;
+                setup.id.product = 0x1;
+                device.write_raw(&setup)?;
+                device.ioctl(UI_DEV_CREATE, 0)?;
+                Ok(device)
+            }
+
+            pub(super) fn key(&self, code: u16, pressed: bool) -> Result<(), String> {
+                self.event(EV_KEY, code, i32::from(pressed))?;
+                self.event(EV_SYN, SYN_REPORT, 0)
+            }
+
+            fn event(&self, kind: u16, code: u16, value: i32) -> Result<(), String> {
+                let mut event: libc::input_event = unsafe { zeroed() };
+                event.type_ = kind;
+                event.code = code;
+                event.value = value;
+                self.write_raw(&event)
+            }
+
+            fn ioctl(&self, request: u64, arg: libc::c_int) -> Result<(), String> {
+                let result = unsafe { libc::ioctl(self.file.as_raw_fd(), request as _, arg) };
+                if result < 0 {
+                    return Err(format!(
+                        "配置 uinput 设备失败: {}",
+                        std::io::Error::last_os_error()
+                    ));
+                }
+                Ok(())
+            }
+
+            fn write_raw<T>(&self, value: &T) -> Result<(), String> {
+                let bytes = unsafe {
+                    std::slice::from_raw_parts((value as *const T).cast::<u8>(), size_of::<T>())
+                };
+                (&self.file)
+                    .write_all(bytes)
+                    .map_err(|e| format!("写入 uinput 事件失败: {e}"))
+            }
+        }
+
+        impl Drop for Device {
+            fn drop(&mut self) {
+                unsafe {
+                    libc::ioctl(self.file.as_raw_fd(), UI_DEV_DESTROY as _);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(target_os = "windows")]
+mod platform {
+    use std::ffi::c_void;
+    use std::thread;
+    use std::time::Duration;
+
+    const VK_SHIFT: u8 = 0x10;
+    const VK_CONTROL: u8 = 0x11;
+    const VK_LEFT: u8 = 0x25;
+    const VK_V: u8 = 0x56;
+    const KEYEVENTF_KEYUP: u32 = 0x0002;
+    const FOCUS_DELAY: Duration = Duration::from_millis(50);
+
+    extern "system" {
+        fn IsWindow(hwnd: *mut c_void) -> i32;
+        fn SetForegroundWindow(hwnd: *mut c_void) -> i32;
+        fn keybd_event(vk: u8, scan: u8, flags: u32, extra: usize);
+    }
+
+    fn key(vk: u8, pressed: bool) {
+        let flags = if pressed { 0 } else { KEYEVENTF_KEYUP };
+        unsafe { keybd_event(vk, 0, flags, 0) };
+    }
+
+    pub(super) fn paste(target: Option<u64>, shift: bool, moves: usize) -> Result<(), String> {
+        if let Some(hwnd) = target.map(|id| id as usize as *mut c_void) {
+            // 本程序刚持有前台，系统允许把前台交还给之前的窗口。
+            if unsafe { IsWindow(hwnd) } != 0 && unsafe { SetForegroundWindow(hwnd) } != 0 {
+                thread::sleep(FOCUS_DELAY);
+            }
+        }
+        key(VK_CONTROL, true);
+        if shift {
+            key(VK_SHIFT, true);
+        }
+        key(VK_V, true);
+        key(VK_V, false);
+        if shift {
+            key(VK_SHIFT, false);
+        }
+        key(VK_CONTROL, false);
+        for _ in 0..moves {
+            key(VK_LEFT, true);
+            key(VK_LEFT, false);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "windows")))]
+mod platform {
+    pub(super) fn paste(_target: Option<u64>, _shift: bool, _moves: usize) -> Result<(), String> {
+        Err("当前平台暂不支持自动粘贴".to_string())
+    }
+}
@@ -0,0 +1,315 @@
+use crate::crypto::{self, Key};
+use crate::{
+    images, ipc, load_settings_from, now_ms, refresh_file_entries, resolve_data_dir, search,
+    storage, text_preview_for_log, write_item_to_clipboard, ClipboardItem,
+};
+use arboard::Clipboard;
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+
+/// 须与 `tauri.conf.json` 中的 `identifier` 一致，图形界面的 `app_data_dir` 即由它决定。
+const APP_IDENTIFIER: &str = "com.gml.clipboard-history";
+const PASSPHRASE_ENV: &str = "CLIPBOARD_HISTORY_PASSPHRASE";
+/// Linux 上剪贴板内容由持有者进程提供；程序未运行时 `copy` 会以此参数启动一个后台子进程持有内容直到被替换。
+const HOLD_FLAG: &str = "--hold";
+const DEFAULT_LIST_LIMIT: usize = 50;
+
+const COMMANDS: &[&str] = &[
+    "list", "search", "get", "copy", "delete", "export", "help", "--help", "-h",
+];
+
+const USAGE: &str = "用法: clipboard-history <命令> [参数]
+
+命令:
+  list [--limit N] [--type 类型] [--json]             按时间倒序列出历史
+  search <关键字> [--limit N] [--type 类型] [--json]  搜索历史
+  get <id> [--output 文件]                           输出条目内容，图片必须指定 --output
+  copy <id>                                          把条目复制到系统剪贴板
+  delete <id>                                        删除条目
+  export [--output 文件]                             以 JSON 导出全部历史
+
+类型: text / html / image / files
+加密存储需通过环境变量 CLIPBOARD_HISTORY_PASSPHRASE 提供口令。";
+
+#[derive(Default)]
+struct Args {
+    positional: Vec<String>,
+    limit: Option<usize>,
+    item_type: Option<String>,
+    output: Option<PathBuf>,
+    json: bool,
+    hold: bool,
+}
+
+fn parse_args(raw: &[String]) -> Result<Args, String> {
+    let mut args = Args::default();
+    let mut iter = raw.iter();
+    while let Some(arg) = iter.next() {
+        let mut value = |name: &str| {
+            iter.next()
+                .cloned()
+                .ok_or_else(|| format!("参数 {name} 缺少取值"))
+        };
+        match arg.as_str() {
+            "--limit" => {
+                args.limit = Some(
+                    value("--limit")?
+                        .parse()
+                        .map_err(|_| "--limit 需要正整数".to_string())?,
+                )
+            }
+            "--type" => args.item_type = Some(value("--type")?),
+            "--output" | "-o" => args.output = Some(PathBuf::from(value("--output")?)),
+            "--json" => args.json = true,
+            HOLD_FLAG => args.hold = true,
+            other if other.starts_with("--") => return Err(format!("未知参数: {other}")),
+            other => args.positional.push(other.to_string()),
+        }
+    }
+    Ok(args)
+}
+
+struct Context {
+    /// 应用数据目录，IPC 令牌与套接字位于其中。
+    root: PathBuf,
+    base: PathBuf,
+    key: Option<Key>,
+}
+
+impl Context {
+    fn open() -> Result<Self, String> {
+        let root = dirs::data_dir()
+            .ok_or_else(|| "无法定位应用数据目录".to_string())?
+            .join(APP_IDENTIFIER);
+        fs::create_dir_all(&root).map_err(|e| format!("创建应用数据目录失败: {e}"))?;
+        let settings = load_settings_from(&root)?;
+        let base = resolve_data_dir(&root, &settings)?;
+
+        let key = if crypto::is_enabled(&base) {
+            let passphrase = std::env::var(PASSPHRASE_ENV)
+                .map_err(|_| format!("存储已加密，请通过环境变量 {PASSPHRASE_ENV} 提供口令"))?;
+            Some(crypto::unlock(&base, &passphrase)?)
+        } else {
+            None
+        };
+        Ok(Self { root, base, key })
+    }
+
+    fn connection(&self) -> Result<rusqlite::Connection, String> {
+        storage::open(&self.base)
+    }
+
+    fn find(&self, id: &str) -> Result<ClipboardItem, String> {
+        let mut item = storage::find_item(&self.connection()?, self.key.as_ref(), id)?
+            .ok_or_else(|| format!("未找到历史项: {id}"))?;
+        refresh_file_entries(&mut item);
+        Ok(item)
+    }
+
+    /// 未过期的候选条目；命令行只读取，不替图形界面执行清理。
+    fn candidates(&self, item_type: Option<&str>) -> Result<Vec<ClipboardItem>, String> {
+        let now = now_ms();
+        let mut items = storage::load_filtered_items(
+            &self.connection()?,
+            self.key.as_ref(),
+            item_type,
+            false,
+            None,
+            None,
+        )?;
+        items.retain(|item| item.expires_at.is_none_or(|at| at > now));
+        items.iter_mut().for_each(refresh_file_entries);
+        Ok(items)
+    }
+}
+
+/// 第一个参数是已知子命令时执行并返回退出码，否则返回 `None`。
+pub(crate) fn run(raw: Vec<String>) -> Option<i32> {
+    let command = raw.first()?.clone();
+    if !COMMANDS.contains(&command.as_str()) {
+        return None;
+    }
+    attach_console();
+
+    match execute(&command, &raw[1..]) {
+        Ok(()) => Some(0),
+        Err(err) => {
+            eprintln!("错误: {err}");
+            Some(1)
+        }
+    }
+}
+
+fn execute(command: &str, raw: &[String]) -> Result<(), String> {
+    if matches!(command, "help" | "--help" | "-h") {
+        return write_stdout(format!("{USAGE}\n").as_bytes());
+    }
+
+    let args = parse_args(raw)?;
+    let ctx = Context::open()?;
+    let id_arg = || {
+        args.positional
+            .first()
+            .map(String::as_str)
+            .ok_or_else(|| format!("{command} 需要指定条目 id"))
+    };
+
+    match command {
+        "list" => {
+            let mut items = ctx.candidates(args.item_type.as_deref())?;
+            items.truncate(args.limit.unwrap_or(DEFAULT_LIST_LIMIT));
+            print_items(&items, args.json)
+        }
+        "search" => {
+            let query = args.positional.join(" ");
+            if query.trim().is_empty() {
+                return Err("search 需要指定关键字".to_string());
+            }
+            let candidates = ctx.candidates(args.item_type.as_deref())?;
+            let items: Vec<ClipboardItem> = search::rank(candidates, &query, args.limit)
+                .into_iter()
+                .map(|result| result.item)
+                .collect();
+            print_items(&items, args.json)
+        }
+        "get" => {
+            let item = ctx.find(id_arg()?)?;
+            let bytes = item_content(&ctx, &item)?;
+            match &args.output {
+                Some(path) => fs::write(path, bytes).map_err(|e| format!("写入文件失败: {e}")),
+                None if item.item_type == "image" => {
+                    Err("图片条目请使用 --output 指定输出文件".to_string())
+                }
+                None => write_stdout(&bytes),
+            }
+        }
+        "copy" => {
+            let id = id_arg()?;
+            // 程序在运行时交给它写入剪贴板：它会记下指纹不把这次复制当作新内容，Linux 上也由它持有内容。
+            if !args.hold {
+                let params = serde_json::json!({ "id": id });
+                if let Some(result) = ipc::call(&ctx.root, "copy_history_item", params) {
+                    return result.map(|_| ());
+                }
+            }
+            copy_item(&ctx, id, args.hold)
+        }
+        "delete" => {
+            let id = id_arg()?;
+            let _lock = storage::lock_dir(&ctx.base)?;
+            let conn = ctx.connection()?;
+            let removed = storage::delete_item(&conn, ctx.key.as_ref(), id)?
+                .ok_or_else(|| format!("未找到历史项: {id}"))?;
+            if let Some(rel) = removed.image_path.as_deref() {
+                images::release(&conn, &ctx.base, rel)?;
+            }
+            println!("已删除 {id}");
+            Ok(())
+        }
+        "export" => {
+            let items = storage::load_items(&ctx.connection()?, ctx.key.as_ref())?;
+            let json = to_json(&items)?;
+            match &args.output {
+                Some(path) => fs::write(path, json).map_err(|e| format!("写入文件失败: {e}")),
+                None => write_stdout(&json),
+            }
+        }
+        _ => Err(format!("未知命令: {command}\n\n{USAGE}")),
+    }
+}
+
+fn item_preview(item: &ClipboardItem) -> String {
+    match item.item_type.as_str() {
+        "image" => item.image_path.clone().unwrap_or_default(),
+        _ => text_preview_for_log(item.text.as_deref().unwrap_or_default(), 80),
+    }
+}
+
+/// 默认每行输出 `id<TAB>类型<TAB>预览`，便于 rofi/dmenu/fzf 等工具直接使用。
+fn print_items(items: &[ClipboardItem], json: bool) -> Result<(), String> {
+    if json {
+        return write_stdout(&to_json(&items)?);
+    }
+    let mut out = String::new();
+    for item in items {
+        out.push_str(&format!(
+            "{}\t{}\t{}\n",
+            item.id,
+            item.item_type,
+            item_preview(item)
+        ));
+    }
+    write_stdout(out.as_bytes())
+}
+
+fn item_content(ctx: &Context, item: &ClipboardItem) -> Result<Vec<u8>, String> {
+    match item.item_type.as_str() {
+        "image" => {
+            let rel = item
+                .image_path
+                .as_deref()
+                .ok_or_else(|| "图片路径缺失".to_string())?;
+            crypto::read_file(ctx.key.as_ref(), &ctx.base.join(rel))
+        }
+        _ => Ok(item.text.clone().unwrap_or_default().into_bytes()),
+    }
+}
+
+fn to_json(value: &impl serde::Serialize) -> Result<Vec<u8>, String> {
+    let mut json = serde_json::to_vec_pretty(value).map_err(|e| format!("序列化历史失败: {e}"))?;
+    json.push(b'\n');
+    Ok(json)
+}
+
+fn write_stdout(bytes: &[u8]) -> Result<(), String> {
+    let mut stdout = std::io::stdout().lock();
+    stdout
+        .write_all(bytes)
+        .and_then(|_| stdout.flush())
+        .map_err(|e| format!("输出失败: {e}"))
+}
+
+#[cfg(target_os = "linux")]
+fn copy_item(ctx: &Context, id: &str, hold: bool) -> Result<(), String> {
+    use arboard::SetExtLinux;
+    use std::process::{Command, Stdio};
+
+    let item = ctx.find(id)?;
+    if !hold {
+        let exe = std::env::current_exe().map_err(|e| format!("定位可执行文件失败: {e}"))?;
+        Command::new(exe)
+            .args(["copy", id, HOLD_FLAG])
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|e| format!("启动剪贴板持有进程失败: {e}"))?;
+        return Ok(());
+    }
+
+    let mut clipboard = Clipboard::new().map_err(|e| format!("访问系统剪贴板失败: {e}"))?;
+    write_item_to_clipboard(clipboard.set().wait(), &item, &ctx.base, ctx.key.as_ref())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn copy_item(ctx: &Context, id: &str, _hold: bool) -> Result<(), String> {
+    let item = ctx.find(id)?;
+    let mut clipboard = Clipboard::new().map_err(|e| format!("访问系统剪贴板失败: {e}"))?;
+    write_item_to_clipboard(clipboard.set(), &item, &ctx.base, ctx.key.as_ref())
+}
+
+/// 发布版在 Windows 上是 GUI 子系统程序，需要挂到父进程的控制台才能输出。
+#[cfg(target_os = "windows")]
+fn attach_console() {
+    extern "system" {
+        fn AttachConsole(pid: u32) -> i32;
+    }
+    const ATTACH_PARENT_PROCESS: u32 = u32::MAX;
+    unsafe {
+        AttachConsole(ATTACH_PARENT_PROCESS);
+    }
+}
+
+#[cfg(not(target_os = "windows"))]
+fn attach_console() {}
@@ -0,0 +1,251 @@
+use argon2::{Algorithm, Argon2, Params, Version};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Read};
+use std::path::Path;
+
+pub(crate) const KEY_FILE_NAME: &str = "encryption.json";
+
+/// 密文格式：魔数 + 24 字节 nonce + XChaCha20-Poly1305 密文。
+const SEALED_MAGIC: &[u8; 4] = b"CHE1";
+const NONCE_LEN: usize = 24;
+const VERIFIER_PLAINTEXT: &[u8] = b"clipboard-history";
+
+#[derive(Clone)]
+pub(crate) struct Key([u8; 32]);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct KeyFile {
+    version: u32,
+    kdf: String,
+    salt: String,
+    m_cost: u32,
+    t_cost: u32,
+    p_cost: u32,
+    verifier: String,
+}
+
+pub(crate) fn is_enabled(data_dir: &Path) -> bool {
+    data_dir.join(KEY_FILE_NAME).exists()
+}
+
+fn derive_key(passphrase: &str, key_file: &KeyFile) -> Result<Key, String> {
+    let salt = BASE64
+        .decode(&key_file.salt)
+        .map_err(|e| format!("密钥文件损坏: {e}"))?;
+    let params = Params::new(key_file.m_cost, key_file.t_cost, key_file.p_cost, Some(32))
+        .map_err(|e| format!("密钥参数无效: {e}"))?;
+    let mut key = [0u8; 32];
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+        .hash_password_into(passphrase.as_bytes(), &salt, &mut key)
+        .map_err(|e| format!("派生密钥失败: {e}"))?;
+    Ok(Key(key))
+}
+
+/// 生成新的盐和密钥并写入密钥文件；密钥文件中只保存用于校验口令的密文，不保存密钥本身。
+pub(crate) fn create_key_file(data_dir: &Path, passphrase: &str) -> Result<Key, String> {
+    if passphrase.chars().count() < 8 {
+        return Err("口令至少需要 8 个字符".to_string());
+    }
+
+    let mut salt = [0u8; 16];
+    OsRng.fill_bytes(&mut salt);
+    let mut key_file = KeyFile {
+        version: 1,
+        kdf: "argon2id".to_string(),
+        salt: BASE64.encode(salt),
+        m_cost: 64 * 1024,
+        t_cost: 3,
+        p_cost: 1,
+        verifier: String::new(),
+    };
+    let key = derive_key(passphrase, &key_file)?;
+    key_file.verifier = BASE64.encode(seal(&key, VERIFIER_PLAINTEXT)?);
+
+    let json =
+        serde_json::to_string_pretty(&key_file).map_err(|e| format!("序列化密钥文件失败: {e}"))?;
+    fs::write(data_dir.join(KEY_FILE_NAME), json).map_err(|e| format!("写入密钥文件失败: {e}"))?;
+    Ok(key)
+}
+
+pub(crate) fn unlock(data_dir: &Path, passphrase: &str) -> Result<Key, String> {
+    let raw = fs::read_to_string(data_dir.join(KEY_FILE_NAME))
+        .map_err(|e| format!("读取密钥文件失败: {e}"))?;
+    let key_file: KeyFile =
+        serde_json::from_str(&raw).map_err(|e| format!("解析密钥文件失败: {e}"))?;
+    let key = derive_key(passphrase, &key_file)?;
+    let verifier = BASE64
+        .decode(&key_file.verifier)
+        .map_err(|e| format!("密钥文件损坏: {e}"))?;
+    match open(&key, &verifier) {
+        Ok(plain) if plain == VERIFIER_PLAINTEXT => Ok(key),
+        _ => Err("口令错误".to_string()),
+    }
+}
+
+pub(crate) fn is_sealed(data: &[u8]) -> bool {
+    data.len() > SEALED_MAGIC.len() + NONCE_LEN && data.starts_with(SEALED_MAGIC)
+}
+
+pub(crate) fn seal(key: &Key, plaintext: &[u8]) -> Result<Vec<u8>, String> {
+    let cipher = XChaCha20Poly1305::new((&key.0).into());
+    let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let ciphertext = cipher
+        .encrypt(&nonce, plaintext)
+        .map_err(|_| "加密失败".to_string())?;
+
+    let mut sealed = Vec::with_capacity(SEALED_MAGIC.len() + NONCE_LEN + ciphertext.len());
+    sealed.extend_from_slice(SEALED_MAGIC);
+    sealed.extend_from_slice(&nonce);
+    sealed.extend_from_slice(&ciphertext);
+    Ok(sealed)
+}
+
+pub(crate) fn open(key: &Key, sealed: &[u8]) -> Result<Vec<u8>, String> {
+    if !is_sealed(sealed) {
+        return Err("数据不是加密格式".to_string());
+    }
+    let (nonce, ciphertext) = sealed[SEALED_MAGIC.len()..].split_at(NONCE_LEN);
+    XChaCha20Poly1305::new((&key.0).into())
+        .decrypt(XNonce::from_slice(nonce), ciphertext)
+        .map_err(|_| "解密失败，数据可能已损坏".to_string())
+}
+
+/// 加密模式下用于去重查找的哈希：对明文哈希做 HMAC-SHA256，避免在库中留下可被字典比对的明文哈希。
+pub(crate) fn lookup_hash(key: &Key, content_hash: &str) -> String {
+    let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(&key.0).expect("HMAC 接受任意长度的密钥");
+    mac.update(content_hash.as_bytes());
+    format!("{:x}", mac.finalize().into_bytes())
+}
+
+/// 读取图片文件，若为密文则解密；未加密的旧文件原样返回，便于中断后的迁移继续进行。
+pub(crate) fn read_file(key: Option<&Key>, path: &Path) -> Result<Vec<u8>, String> {
+    let bytes = fs::read(path).map_err(|e| format!("读取文件失败: {e}"))?;
+    if !is_sealed(&bytes) {
+        return Ok(bytes);
+    }
+    let key = key.ok_or_else(|| "存储已加密，请先解锁".to_string())?;
+    open(key, &bytes)
+}
+
+pub(crate) fn write_file(key: Option<&Key>, path: &Path, bytes: &[u8]) -> Result<(), String> {
+    let data = match key {
+        Some(key) => seal(key, bytes)?,
+        None => bytes.to_vec(),
+    };
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    fs::write(&tmp, data).map_err(|e| format!("写入文件失败: {e}"))?;
+    fs::rename(&tmp, path).map_err(|e| format!("写入文件失败: {e}"))
+}
+
+/// 删除前先用零覆盖文件内容，尽量不在磁盘上留下明文（写时复制的文件系统和 SSD 上无法完全保证）。
+pub(crate) fn shred_file(path: &Path) -> Result<(), String> {
+    let len = fs::metadata(path)
+        .map_err(|e| format!("读取文件失败: {e}"))?
+        .len();
+    let mut file = OpenOptions::new()
+        .write(true)
+        .open(path)
+        .map_err(|e| format!("覆盖文件失败: {e}"))?;
+    io::copy(&mut io::repeat(0).take(len), &mut file)
+        .and_then(|_| file.sync_all())
+        .map_err(|e| format!("覆盖文件失败: {e}"))?;
+    drop(file);
+    fs::remove_file(path).map_err(|e| format!("删除文件失败: {e}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn test_key(byte: u8) -> Key {
+        Key([byte; 32])
+    }
+
+    /// 每个测试使用独立的临时目录，文件名带进程号避免同时运行的测试进程互相覆盖。
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "clipboard-history-crypto-{}-{name}",
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn seal_round_trips() {
+        let key = test_key(1);
+        let sealed = seal(&key, b"hello").unwrap();
+        assert!(is_sealed(&sealed));
+        assert_ne!(&sealed[SEALED_MAGIC.len() + NONCE_LEN..], b"hello");
+        assert_eq!(open(&key, &sealed).unwrap(), b"hello");
+        // 每次加密使用新的 nonce。
+        assert_ne!(seal(&key, b"hello").unwrap(), sealed);
+    }
+
+    #[test]
+    fn open_rejects_wrong_key_and_tampering() {
+        let sealed = seal(&test_key(1), b"hello").unwrap();
+        assert!(open(&test_key(2), &sealed).is_err());
+
+        let mut tampered = sealed.clone();
+        *tampered.last_mut().unwrap() ^= 1;
+        assert!(open(&test_key(1), &tampered).is_err());
+        assert!(open(&test_key(1), b"plain text").is_err());
+    }
+
+    #[test]
+    fn unlock_checks_passphrase() {
+        let dir = temp_dir("unlock");
+        assert!(create_key_file(&dir, "short").is_err());
+        let key = create_key_file(&dir, "correct horse").unwrap();
+        assert!(is_enabled(&dir));
+
+        let unlocked = unlock(&dir, "correct horse").unwrap();
+        assert_eq!(unlocked.0, key.0);
+        assert_eq!(
+            unlock(&dir, "wrong horse").err().as_deref(),
+            Some("口令错误")
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn lookup_hash_is_hmac_sha256() {
+        let key = Key(std::array::from_fn(|i| i as u8));
+        assert_eq!(
+            lookup_hash(&key, "abc"),
+            "f0133729c4163dede81e21cd47839256da58171238c8a0d874397c73b14e1e47"
+        );
+        assert_ne!(
+            lookup_hash(&test_key(1), "abc"),
+            lookup_hash(&test_key(2), "abc")
+        );
+    }
+
+    #[test]
+    fn files_round_trip_and_shred() {
+        let dir = temp_dir("files");
+        let path = dir.join("image.png");
+        let key = test_key(3);
+        write_file(Some(&key), &path, b"png bytes").unwrap();
+        assert!(is_sealed(&fs::read(&path).unwrap()));
+        assert_eq!(read_file(Some(&key), &path).unwrap(), b"png bytes");
+        assert!(read_file(None, &path).is_err());
+
+        shred_file(&path).unwrap();
+        assert!(!path.exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
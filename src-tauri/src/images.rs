@@ -0,0 +1,158 @@
+use crate::crypto::{self, Key};
+use crate::{hash_bytes, storage, HISTORY_FILE_NAME, IMAGE_DIR_NAME};
+use rusqlite::Connection;
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// 把记录中的相对路径解析为图片目录内的绝对路径；不在图片目录内的路径一律拒绝，避免误删其他文件。
+pub(crate) fn resolve(base: &Path, image_path: &str) -> Option<PathBuf> {
+    let normalized = image_path.replace('\\', "/");
+    let name = normalized.strip_prefix(&format!("{IMAGE_DIR_NAME}/"))?;
+    if name.is_empty() || name.contains('/') || name == "." || name == ".." {
+        return None;
+    }
+    Some(base.join(IMAGE_DIR_NAME).join(name))
+}
+
+/// 把 PNG 按内容哈希写入图片目录（加密时改用带密钥的哈希命名，避免文件名泄露内容），
+/// 同名文件已存在时不重复写入。返回相对路径以及是否新写入了文件。
+pub(crate) fn store(
+    base: &Path,
+    key: Option<&Key>,
+    content_hash: &str,
+    png_bytes: &[u8],
+) -> Result<(String, bool), String> {
+    let name_hash = match key {
+        Some(key) => crypto::lookup_hash(key, content_hash),
+        None => content_hash.to_string(),
+    };
+    let file_name = format!("{hash}.png", hash = &name_hash[0..24]);
+    let dir = base.join(IMAGE_DIR_NAME);
+    fs::create_dir_all(&dir).map_err(|e| format!("创建图片目录失败: {e}"))?;
+    let full_path = dir.join(&file_name);
+    let is_new_file = !full_path.exists();
+    if is_new_file {
+        crypto::write_file(key, &full_path, png_bytes).map_err(|e| format!("保存图片失败: {e}"))?;
+    }
+    Ok((format!("{IMAGE_DIR_NAME}/{file_name}"), is_new_file))
+}
+
+/// 启用加密时处理图片目录中尚未加密的文件：按带密钥的哈希重新命名并加密写入，再改写引用它的记录，
+/// 最后覆盖删除明文文件。中途中断后重新执行会接着完成。返回处理的文件数。
+pub(crate) fn encrypt_existing(
+    conn: &mut Connection,
+    base: &Path,
+    key: &Key,
+) -> Result<usize, String> {
+    let dir = base.join(IMAGE_DIR_NAME);
+    if !dir.exists() {
+        return Ok(0);
+    }
+
+    let mut count = 0usize;
+    let entries = fs::read_dir(&dir).map_err(|e| format!("读取图片目录失败: {e}"))?;
+    for entry in entries {
+        let path = entry.map_err(|e| format!("读取目录项失败: {e}"))?.path();
+        if !path.is_file() || path.extension().is_some_and(|ext| ext == "tmp") {
+            continue;
+        }
+        let bytes = fs::read(&path).map_err(|e| format!("读取图片失败: {e}"))?;
+        if crypto::is_sealed(&bytes) {
+            continue;
+        }
+        let Some(name) = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+        else {
+            continue;
+        };
+        // 图片条目的内容哈希就是 PNG 字节的哈希，与 `store` 写入新图片时的命名一致。
+        let (new_path, _) = store(base, Some(key), &hash_bytes(&bytes), &bytes)?;
+        storage::rename_image_path(
+            conn,
+            Some(key),
+            &format!("{IMAGE_DIR_NAME}/{name}"),
+            &new_path,
+        )?;
+        crypto::shred_file(&path)?;
+        count += 1;
+    }
+    Ok(count)
+}
+
+/// 图片文件按内容哈希命名，多个条目可能引用同一文件。引用计数以数据库中的 `image_path` 为准，
+/// 只有最后一个引用被删除后才删除文件。返回是否删除了文件。
+pub(crate) fn release(conn: &Connection, base: &Path, image_path: &str) -> Result<bool, String> {
+    if storage::image_ref_count(conn, image_path)? > 0 {
+        return Ok(false);
+    }
+    let Some(path) = resolve(base, image_path) else {
+        return Ok(false);
+    };
+    if !path.exists() {
+        return Ok(false);
+    }
+    fs::remove_file(&path).map_err(|e| format!("删除图片失败: {e}"))?;
+    Ok(true)
+}
+
+pub(crate) fn release_all(
+    conn: &Connection,
+    base: &Path,
+    image_paths: &[String],
+) -> Result<usize, String> {
+    let mut removed = 0usize;
+    for image_path in image_paths {
+        if release(conn, base, image_path)? {
+            removed += 1;
+        }
+    }
+    Ok(removed)
+}
+
+/// 图片目录中没有任何记录引用的文件（返回相对路径）。调用方需持有历史锁，避免把刚写入、尚未入库的图片当作孤立文件。
+pub(crate) fn orphan_files(conn: &Connection, base: &Path) -> Result<Vec<String>, String> {
+    // 旧版历史文件尚未导入（例如加密存储未解锁）时，其中引用的图片还不在数据库里，不能当作孤立文件。
+    if base.join(HISTORY_FILE_NAME).exists() {
+        return Ok(Vec::new());
+    }
+    let dir = base.join(IMAGE_DIR_NAME);
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let referenced: HashSet<String> = storage::image_rows(conn)?
+        .into_iter()
+        .map(|row| row.image_path)
+        .chain(storage::snippet_image_paths(conn)?)
+        .map(|path| path.replace('\\', "/"))
+        .collect();
+    let mut orphans = Vec::new();
+    let entries = fs::read_dir(&dir).map_err(|e| format!("读取图片目录失败: {e}"))?;
+    for entry in entries {
+        let path = entry.map_err(|e| format!("读取目录项失败: {e}"))?.path();
+        if !path.is_file() || path.extension().is_some_and(|ext| ext == "tmp") {
+            continue;
+        }
+        let Some(name) = path.file_name().map(|name| name.to_string_lossy()) else {
+            continue;
+        };
+        let rel = format!("{IMAGE_DIR_NAME}/{name}");
+        if !referenced.contains(&rel) {
+            orphans.push(rel);
+        }
+    }
+    orphans.sort();
+    Ok(orphans)
+}
+
+pub(crate) fn remove_orphans(conn: &Connection, base: &Path) -> Result<usize, String> {
+    let orphans = orphan_files(conn, base)?;
+    for rel in &orphans {
+        if let Some(path) = resolve(base, rel) {
+            fs::remove_file(path).map_err(|e| format!("删除图片失败: {e}"))?;
+        }
+    }
+    Ok(orphans.len())
+}
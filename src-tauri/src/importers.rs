@@ -0,0 +1,1082 @@
+use crate::archive::{self, ImportReport};
+use crate::{
+    data_dir, ensure_storage_layout, html_to_plain_text, image_item_in, lock_history, now_ms,
+    storage_key, to_files_item, to_html_item, to_text_item, AppState,
+};
+use chrono::DateTime;
+use flate2::read::ZlibDecoder;
+use regex::Regex;
+use rusqlite::{params, Connection, OpenFlags};
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Manager};
+
+pub(crate) const SOURCE_COPYQ: &str = "copyq";
+pub(crate) const SOURCE_CLIPMAN: &str = "clipman";
+pub(crate) const SOURCE_GPASTE: &str = "gpaste";
+pub(crate) const SOURCE_CLIPHIST: &str = "cliphist";
+pub(crate) const SOURCE_DITTO: &str = "ditto";
+
+const SOURCES: &[&str] = &[
+    SOURCE_COPYQ,
+    SOURCE_CLIPMAN,
+    SOURCE_GPASTE,
+    SOURCE_CLIPHIST,
+    SOURCE_DITTO,
+];
+
+const PNG_MAGIC: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+/// 从其他剪贴板管理器读出的一条内容，转换成 `ClipboardItem` 前的中间形式。
+enum Content {
+    Text(String),
+    Html { html: String, text: Option<String> },
+    Png(Vec<u8>),
+    Files(Vec<PathBuf>),
+}
+
+struct Imported {
+    content: Content,
+    /// 毫秒时间戳；来源没有记录时间时为空，按原顺序补齐。
+    time: Option<u64>,
+    favorite: bool,
+}
+
+impl Imported {
+    fn new(content: Content) -> Self {
+        Self {
+            content,
+            time: None,
+            favorite: false,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ImportSource {
+    id: &'static str,
+    path: Option<String>,
+    found: bool,
+}
+
+/// 列出支持的来源及其默认数据位置是否存在，供界面提示。
+pub(crate) fn sources() -> Vec<ImportSource> {
+    SOURCES
+        .iter()
+        .map(|id| {
+            let path = default_path(id);
+            ImportSource {
+                id,
+                found: path.as_ref().is_some_and(|path| path.exists()),
+                path: path.map(|path| path.to_string_lossy().to_string()),
+            }
+        })
+        .collect()
+}
+
+/// 各工具默认的数据位置；可以在调用时用 `path` 覆盖。
+pub(crate) fn default_path(source: &str) -> Option<PathBuf> {
+    match source {
+        SOURCE_COPYQ => dirs::config_dir().map(|dir| dir.join("copyq")),
+        SOURCE_CLIPMAN => dirs::data_dir().map(|dir| dir.join("clipman.json")),
+        SOURCE_GPASTE => dirs::data_dir().map(|dir| dir.join("gpaste").join("history.xml")),
+        SOURCE_CLIPHIST => dirs::cache_dir().map(|dir| dir.join("cliphist").join("db")),
+        SOURCE_DITTO => dirs::config_dir().map(|dir| dir.join("Ditto").join("Ditto.db")),
+        _ => None,
+    }
+}
+
+/// 读取指定剪贴板管理器的数据并合并进历史；无法识别或无法还原的条目计入 `skipped`。
+pub(crate) fn import(
+    app: &AppHandle,
+    source: &str,
+    path: Option<&Path>,
+) -> Result<ImportReport, String> {
+    let path = match path {
+        Some(path) => path.to_path_buf(),
+        None => default_path(source).ok_or_else(|| format!("未知的导入来源: {source}"))?,
+    };
+    if !path.exists() {
+        return Err(format!("未找到数据: {}", path.display()));
+    }
+
+    let mut report = ImportReport::default();
+    let entries = match source {
+        SOURCE_COPYQ => read_copyq(&path, &mut report)?,
+        SOURCE_CLIPMAN => read_clipman(&path)?,
+        SOURCE_GPASTE => read_gpaste(&path, &mut report)?,
+        SOURCE_CLIPHIST => read_cliphist(&path, &mut report)?,
+        SOURCE_DITTO => read_ditto(&path, &mut report)?,
+        other => return Err(format!("未知的导入来源: {other}")),
+    };
+
+    ensure_storage_layout(app)?;
+    let state = app.state::<AppState>();
+    let _guard = lock_history(app, &state)?;
+    let base = data_dir(app)?;
+    let key = storage_key(app)?;
+
+    // 条目按从旧到新排列；没有时间的按顺序排在导入时刻之前，保持原有先后。
+    let now = now_ms();
+    let total = entries.len() as u64;
+    let mut items = Vec::with_capacity(entries.len());
+    for (idx, entry) in entries.into_iter().enumerate() {
+        let time = entry
+            .time
+            .unwrap_or_else(|| now.saturating_sub(total - idx as u64));
+        let mut item = match entry.content {
+            Content::Text(text) if text.trim().is_empty() => {
+                report.skipped += 1;
+                continue;
+            }
+            Content::Text(text) => to_text_item(text),
+            Content::Html { html, text } => {
+                let text = text.unwrap_or_else(|| html_to_plain_text(&html));
+                to_html_item(html, text)
+            }
+            Content::Files(paths) if paths.is_empty() => {
+                report.skipped += 1;
+                continue;
+            }
+            Content::Files(paths) => to_files_item(&paths),
+            Content::Png(bytes) => image_item_in(&base, key.as_ref(), bytes)?,
+        };
+        item.created_at = time;
+        item.updated_at = time;
+        item.is_favorite = entry.favorite;
+        item.source_app = Some(source.to_string());
+        items.push(item);
+    }
+
+    archive::merge_imported(app, &base, key.as_ref(), items, &mut report)?;
+    Ok(report)
+}
+
+fn read_clipman(path: &Path) -> Result<Vec<Imported>, String> {
+    let raw = fs::read(path).map_err(|e| format!("读取 Clipman 历史失败: {e}"))?;
+    let texts: Vec<String> =
+        serde_json::from_slice(&raw).map_err(|e| format!("解析 Clipman 历史失败: {e}"))?;
+    Ok(texts
+        .into_iter()
+        .map(|text| Imported::new(Content::Text(text)))
+        .collect())
+}
+
+/// 时间戳可能是秒、毫秒、微秒或 ISO 8601 字符串。
+fn parse_time(raw: &str) -> Option<u64> {
+    if let Ok(value) = raw.trim().parse::<u64>() {
+        return Some(match value {
+            v if v > 100_000_000_000_000 => v / 1000,
+            v if v > 100_000_000_000 => v,
+            v => v * 1000,
+        });
+    }
+    DateTime::parse_from_rfc3339(raw.trim())
+        .ok()
+        .and_then(|time| u64::try_from(time.timestamp_millis()).ok())
+}
+
+fn unescape_xml(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+fn uri_to_path(uri: &str) -> Option<PathBuf> {
+    let rest = uri.trim().strip_prefix("file://")?;
+    let mut bytes = Vec::with_capacity(rest.len());
+    let mut iter = rest.bytes();
+    while let Some(b) = iter.next() {
+        if b == b'%' {
+            let hex: Vec<u8> = iter.by_ref().take(2).collect();
+            let value = std::str::from_utf8(&hex)
+                .ok()
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())?;
+            bytes.push(value);
+        } else {
+            bytes.push(b);
+        }
+    }
+    Some(PathBuf::from(String::from_utf8(bytes).ok()?))
+}
+
+fn parse_uri_list(text: &str) -> Vec<PathBuf> {
+    text.lines()
+        .filter(|line| !line.starts_with('#'))
+        .filter_map(uri_to_path)
+        .collect()
+}
+
+/// GPaste 的 `history.xml`：每个 `<item kind=...>` 含一个 `<value>`，内容通常放在 CDATA 中，
+/// 图片条目的值是 GPaste 目录中的图片路径。密码条目一律跳过。
+fn read_gpaste(path: &Path, report: &mut ImportReport) -> Result<Vec<Imported>, String> {
+    let raw = fs::read_to_string(path).map_err(|e| format!("读取 GPaste 历史失败: {e}"))?;
+    let item_re = Regex::new(r"(?s)<item\s([^>]*)>\s*<value>(.*?)</value>")
+        .map_err(|e| format!("解析 GPaste 历史失败: {e}"))?;
+    let attr_re =
+        Regex::new(r#"(\w+)="([^"]*)""#).map_err(|e| format!("解析 GPaste 历史失败: {e}"))?;
+
+    let mut entries = Vec::new();
+    for caps in item_re.captures_iter(&raw) {
+        let mut kind = "";
+        let mut time = None;
+        for attr in attr_re.captures_iter(&caps[1]) {
+            match &attr[1] {
+                "kind" => kind = attr.get(2).map_or("", |m| m.as_str()),
+                "date" => time = parse_time(&attr[2]),
+                _ => {}
+            }
+        }
+
+        // 内容中的 "]]>" 会被拆成相邻的两个 CDATA 段。
+        let value = caps[2].trim();
+        let value = match value
+            .strip_prefix("<![CDATA[")
+            .and_then(|v| v.strip_suffix("]]>"))
+        {
+            Some(cdata) => cdata.replace("]]><![CDATA[", ""),
+            None => unescape_xml(value),
+        };
+
+        let content = match kind {
+            "Text" => Content::Text(value),
+            "Uris" => Content::Files(parse_uri_list(&value)),
+            "Image" => match fs::read(value.trim()) {
+                Ok(bytes) if bytes.starts_with(PNG_MAGIC) => Content::Png(bytes),
+                _ => {
+                    report.skipped += 1;
+                    continue;
+                }
+            },
+            _ => {
+                report.skipped += 1;
+                continue;
+            }
+        };
+        entries.push(Imported {
+            content,
+            time,
+            favorite: false,
+        });
+    }
+    // GPaste 把最新的条目写在最前面。
+    entries.reverse();
+    Ok(entries)
+}
+
+/// cliphist 把原始内容存在 bbolt 数据库的 `b` 桶中，键为大端序递增 id。
+fn read_cliphist(path: &Path, report: &mut ImportReport) -> Result<Vec<Imported>, String> {
+    let raw = fs::read(path).map_err(|e| format!("读取 cliphist 数据库失败: {e}"))?;
+    let db = bolt::Db::open(&raw)?;
+    let mut rows = db.bucket_entries(b"b")?;
+    rows.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let mut entries = Vec::new();
+    for (_, value) in rows {
+        if value.starts_with(PNG_MAGIC) {
+            entries.push(Imported::new(Content::Png(value)));
+            continue;
+        }
+        match String::from_utf8(value) {
+            Ok(text) if text.starts_with("file://") => {
+                entries.push(Imported::new(Content::Files(parse_uri_list(&text))));
+            }
+            Ok(text) => entries.push(Imported::new(Content::Text(text))),
+            // 其他格式的图片（JPEG 等）与二进制内容无法还原。
+            Err(_) => report.skipped += 1,
+        }
+    }
+    Ok(entries)
+}
+
+/// Ditto 的 SQLite 数据库：`Main` 表每行一条记录，`Data` 表保存该记录的各个剪贴板格式。
+fn read_ditto(path: &Path, report: &mut ImportReport) -> Result<Vec<Imported>, String> {
+    let conn = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)
+        .map_err(|e| format!("打开 Ditto 数据库失败: {e}"))?;
+    let mut main = conn
+        .prepare(
+            "SELECT lID, lDate, mText, lDontAutoDelete FROM Main
+             WHERE bIsGroup = 0 ORDER BY lDate ASC, lID ASC",
+        )
+        .map_err(|e| format!("读取 Ditto 数据库失败: {e}"))?;
+    let rows = main
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, Option<i64>>(1)?,
+                row.get::<_, Option<String>>(2)?,
+                row.get::<_, Option<i64>>(3)?,
+            ))
+        })
+        .map_err(|e| format!("读取 Ditto 数据库失败: {e}"))?
+        .collect::<rusqlite::Result<Vec<_>>>()
+        .map_err(|e| format!("读取 Ditto 数据库失败: {e}"))?;
+    let mut data = conn
+        .prepare("SELECT strClipBoardFormat, ooData FROM Data WHERE lParentID = ?1")
+        .map_err(|e| format!("读取 Ditto 数据库失败: {e}"))?;
+
+    let mut entries = Vec::new();
+    for (id, date, summary, dont_delete) in rows {
+        let formats = data
+            .query_map(params![id], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, Vec<u8>>(1)?))
+            })
+            .map_err(|e| format!("读取 Ditto 数据库失败: {e}"))?
+            .collect::<rusqlite::Result<Vec<_>>>()
+            .map_err(|e| format!("读取 Ditto 数据库失败: {e}"))?;
+        let find = |name: &str| {
+            formats
+                .iter()
+                .find(|(format, _)| format.eq_ignore_ascii_case(name))
+                .map(|(_, bytes)| bytes.as_slice())
+        };
+
+        let text = find("CF_UNICODETEXT")
+            .map(decode_utf16le)
+            .or_else(|| find("CF_TEXT").map(decode_ansi));
+        let content = if let Some(files) = find("CF_HDROP").map(parse_hdrop) {
+            Content::Files(files)
+        } else if let Some(png) = find("PNG").filter(|bytes| bytes.starts_with(PNG_MAGIC)) {
+            Content::Png(png.to_vec())
+        } else if let Some(html) = find("HTML Format").and_then(parse_cf_html) {
+            Content::Html { html, text }
+        } else if let Some(text) = text.or(summary) {
+            Content::Text(text)
+        } else {
+            report.skipped += 1;
+            continue;
+        };
+        entries.push(Imported {
+            content,
+            time: date
+                .and_then(|secs| u64::try_from(secs).ok())
+                .map(|secs| secs * 1000),
+            favorite: dont_delete.is_some_and(|v| v > 0),
+        });
+    }
+    Ok(entries)
+}
+
+fn decode_utf16le(bytes: &[u8]) -> String {
+    let units: Vec<u16> = bytes
+        .chunks_exact(2)
+        .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+        .take_while(|unit| *unit != 0)
+        .collect();
+    String::from_utf16_lossy(&units)
+}
+
+fn decode_ansi(bytes: &[u8]) -> String {
+    let end = bytes.iter().position(|b| *b == 0).unwrap_or(bytes.len());
+    String::from_utf8_lossy(&bytes[..end]).to_string()
+}
+
+/// `DROPFILES` 结构：偏移 0 为文件列表起点，偏移 16 标记是否为宽字符，列表以空字符串结尾。
+fn parse_hdrop(bytes: &[u8]) -> Vec<PathBuf> {
+    let read_u32 = |at: usize| {
+        bytes
+            .get(at..at + 4)
+            .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as usize)
+    };
+    let (Some(offset), Some(wide)) = (read_u32(0), read_u32(16)) else {
+        return Vec::new();
+    };
+    let Some(list) = bytes.get(offset..) else {
+        return Vec::new();
+    };
+    let names: Vec<String> = if wide != 0 {
+        let units: Vec<u16> = list
+            .chunks_exact(2)
+            .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+            .collect();
+        units
+            .split(|unit| *unit == 0)
+            .take_while(|name| !name.is_empty())
+            .map(String::from_utf16_lossy)
+            .collect()
+    } else {
+        list.split(|b| *b == 0)
+            .take_while(|name| !name.is_empty())
+            .map(|name| String::from_utf8_lossy(name).to_string())
+            .collect()
+    };
+    names.into_iter().map(PathBuf::from).collect()
+}
+
+/// Windows `HTML Format`：头部给出片段的字节偏移，取不到时退回到头部之后的全部内容。
+fn parse_cf_html(bytes: &[u8]) -> Option<String> {
+    let raw = String::from_utf8_lossy(bytes);
+    let offset = |name: &str| {
+        raw.lines()
+            .find_map(|line| line.strip_prefix(name))
+            .and_then(|value| value.trim().parse::<usize>().ok())
+    };
+    let fragment = match (offset("StartFragment:"), offset("EndFragment:")) {
+        (Some(start), Some(end)) if start < end && end <= bytes.len() => {
+            String::from_utf8_lossy(&bytes[start..end]).to_string()
+        }
+        _ => {
+            let start = offset("StartHTML:").filter(|start| *start < bytes.len())?;
+            String::from_utf8_lossy(&bytes[start..]).to_string()
+        }
+    };
+    let fragment = fragment.trim_matches('\0').trim().to_string();
+    (!fragment.is_empty()).then_some(fragment)
+}
+
+/// CopyQ 的标签页文件（`copyq_tab_*.dat`）。`path` 可以是单个文件或 CopyQ 配置目录。
+fn read_copyq(path: &Path, report: &mut ImportReport) -> Result<Vec<Imported>, String> {
+    let files: Vec<PathBuf> = if path.is_dir() {
+        let mut files: Vec<PathBuf> = fs::read_dir(path)
+            .map_err(|e| format!("读取 CopyQ 目录失败: {e}"))?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|file| {
+                file.file_name()
+                    .map(|name| name.to_string_lossy())
+                    .is_some_and(|name| name.starts_with("copyq_tab_") && name.ends_with(".dat"))
+            })
+            .collect();
+        files.sort();
+        files
+    } else {
+        vec![path.to_path_buf()]
+    };
+
+    let mut entries = Vec::new();
+    for file in files {
+        let raw = fs::read(&file).map_err(|e| format!("读取 CopyQ 标签页失败: {e}"))?;
+        let items =
+            copyq::parse_tab(&raw).map_err(|e| format!("解析 {} 失败: {e}", file.display()))?;
+        // 标签页中最新的条目在最前面。
+        for formats in items.into_iter().rev() {
+            let find = |mime: &str| {
+                formats
+                    .iter()
+                    .find(|(name, _)| name == mime || name.starts_with(&format!("{mime};")))
+                    .map(|(_, bytes)| bytes.as_slice())
+            };
+            let text = find("text/plain").map(|bytes| String::from_utf8_lossy(bytes).to_string());
+            let content = if let Some(files) = find("text/uri-list")
+                .map(|bytes| parse_uri_list(&String::from_utf8_lossy(bytes)))
+                .filter(|files| !files.is_empty())
+            {
+                Content::Files(files)
+            } else if let Some(png) = find("image/png") {
+                Content::Png(png.to_vec())
+            } else if let Some(html) = find("text/html") {
+                Content::Html {
+                    html: String::from_utf8_lossy(html).to_string(),
+                    text,
+                }
+            } else if let Some(text) = text {
+                Content::Text(text)
+            } else {
+                report.skipped += 1;
+                continue;
+            };
+            entries.push(Imported::new(content));
+        }
+    }
+    Ok(entries)
+}
+
+/// Qt `QDataStream`（大端序）格式的 CopyQ 标签页解析。
+mod copyq {
+    use super::ZlibDecoder;
+    use std::io::Read;
+
+    /// 条目中的 MIME 类型按前缀压缩存储。
+    const MIME_PREFIXES: &[(char, &str)] = &[
+        ('0', "application/x-copyq-"),
+        ('1', "text/"),
+        ('2', "application/"),
+        ('3', "image/"),
+    ];
+
+    pub(super) type Formats = Vec<(String, Vec<u8>)>;
+
+    struct Reader<'a> {
+        data: &'a [u8],
+        pos: usize,
+    }
+
+    impl<'a> Reader<'a> {
+        fn take(&mut self, len: usize) -> Result<&'a [u8], String> {
+            let end = self
+                .pos
+                .checked_add(len)
+                .filter(|end| *end <= self.data.len())
+                .ok_or_else(|| "数据意外结束".to_string())?;
+            let bytes = &self.data[self.pos..end];
+            self.pos = end;
+            Ok(bytes)
+        }
+
+        fn u32(&mut self) -> Result<u32, String> {
+            let b = self.take(4)?;
+            Ok(u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+        }
+
+        fn i32(&mut self) -> Result<i32, String> {
+            Ok(self.u32()? as i32)
+        }
+
+        fn bool(&mut self) -> Result<bool, String> {
+            Ok(self.take(1)?[0] != 0)
+        }
+
+        /// 长度为 `0xFFFFFFFF` 表示空值。
+        fn bytes(&mut self) -> Result<Vec<u8>, String> {
+            match self.u32()? {
+                u32::MAX => Ok(Vec::new()),
+                len => Ok(self.take(len as usize)?.to_vec()),
+            }
+        }
+
+        fn string(&mut self) -> Result<String, String> {
+            let raw = self.bytes()?;
+            let units: Vec<u16> = raw
+                .chunks_exact(2)
+                .map(|pair| u16::from_be_bytes([pair[0], pair[1]]))
+                .collect();
+            Ok(String::from_utf16_lossy(&units))
+        }
+    }
+
+    fn decompress_mime(mime: &str) -> String {
+        let mut chars = mime.chars();
+        if let Some(first) = chars.next() {
+            if let Some((_, prefix)) = MIME_PREFIXES.iter().find(|(tag, _)| *tag == first) {
+                return format!("{prefix}{}", chars.as_str());
+            }
+        }
+        mime.to_string()
+    }
+
+    /// `qCompress` 格式：4 字节大端序原始长度，后接 zlib 数据。
+    fn uncompress(bytes: &[u8]) -> Result<Vec<u8>, String> {
+        let Some(stream) = bytes.get(4..) else {
+            return Ok(Vec::new());
+        };
+        let mut out = Vec::new();
+        ZlibDecoder::new(stream)
+            .read_to_end(&mut out)
+            .map_err(|e| format!("解压失败: {e}"))?;
+        Ok(out)
+    }
+
+    /// 文件可能以 "CopyQ v..." 头和保存插件 id 开头，之后是条目数量与各条目的格式表。
+    pub(super) fn parse_tab(data: &[u8]) -> Result<Vec<Formats>, String> {
+        let mut reader = Reader { data, pos: 0 };
+        let header = reader.string().unwrap_or_default();
+        if header.starts_with("CopyQ") {
+            let plugin = reader.string()?;
+            if plugin.contains("encrypted") {
+                return Err("加密的标签页无法导入".to_string());
+            }
+        } else {
+            reader.pos = 0;
+        }
+
+        let count = reader.i32()?;
+        let mut items = Vec::new();
+        for _ in 0..count.max(0) {
+            let version = reader.i32()?;
+            let (size, compressed_mime) = match version {
+                -2 => (reader.i32()?, true),
+                -1 => (reader.i32()?, false),
+                size => (size, false),
+            };
+            let mut formats = Vec::new();
+            for _ in 0..size.max(0) {
+                let (mime, bytes) = if compressed_mime {
+                    let mime = decompress_mime(&reader.string()?);
+                    let compressed = reader.bool()?;
+                    let bytes = reader.bytes()?;
+                    (
+                        mime,
+                        if compressed {
+                            uncompress(&bytes)?
+                        } else {
+                            bytes
+                        },
+                    )
+                } else {
+                    let mime = reader.string()?;
+                    (mime, uncompress(&reader.bytes()?)?)
+                };
+                formats.push((mime, bytes));
+            }
+            items.push(formats);
+        }
+        Ok(items)
+    }
+}
+
+/// 只读的 bbolt（BoltDB）解析，足以遍历单个桶中的全部键值。
+mod bolt {
+    const MAGIC: u32 = 0xED0C_DAED;
+    const PAGE_HEADER: usize = 16;
+    const ELEMENT_SIZE: usize = 16;
+    const BRANCH_PAGE: u16 = 0x01;
+    const LEAF_PAGE: u16 = 0x02;
+    const BUCKET_LEAF: u32 = 0x01;
+    /// 防止损坏的文件造成页面循环引用。
+    const MAX_DEPTH: usize = 64;
+
+    pub(super) type Entry = (Vec<u8>, Vec<u8>);
+
+    pub(super) struct Db<'a> {
+        data: &'a [u8],
+        page_size: usize,
+        root: u64,
+    }
+
+    fn u16_at(data: &[u8], at: usize) -> Option<u16> {
+        Some(u16::from_le_bytes(data.get(at..at + 2)?.try_into().ok()?))
+    }
+
+    fn u32_at(data: &[u8], at: usize) -> Option<u32> {
+        Some(u32::from_le_bytes(data.get(at..at + 4)?.try_into().ok()?))
+    }
+
+    fn u64_at(data: &[u8], at: usize) -> Option<u64> {
+        Some(u64::from_le_bytes(data.get(at..at + 8)?.try_into().ok()?))
+    }
+
+    fn fnv64a(bytes: &[u8]) -> u64 {
+        bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, b| {
+            (hash ^ u64::from(*b)).wrapping_mul(0x0100_0000_01b3)
+        })
+    }
+
+    /// 元数据页：magic、版本、页大小、标志、根桶（根页号、序列号）、空闲列表、最大页号、事务号、校验和。
+    fn read_meta(data: &[u8], offset: usize) -> Option<(usize, u64, u64)> {
+        let meta = data.get(offset + PAGE_HEADER..offset + PAGE_HEADER + 64)?;
+        if u32_at(meta, 0)? != MAGIC || fnv64a(&meta[..56]) != u64_at(meta, 56)? {
+            return None;
+        }
+        Some((
+            u32_at(meta, 8)? as usize,
+            u64_at(meta, 16)?,
+            u64_at(meta, 48)?,
+        ))
+    }
+
+    impl<'a> Db<'a> {
+        /// 两个元数据页中取校验通过且事务号较大的一个。
+        pub(super) fn open(data: &'a [u8]) -> Result<Self, String> {
+            let first = read_meta(data, 0);
+            let page_size = first.map_or(4096, |(size, _, _)| size);
+            let second = read_meta(data, page_size);
+            let (page_size, root, _) = [first, second]
+                .into_iter()
+                .flatten()
+                .filter(|(size, _, _)| *size >= 512)
+                .max_by_key(|(_, _, txid)| *txid)
+                .ok_or_else(|| "不是有效的 bbolt 数据库".to_string())?;
+            Ok(Self {
+                data,
+                page_size,
+                root,
+            })
+        }
+
+        fn page(&self, id: u64) -> Result<&'a [u8], String> {
+            let invalid = || format!("页面 {id} 越界");
+            let start = usize::try_from(id)
+                .ok()
+                .and_then(|id| id.checked_mul(self.page_size))
+                .ok_or_else(invalid)?;
+            let overflow = start
+                .checked_add(12)
+                .and_then(|at| u32_at(self.data, at))
+                .ok_or_else(invalid)? as usize;
+            let end = overflow
+                .checked_add(1)
+                .and_then(|pages| pages.checked_mul(self.page_size))
+                .and_then(|len| start.checked_add(len))
+                .ok_or_else(invalid)?
+                .min(self.data.len());
+            self.data.get(start..end).ok_or_else(invalid)
+        }
+
+        /// 遍历以 `page` 为根的子树中的全部叶子元素：(标志, 键, 值)。
+        fn walk(
+            &self,
+            page: &'a [u8],
+            depth: usize,
+            visit: &mut dyn FnMut(u32, &'a [u8], &'a [u8]),
+        ) -> Result<(), String> {
+            if depth > MAX_DEPTH {
+                return Err("页面层级过深".to_string());
+            }
+            let corrupt = || "页面数据损坏".to_string();
+            let flags = u16_at(page, 8).ok_or_else(corrupt)?;
+            let count = u16_at(page, 10).ok_or_else(corrupt)? as usize;
+            for idx in 0..count {
+                let elem = PAGE_HEADER + idx * ELEMENT_SIZE;
+                if flags & BRANCH_PAGE != 0 {
+                    let child = u64_at(page, elem + 8).ok_or_else(corrupt)?;
+                    self.walk(self.page(child)?, depth + 1, visit)?;
+                } else if flags & LEAF_PAGE != 0 {
+                    let elem_flags = u32_at(page, elem).ok_or_else(corrupt)?;
+                    let pos = u32_at(page, elem + 4).ok_or_else(corrupt)? as usize;
+                    let ksize = u32_at(page, elem + 8).ok_or_else(corrupt)? as usize;
+                    let vsize = u32_at(page, elem + 12).ok_or_else(corrupt)? as usize;
+                    let key_start = elem.checked_add(pos).ok_or_else(corrupt)?;
+                    let key_end = key_start.checked_add(ksize).ok_or_else(corrupt)?;
+                    let value_end = key_end.checked_add(vsize).ok_or_else(corrupt)?;
+                    let key = page.get(key_start..key_end).ok_or_else(corrupt)?;
+                    let value = page.get(key_end..value_end).ok_or_else(corrupt)?;
+                    visit(elem_flags, key, value);
+                }
+            }
+            Ok(())
+        }
+
+        /// 桶的值以根页号开头；根页号为 0 的小桶把整页内联在值中（跳过 16 字节桶头）。
+        pub(super) fn bucket_entries(&self, name: &[u8]) -> Result<Vec<Entry>, String> {
+            let mut bucket = None;
+            self.walk(self.page(self.root)?, 0, &mut |flags, key, value| {
+                if flags & BUCKET_LEAF != 0 && key == name {
+                    bucket = Some(value);
+                }
+            })?;
+            let value = bucket.ok_or_else(|| "数据库中没有历史记录".to_string())?;
+            let root = u64_at(value, 0).ok_or_else(|| "桶数据损坏".to_string())?;
+            let page = if root == 0 {
+                value.get(16..).ok_or_else(|| "桶数据损坏".to_string())?
+            } else {
+                self.page(root)?
+            };
+
+            let mut entries = Vec::new();
+            self.walk(page, 0, &mut |flags, key, value| {
+                if flags & BUCKET_LEAF == 0 {
+                    entries.push((key.to_vec(), value.to_vec()));
+                }
+            })?;
+            Ok(entries)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::write::ZlibEncoder;
+    use flate2::Compression;
+    use std::io::Write;
+
+    /// 文件名带进程号，避免同时运行的测试进程互相覆盖。
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!(
+            "clipboard-history-import-{}-{name}",
+            std::process::id()
+        ))
+    }
+
+    fn fixture(name: &str, bytes: &[u8]) -> PathBuf {
+        let path = temp_path(name);
+        fs::write(&path, bytes).unwrap();
+        path
+    }
+
+    fn describe(entries: &[Imported]) -> Vec<String> {
+        entries
+            .iter()
+            .map(|entry| match &entry.content {
+                Content::Text(text) => format!("text:{text}"),
+                Content::Html { html, text } => {
+                    format!("html:{html}|{}", text.as_deref().unwrap_or(""))
+                }
+                Content::Png(bytes) => format!("png:{}", bytes.len()),
+                Content::Files(paths) => format!(
+                    "files:{}",
+                    paths
+                        .iter()
+                        .map(|path| path.to_string_lossy())
+                        .collect::<Vec<_>>()
+                        .join(",")
+                ),
+            })
+            .collect()
+    }
+
+    fn qbytes(out: &mut Vec<u8>, bytes: &[u8]) {
+        out.extend((bytes.len() as u32).to_be_bytes());
+        out.extend(bytes);
+    }
+
+    fn qstring(out: &mut Vec<u8>, text: &str) {
+        let raw: Vec<u8> = text.encode_utf16().flat_map(u16::to_be_bytes).collect();
+        qbytes(out, &raw);
+    }
+
+    fn qcompress(bytes: &[u8]) -> Vec<u8> {
+        let mut out = (bytes.len() as u32).to_be_bytes().to_vec();
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(bytes).unwrap();
+        out.extend(encoder.finish().unwrap());
+        out
+    }
+
+    /// 两条记录：新版的压缩 MIME 格式（HTML + 纯文本），以及旧版 `qCompress` 格式的纯文本。
+    fn copyq_tab() -> Vec<u8> {
+        let mut tab = Vec::new();
+        qstring(&mut tab, "CopyQ v3");
+        qstring(&mut tab, "itemsync");
+        tab.extend(2i32.to_be_bytes());
+
+        tab.extend((-2i32).to_be_bytes());
+        tab.extend(2i32.to_be_bytes());
+        qstring(&mut tab, "1html");
+        tab.push(0);
+        qbytes(&mut tab, b"<b>new</b>");
+        qstring(&mut tab, "1plain");
+        tab.push(1);
+        qbytes(&mut tab, &qcompress(b"new"));
+
+        tab.extend((-1i32).to_be_bytes());
+        tab.extend(1i32.to_be_bytes());
+        qstring(&mut tab, "text/plain");
+        qbytes(&mut tab, &qcompress(b"old"));
+        tab
+    }
+
+    #[test]
+    fn copyq_tab_is_read_oldest_first() {
+        let path = fixture("copyq_tab_test.dat", &copyq_tab());
+        let mut report = ImportReport::default();
+        let entries = read_copyq(&path, &mut report).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(describe(&entries), ["text:old", "html:<b>new</b>|new"]);
+        assert_eq!(report.skipped, 0);
+    }
+
+    #[test]
+    fn copyq_truncated_tab_is_rejected() {
+        let tab = copyq_tab();
+        for len in [3, 20, tab.len() - 1] {
+            assert_eq!(
+                copyq::parse_tab(&tab[..len]).err().as_deref(),
+                Some("数据意外结束")
+            );
+        }
+    }
+
+    const BOLT_PAGE: usize = 4096;
+
+    /// 叶子页：页头之后是元素数组，键值数据紧随其后；`pos` 相对元素自身的起点。
+    fn bolt_leaf(id: u64, elements: &[(u32, &[u8], &[u8])]) -> Vec<u8> {
+        let mut page = id.to_le_bytes().to_vec();
+        page.extend(0x02u16.to_le_bytes());
+        page.extend((elements.len() as u16).to_le_bytes());
+        page.extend(0u32.to_le_bytes());
+        let mut data: Vec<u8> = Vec::new();
+        for (idx, (flags, key, value)) in elements.iter().enumerate() {
+            let pos = (elements.len() - idx) * 16 + data.len();
+            page.extend(flags.to_le_bytes());
+            page.extend((pos as u32).to_le_bytes());
+            page.extend((key.len() as u32).to_le_bytes());
+            page.extend((value.len() as u32).to_le_bytes());
+            data.extend(*key);
+            data.extend(*value);
+        }
+        page.extend(data);
+        page
+    }
+
+    fn bolt_meta(id: u64, root: u64, txid: u64) -> Vec<u8> {
+        let mut page = id.to_le_bytes().to_vec();
+        page.extend(0x04u16.to_le_bytes());
+        page.extend([0; 6]);
+        let mut meta = 0xED0C_DAEDu32.to_le_bytes().to_vec();
+        meta.extend(2u32.to_le_bytes());
+        meta.extend((BOLT_PAGE as u32).to_le_bytes());
+        meta.extend(0u32.to_le_bytes());
+        meta.extend(root.to_le_bytes());
+        meta.extend(0u64.to_le_bytes());
+        meta.extend(2u64.to_le_bytes());
+        meta.extend(4u64.to_le_bytes());
+        meta.extend(txid.to_le_bytes());
+        let checksum = meta.iter().fold(0xcbf2_9ce4_8422_2325u64, |hash, b| {
+            (hash ^ u64::from(*b)).wrapping_mul(0x0100_0000_01b3)
+        });
+        meta.extend(checksum.to_le_bytes());
+        page.extend(meta);
+        page
+    }
+
+    /// 两个元数据页、一个空页和根页；cliphist 的 `b` 桶内联在根页中。
+    fn cliphist_db() -> Vec<u8> {
+        let png = [PNG_MAGIC, b"data"].concat();
+        let bucket_page = bolt_leaf(
+            0,
+            &[
+                (0, &1u64.to_be_bytes(), b"first"),
+                (0, &2u64.to_be_bytes(), b"file:///tmp/a%20b.txt"),
+                (0, &3u64.to_be_bytes(), &[0xff, 0xfe, 0x00]),
+                (0, &4u64.to_be_bytes(), &png),
+            ],
+        );
+        let mut bucket = vec![0; 16];
+        bucket.extend(bucket_page);
+        let root = bolt_leaf(3, &[(0x01, b"b", &bucket)]);
+
+        let mut db = vec![0; BOLT_PAGE * 4];
+        for (id, page) in [bolt_meta(0, 3, 1), bolt_meta(1, 3, 2), root]
+            .into_iter()
+            .enumerate()
+        {
+            let offset = if id == 2 { 3 } else { id } * BOLT_PAGE;
+            db[offset..offset + page.len()].copy_from_slice(&page);
+        }
+        db
+    }
+
+    #[test]
+    fn cliphist_bucket_is_read() {
+        let path = fixture("cliphist.db", &cliphist_db());
+        let mut report = ImportReport::default();
+        let entries = read_cliphist(&path, &mut report).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(
+            describe(&entries),
+            ["text:first", "files:/tmp/a b.txt", "png:12"]
+        );
+        assert_eq!(report.skipped, 1);
+    }
+
+    #[test]
+    fn cliphist_truncated_db_is_rejected() {
+        let db = cliphist_db();
+        assert!(bolt::Db::open(&db[..40]).is_err());
+        let truncated = bolt::Db::open(&db[..BOLT_PAGE * 3 + 60]).unwrap();
+        assert!(truncated.bucket_entries(b"b").is_err());
+    }
+
+    #[test]
+    fn cliphist_page_overflow_is_clamped() {
+        let mut db = cliphist_db();
+        let at = BOLT_PAGE * 3 + 12;
+        db[at..at + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+        let entries = bolt::Db::open(&db).unwrap().bucket_entries(b"b").unwrap();
+        assert_eq!(entries.len(), 4);
+    }
+
+    const GPASTE_HISTORY: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<history version="2.0">
+  <item kind="Text" date="1700000000"><value><![CDATA[newest]]></value></item>
+  <item kind="Password" name="bank"><value><![CDATA[hunter2]]></value></item>
+  <item kind="Uris"><value><![CDATA[file:///tmp/a.txt
+file:///tmp/b.txt]]></value></item>
+  <item kind="Text"><value>a &lt;&amp;&gt; b</value></item>
+</history>
+"#;
+
+    #[test]
+    fn gpaste_history_is_read_oldest_first() {
+        let path = fixture("gpaste.xml", GPASTE_HISTORY.as_bytes());
+        let mut report = ImportReport::default();
+        let entries = read_gpaste(&path, &mut report).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(
+            describe(&entries),
+            ["text:a <&> b", "files:/tmp/a.txt,/tmp/b.txt", "text:newest"]
+        );
+        assert_eq!(entries[2].time, Some(1_700_000_000_000));
+        assert_eq!(report.skipped, 1);
+    }
+
+    #[test]
+    fn gpaste_truncated_history_keeps_complete_items() {
+        let cut = GPASTE_HISTORY.find("a &lt;").unwrap();
+        let path = fixture("gpaste-truncated.xml", &GPASTE_HISTORY.as_bytes()[..cut]);
+        let mut report = ImportReport::default();
+        let entries = read_gpaste(&path, &mut report).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(
+            describe(&entries),
+            ["files:/tmp/a.txt,/tmp/b.txt", "text:newest"]
+        );
+    }
+
+    fn ditto_db(path: &Path) {
+        let _ = fs::remove_file(path);
+        let conn = Connection::open(path).unwrap();
+        conn.execute_batch(
+            "CREATE TABLE Main (lID INTEGER PRIMARY KEY, lDate INTEGER, mText TEXT,
+                                lDontAutoDelete INTEGER, bIsGroup INTEGER);
+             CREATE TABLE Data (lParentID INTEGER, strClipBoardFormat TEXT, ooData BLOB);
+             INSERT INTO Main VALUES (1, 100, 'summary', 1, 0);
+             INSERT INTO Main VALUES (2, 150, 'group', 0, 1);
+             INSERT INTO Main VALUES (3, 200, NULL, 0, 0);
+             INSERT INTO Main VALUES (4, 300, NULL, 0, 0);",
+        )
+        .unwrap();
+        let text: Vec<u8> = "hello\0"
+            .encode_utf16()
+            .flat_map(u16::to_le_bytes)
+            .collect();
+        let mut hdrop = 20u32.to_le_bytes().to_vec();
+        hdrop.extend([0; 12]);
+        hdrop.extend(1u32.to_le_bytes());
+        hdrop.extend("C:\\a.txt\0\0".encode_utf16().flat_map(u16::to_le_bytes));
+        for (parent, format, data) in [(1, "CF_UNICODETEXT", text), (3, "CF_HDROP", hdrop)] {
+            conn.execute(
+                "INSERT INTO Data VALUES (?1, ?2, ?3)",
+                params![parent, format, data],
+            )
+            .unwrap();
+        }
+    }
+
+    #[test]
+    fn ditto_database_is_read() {
+        let path = temp_path("ditto.db");
+        ditto_db(&path);
+        let mut report = ImportReport::default();
+        let entries = read_ditto(&path, &mut report).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(describe(&entries), ["text:hello", "files:C:\\a.txt"]);
+        assert_eq!(entries[0].time, Some(100_000));
+        assert!(entries[0].favorite);
+        assert!(!entries[1].favorite);
+        assert_eq!(report.skipped, 1);
+    }
+
+    #[test]
+    fn ditto_truncated_database_is_rejected() {
+        let source = temp_path("ditto-full.db");
+        ditto_db(&source);
+        let raw = fs::read(&source).unwrap();
+        fs::remove_file(&source).unwrap();
+        let path = fixture("ditto-truncated.db", &raw[..1000]);
+        let mut report = ImportReport::default();
+        let result = read_ditto(&path, &mut report);
+        fs::remove_file(&path).unwrap();
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn clipman_history_is_read() {
+        let path = fixture("clipman.json", br#"["first", "second\nline"]"#);
+        let entries = read_clipman(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(describe(&entries), ["text:first", "text:second\nline"]);
+    }
+
+    #[test]
+    fn clipman_truncated_history_is_rejected() {
+        let path = fixture("clipman-truncated.json", br#"["first", "sec"#);
+        let result = read_clipman(&path);
+        fs::remove_file(&path).unwrap();
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,194 @@
+use crate::{
+    append_log, crypto, data_dir, ensure_storage_layout, hash_bytes, images, lock_history,
+    open_history_db, storage, storage_key, AppState, ClipboardItem,
+};
+use serde::Serialize;
+use std::path::Path;
+use tauri::{AppHandle, Manager};
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct RecordIssue {
+    pub(crate) id: String,
+    pub(crate) reason: String,
+}
+
+#[derive(Debug, Default, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct VerifyReport {
+    pub(crate) checked_items: usize,
+    /// 存储加密且未解锁时无法校验内容，只检查文件层面的问题。
+    pub(crate) content_checked: bool,
+    pub(crate) orphan_files: Vec<String>,
+    pub(crate) missing_images: Vec<RecordIssue>,
+    pub(crate) hash_mismatches: Vec<RecordIssue>,
+    pub(crate) malformed_records: Vec<RecordIssue>,
+    pub(crate) repaired: bool,
+}
+
+impl VerifyReport {
+    pub(crate) fn issue_count(&self) -> usize {
+        self.orphan_files.len()
+            + self.missing_images.len()
+            + self.hash_mismatches.len()
+            + self.malformed_records.len()
+    }
+}
+
+fn issue(id: &str, reason: impl Into<String>) -> RecordIssue {
+    RecordIssue {
+        id: id.to_string(),
+        reason: reason.into(),
+    }
+}
+
+/// 检查记录自身是否完整，不涉及文件。
+pub(crate) fn validate_record(item: &ClipboardItem, content_available: bool) -> Result<(), String> {
+    if item.id.is_empty() {
+        return Err("缺少 id".to_string());
+    }
+    if item.content_hash.is_empty() {
+        return Err("缺少内容哈希".to_string());
+    }
+    match item.item_type.as_str() {
+        "image" => {
+            if item.image_path.is_none() {
+                return Err("图片记录缺少路径".to_string());
+            }
+        }
+        "text" | "html" | "files" if !content_available => {}
+        "text" => {
+            if item.text.as_deref().is_none_or(str::is_empty) {
+                return Err("文本记录内容为空".to_string());
+            }
+        }
+        "html" => {
+            if item.html.is_none() || item.text.is_none() {
+                return Err("富文本记录缺少 HTML 或纯文本".to_string());
+            }
+        }
+        "files" => {
+            if item.files.as_ref().is_none_or(Vec::is_empty) {
+                return Err("文件列表记录为空".to_string());
+            }
+        }
+        other => return Err(format!("未知类型: {other}")),
+    }
+    Ok(())
+}
+
+/// 图片记录：文件须存在于图片目录内；能读取内容时再核对哈希。
+fn check_image(
+    base: &Path,
+    key: Option<&crypto::Key>,
+    item: &ClipboardItem,
+    content_available: bool,
+    report: &mut VerifyReport,
+) {
+    let Some(rel) = item.image_path.as_deref() else {
+        return;
+    };
+    let Some(path) = images::resolve(base, rel) else {
+        report
+            .malformed_records
+            .push(issue(&item.id, format!("图片路径不在图片目录内: {rel}")));
+        return;
+    };
+    if !path.exists() {
+        report.missing_images.push(issue(&item.id, rel));
+        return;
+    }
+    if !content_available {
+        return;
+    }
+    match crypto::read_file(key, &path) {
+        Ok(bytes) if hash_bytes(&bytes) != item.content_hash => {
+            report.hash_mismatches.push(issue(&item.id, rel));
+        }
+        Ok(_) => {}
+        // 有密钥却解不开说明文件已损坏；没有密钥时读不出内容不代表文件有问题。
+        Err(err) if key.is_some() => report.hash_mismatches.push(issue(&item.id, err)),
+        Err(_) => {}
+    }
+}
+
+/// 检查孤立图片、缺失图片、图片哈希不符与格式错误的记录；`repair` 为真时删除孤立文件和有问题的记录，
+/// 被删记录引用的图片在引用归零后一并删除。
+pub(crate) fn verify(app: &AppHandle, repair: bool) -> Result<VerifyReport, String> {
+    ensure_storage_layout(app)?;
+    let state = app.state::<AppState>();
+    let _guard = lock_history(app, &state)?;
+
+    let base = data_dir(app)?;
+    let key = storage_key(app).ok().flatten();
+    let conn = open_history_db(app)?;
+    let rows = storage::audit_items(&conn, key.as_ref())?;
+
+    let mut report = VerifyReport {
+        checked_items: rows.len(),
+        content_checked: rows.iter().all(|row| !row.sealed_locked),
+        orphan_files: images::orphan_files(&conn, &base)?,
+        ..VerifyReport::default()
+    };
+
+    for row in &rows {
+        let item = match &row.item {
+            Ok(item) => item,
+            Err(err) => {
+                report.malformed_records.push(issue(&row.id, err.clone()));
+                continue;
+            }
+        };
+        let content_available = !row.sealed_locked;
+        if let Err(err) = validate_record(item, content_available) {
+            report.malformed_records.push(issue(&row.id, err));
+            continue;
+        }
+        if item.item_type == "image" {
+            check_image(&base, key.as_ref(), item, content_available, &mut report);
+        }
+    }
+
+    if repair && report.issue_count() > 0 {
+        let broken_ids = report
+            .missing_images
+            .iter()
+            .chain(&report.hash_mismatches)
+            .chain(&report.malformed_records)
+            .map(|issue| issue.id.as_str());
+        let mut released = Vec::new();
+        for id in broken_ids {
+            let image_path = rows
+                .iter()
+                .find(|row| row.id == id)
+                .and_then(|row| row.item.as_ref().ok())
+                .and_then(|item| item.image_path.clone());
+            storage::delete_by_id(&conn, id)?;
+            released.extend(image_path);
+        }
+        images::release_all(&conn, &base, &released)?;
+        images::remove_orphans(&conn, &base)?;
+        report.repaired = true;
+    }
+    Ok(report)
+}
+
+/// 启动时在后台执行一次只读检查，结果写入日志；删除记录须由用户在设置页手动修复。
+pub(crate) fn run_startup_check(app: &AppHandle) {
+    match verify(app, false) {
+        Ok(report) if report.issue_count() > 0 => append_log(
+            app,
+            "WARN",
+            &format!(
+                "storage verify found orphan_files={} missing_images={} hash_mismatches={} malformed_records={} content_checked={}",
+                report.orphan_files.len(),
+                report.missing_images.len(),
+                report.hash_mismatches.len(),
+                report.malformed_records.len(),
+                report.content_checked
+            ),
+        ),
+        Ok(_) => {}
+        Err(err) => append_log(app, "WARN", &format!("storage verify failed: {err}")),
+    }
+}
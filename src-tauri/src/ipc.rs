@@ -0,0 +1,604 @@
+use crate::{
+    app_root_dir, append_log, clear_history, copy_history_item, delete_history_item, get_history,
+    get_settings, search, search_history, toggle_favorite, update_settings, AppState,
+    UpdateSettingsPayload,
+};
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::OsRng;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fs;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::Path;
+use std::thread;
+use tauri::{AppHandle, Emitter, Manager};
+
+pub(crate) const TOKEN_FILE_NAME: &str = "ipc-token";
+/// 通过 IPC 修改历史或设置后通知界面刷新。
+pub(crate) const HISTORY_CHANGED_EVENT: &str = "clipboard://history-changed";
+pub(crate) const SETTINGS_CHANGED_EVENT: &str = "clipboard://settings-changed";
+
+/// 单条请求的长度上限，防止客户端发送不带换行的超长数据占满内存。
+const MAX_REQUEST_BYTES: u64 = 1024 * 1024;
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const UNAUTHORIZED: i64 = -32001;
+const APP_ERROR: i64 = -32000;
+
+/// JSON-RPC 2.0 请求，每行一条；`token` 须与应用数据目录中 `ipc-token` 文件的内容一致。
+#[derive(Debug, Deserialize)]
+struct Request {
+    jsonrpc: Option<String>,
+    #[serde(default)]
+    id: Value,
+    method: String,
+    #[serde(default)]
+    params: Value,
+    token: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct RpcError {
+    code: i64,
+    message: String,
+}
+
+#[derive(Debug, Serialize)]
+struct Response {
+    jsonrpc: &'static str,
+    id: Value,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    result: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<RpcError>,
+}
+
+impl Response {
+    fn ok(id: Value, result: Value) -> Self {
+        Self {
+            jsonrpc: "2.0",
+            id,
+            result: Some(result),
+            error: None,
+        }
+    }
+
+    fn err(id: Value, code: i64, message: impl Into<String>) -> Self {
+        Self {
+            jsonrpc: "2.0",
+            id,
+            result: None,
+            error: Some(RpcError {
+                code,
+                message: message.into(),
+            }),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct IdParams {
+    id: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct CopyParams {
+    id: String,
+    fields: Option<HashMap<String, String>>,
+}
+
+/// 在后台线程启动 IPC 服务；失败只记录日志，不影响主程序。
+pub(crate) fn spawn(app: AppHandle) {
+    let spawned = thread::Builder::new()
+        .name("ipc-server".to_string())
+        .spawn(move || {
+            if let Err(err) = serve(&app) {
+                append_log(&app, "WARN", &format!("ipc server stopped: {err}"));
+            }
+        });
+    if let Err(err) = spawned {
+        eprintln!("ipc server spawn failed: {err}");
+    }
+}
+
+fn serve(app: &AppHandle) -> Result<(), String> {
+    let root = app_root_dir(app)?;
+    let token = load_or_create_token(&root)?;
+    platform::listen(&root, |reader, writer| {
+        let app = app.clone();
+        let token = token.clone();
+        let spawned = thread::Builder::new()
+            .name("ipc-client".to_string())
+            .spawn(move || handle_connection(&app, &token, reader, writer));
+        if let Err(err) = spawned {
+            eprintln!("ipc client spawn failed: {err}");
+        }
+    })
+}
+
+/// 令牌在首次启动时随机生成并保留，脚本可以直接读取文件获得。
+fn load_or_create_token(root: &Path) -> Result<String, String> {
+    let path = root.join(TOKEN_FILE_NAME);
+    if let Ok(existing) = fs::read_to_string(&path) {
+        let existing = existing.trim();
+        if existing.len() >= 32 {
+            return Ok(existing.to_string());
+        }
+    }
+
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+    let token: String = bytes.iter().map(|b| format!("{b:02x}")).collect();
+    platform::write_private(&path, &token).map_err(|e| format!("写入 IPC 令牌失败: {e}"))?;
+    Ok(token)
+}
+
+/// 命令行客户端：程序正在运行时通过 IPC 调用方法。没有实例应答（未运行或令牌不可读）时返回 `None`，
+/// 实例应答了错误时返回其错误信息。
+pub(crate) fn call(root: &Path, method: &str, params: Value) -> Option<Result<Value, String>> {
+    let token = fs::read_to_string(root.join(TOKEN_FILE_NAME)).ok()?;
+    let (reader, mut writer) = platform::connect(root)?;
+    let request = serde_json::json!({
+        "jsonrpc": "2.0",
+        "id": 1,
+        "method": method,
+        "params": params,
+        "token": token.trim(),
+    });
+    let mut line = serde_json::to_vec(&request).ok()?;
+    line.push(b'\n');
+    writer.write_all(&line).and_then(|_| writer.flush()).ok()?;
+
+    let mut raw = String::new();
+    BufReader::new(reader).read_line(&mut raw).ok()?;
+    let mut response: Value = serde_json::from_str(&raw).ok()?;
+    if let Some(error) = response.get("error") {
+        let message = error.get("message").and_then(Value::as_str);
+        return Some(Err(message.unwrap_or("IPC 调用失败").to_string()));
+    }
+    Some(Ok(response["result"].take()))
+}
+
+/// 逐字节比较全部内容，耗时与不匹配的位置无关。
+fn token_matches(expected: &str, provided: &str) -> bool {
+    expected.len() == provided.len()
+        && expected
+            .bytes()
+            .zip(provided.bytes())
+            .fold(0u8, |acc, (a, b)| acc | (a ^ b))
+            == 0
+}
+
+fn handle_connection(app: &AppHandle, token: &str, reader: impl Read, mut writer: impl Write) {
+    let mut reader = BufReader::new(reader);
+    loop {
+        let mut line = String::new();
+        match reader.by_ref().take(MAX_REQUEST_BYTES).read_line(&mut line) {
+            Ok(0) => return,
+            Ok(_) if !line.ends_with('\n') && line.len() as u64 >= MAX_REQUEST_BYTES => {
+                let _ = write_response(
+                    &mut writer,
+                    &Response::err(Value::Null, INVALID_REQUEST, "请求过长"),
+                );
+                return;
+            }
+            Ok(_) => {}
+            Err(_) => return,
+        }
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let response = handle_line(app, token, &line);
+        if write_response(&mut writer, &response).is_err() {
+            return;
+        }
+    }
+}
+
+fn write_response(writer: &mut impl Write, response: &Response) -> std::io::Result<()> {
+    let mut out = serde_json::to_vec(response)?;
+    out.push(b'\n');
+    writer.write_all(&out)?;
+    writer.flush()
+}
+
+fn handle_line(app: &AppHandle, token: &str, line: &str) -> Response {
+    let request: Request = match serde_json::from_str(line) {
+        Ok(request) => request,
+        Err(err) => return Response::err(Value::Null, PARSE_ERROR, format!("解析请求失败: {err}")),
+    };
+    if request.jsonrpc.as_deref().is_some_and(|v| v != "2.0") {
+        return Response::err(request.id, INVALID_REQUEST, "仅支持 JSON-RPC 2.0");
+    }
+    if !request
+        .token
+        .as_deref()
+        .is_some_and(|provided| token_matches(token, provided))
+    {
+        return Response::err(request.id, UNAUTHORIZED, "令牌无效");
+    }
+
+    match dispatch(app, &request.method, request.params) {
+        Ok(result) => Response::ok(request.id, result),
+        Err((code, message)) => Response::err(request.id, code, message),
+    }
+}
+
+fn params<T: DeserializeOwned>(params: Value) -> Result<T, (i64, String)> {
+    // 省略 params 时按空对象处理，便于只有可选字段的方法。
+    let params = if params.is_null() {
+        Value::Object(Default::default())
+    } else {
+        params
+    };
+    serde_json::from_value(params).map_err(|e| (INVALID_PARAMS, format!("参数无效: {e}")))
+}
+
+fn to_value<T: Serialize>(result: Result<T, String>) -> Result<Value, (i64, String)> {
+    let value = result.map_err(|err| (APP_ERROR, err))?;
+    serde_json::to_value(value).map_err(|e| (APP_ERROR, format!("序列化结果失败: {e}")))
+}
+
+/// 与同名 Tauri 命令共用实现；修改类方法完成后通知界面刷新。
+fn dispatch(app: &AppHandle, method: &str, raw: Value) -> Result<Value, (i64, String)> {
+    let state = app.state::<AppState>();
+    match method {
+        "get_history" => to_value(get_history(app.clone())),
+        "search_history" => to_value(search_history(
+            params::<search::SearchPayload>(raw)?,
+            app.clone(),
+        )),
+        "copy_history_item" => {
+            let params = params::<CopyParams>(raw)?;
+            to_value(copy_history_item(
+                params.id,
+                params.fields,
+                app.clone(),
+                state,
+            ))
+        }
+        "toggle_favorite" => {
+            let result = to_value(toggle_favorite(
+                params::<IdParams>(raw)?.id,
+                app.clone(),
+                state,
+            ));
+            notify(app, HISTORY_CHANGED_EVENT, &result);
+            result
+        }
+        "delete_history_item" => {
+            let result = to_value(delete_history_item(
+                params::<IdParams>(raw)?.id,
+                app.clone(),
+                state,
+            ));
+            notify(app, HISTORY_CHANGED_EVENT, &result);
+            result
+        }
+        "clear_history" => {
+            let result = to_value(clear_history(app.clone(), state));
+            notify(app, HISTORY_CHANGED_EVENT, &result);
+            result
+        }
+        "get_settings" => to_value(get_settings(app.clone())),
+        "update_settings" => {
+            let result = to_value(update_settings(
+                params::<UpdateSettingsPayload>(raw)?,
+                app.clone(),
+            ));
+            notify(app, SETTINGS_CHANGED_EVENT, &result);
+            result
+        }
+        other => Err((METHOD_NOT_FOUND, format!("未知方法: {other}"))),
+    }
+}
+
+fn notify(app: &AppHandle, event: &str, result: &Result<Value, (i64, String)>) {
+    if result.is_ok() {
+        let _ = app.emit(event, ());
+    }
+}
+
+#[cfg(unix)]
+mod platform {
+    use std::fs::{self, DirBuilder, OpenOptions};
+    use std::io::{ErrorKind, Write};
+    use std::os::unix::fs::{DirBuilderExt, OpenOptionsExt, PermissionsExt};
+    use std::os::unix::net::{UnixListener, UnixStream};
+    use std::path::{Path, PathBuf};
+    use std::time::Duration;
+
+    /// 套接字放在仅当前用户可访问的子目录中：绑定时套接字文件按 umask 创建，不能依赖它自身的权限。
+    pub(crate) const SOCKET_DIR_NAME: &str = "ipc";
+    pub(crate) const SOCKET_FILE_NAME: &str = "clipboard-history.sock";
+    /// 客户端等待应答的上限，实例卡住时不让命令行一直挂起。
+    const CLIENT_TIMEOUT: Duration = Duration::from_secs(10);
+
+    fn socket_path(root: &Path) -> PathBuf {
+        root.join(SOCKET_DIR_NAME).join(SOCKET_FILE_NAME)
+    }
+
+    pub(super) fn connect(root: &Path) -> Option<(UnixStream, UnixStream)> {
+        let stream = UnixStream::connect(socket_path(root)).ok()?;
+        stream.set_read_timeout(Some(CLIENT_TIMEOUT)).ok()?;
+        Some((stream.try_clone().ok()?, stream))
+    }
+
+    /// 以 0600 新建文件后写入，内容不会有任何时刻对其他用户可读；已有的旧文件先删除。
+    pub(super) fn write_private(path: &Path, contents: &str) -> std::io::Result<()> {
+        match fs::remove_file(path) {
+            Err(err) if err.kind() != ErrorKind::NotFound => return Err(err),
+            _ => {}
+        }
+        OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .mode(0o600)
+            .open(path)?
+            .write_all(contents.as_bytes())
+    }
+
+    pub(super) fn listen(
+        root: &Path,
+        mut on_client: impl FnMut(UnixStream, UnixStream),
+    ) -> Result<(), String> {
+        let dir = root.join(SOCKET_DIR_NAME);
+        DirBuilder::new()
+            .recursive(true)
+            .mode(0o700)
+            .create(&dir)
+            .and_then(|_| fs::set_permissions(&dir, fs::Permissions::from_mode(0o700)))
+            .map_err(|e| format!("创建 IPC 目录失败: {e}"))?;
+
+        let path = socket_path(root);
+        if path.exists() {
+            // 能连上说明已有实例在服务，不抢占；否则是上次异常退出遗留的文件。
+            if UnixStream::connect(&path).is_ok() {
+                return Err("IPC 套接字已被其他实例占用".to_string());
+            }
+            fs::remove_file(&path).map_err(|e| format!("删除旧的 IPC 套接字失败: {e}"))?;
+        }
+        let listener =
+            UnixListener::bind(&path).map_err(|e| format!("创建 IPC 套接字失败: {e}"))?;
+
+        for stream in listener.incoming() {
+            let Ok(stream) = stream else {
+                continue;
+            };
+            if let Ok(reader) = stream.try_clone() {
+                on_client(reader, stream);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(windows)]
+mod platform {
+    use std::ffi::c_void;
+    use std::fs::{self, File, OpenOptions};
+    use std::io::{ErrorKind, Write};
+    use std::os::windows::io::{AsRawHandle, FromRawHandle};
+    use std::path::Path;
+
+    const PIPE_ACCESS_DUPLEX: u32 = 0x0000_0003;
+    const FILE_FLAG_FIRST_PIPE_INSTANCE: u32 = 0x0008_0000;
+    const PIPE_TYPE_BYTE: u32 = 0x0000_0000;
+    const PIPE_WAIT: u32 = 0x0000_0000;
+    const PIPE_REJECT_REMOTE_CLIENTS: u32 = 0x0000_0008;
+    const PIPE_UNLIMITED_INSTANCES: u32 = 255;
+    const BUFFER_SIZE: u32 = 64 * 1024;
+    const ERROR_PIPE_CONNECTED: u32 = 535;
+    const PROCESS_QUERY_LIMITED_INFORMATION: u32 = 0x1000;
+    const TOKEN_QUERY: u32 = 0x0008;
+    const TOKEN_USER: u32 = 1;
+    const SDDL_REVISION_1: u32 = 1;
+
+    #[repr(C)]
+    struct SecurityAttributes {
+        length: u32,
+        security_descriptor: *mut c_void,
+        inherit_handle: i32,
+    }
+
+    extern "system" {
+        fn CreateNamedPipeW(
+            name: *const u16,
+            open_mode: u32,
+            pipe_mode: u32,
+            max_instances: u32,
+            out_buffer_size: u32,
+            in_buffer_size: u32,
+            default_timeout: u32,
+            security_attributes: *mut SecurityAttributes,
+        ) -> *mut c_void;
+        fn ConnectNamedPipe(pipe: *mut c_void, overlapped: *mut c_void) -> i32;
+        fn GetNamedPipeServerProcessId(pipe: *mut c_void, pid: *mut u32) -> i32;
+        fn GetCurrentProcess() -> *mut c_void;
+        fn OpenProcess(access: u32, inherit: i32, pid: u32) -> *mut c_void;
+        fn CloseHandle(handle: *mut c_void) -> i32;
+        fn LocalFree(memory: *mut c_void) -> *mut c_void;
+        fn GetLastError() -> u32;
+    }
+
+    #[link(name = "advapi32")]
+    extern "system" {
+        fn OpenProcessToken(process: *mut c_void, access: u32, token: *mut *mut c_void) -> i32;
+        fn GetTokenInformation(
+            token: *mut c_void,
+            class: u32,
+            info: *mut c_void,
+            len: u32,
+            returned: *mut u32,
+        ) -> i32;
+        fn GetLengthSid(sid: *mut c_void) -> u32;
+        fn ConvertSidToStringSidW(sid: *mut c_void, string: *mut *mut u16) -> i32;
+        fn ConvertStringSecurityDescriptorToSecurityDescriptorW(
+            sddl: *const u16,
+            revision: u32,
+            descriptor: *mut *mut c_void,
+            size: *mut u32,
+        ) -> i32;
+    }
+
+    /// 管道名按用户区分；名字本身任何人都能抢先创建，访问控制靠管道的 DACL 与客户端对服务端身份的检查。
+    pub(crate) fn pipe_name() -> String {
+        let user = std::env::var("USERNAME").unwrap_or_default();
+        format!(r"\\.\pipe\clipboard-history-{user}")
+    }
+
+    /// 进程令牌中的用户 SID（原始字节）。
+    fn process_user_sid(process: *mut c_void) -> Option<Vec<u8>> {
+        unsafe {
+            let mut token = std::ptr::null_mut();
+            if OpenProcessToken(process, TOKEN_QUERY, &mut token) == 0 {
+                return None;
+            }
+            let mut len = 0u32;
+            GetTokenInformation(token, TOKEN_USER, std::ptr::null_mut(), 0, &mut len);
+            // `TOKEN_USER` 以指向 SID 的指针开头，按指针对齐分配缓冲区。
+            let mut buf = vec![0usize; (len as usize).div_ceil(size_of::<usize>())];
+            let ok = GetTokenInformation(token, TOKEN_USER, buf.as_mut_ptr().cast(), len, &mut len);
+            CloseHandle(token);
+            if ok == 0 {
+                return None;
+            }
+            let sid = buf[0] as *mut c_void;
+            let sid_len = GetLengthSid(sid) as usize;
+            Some(std::slice::from_raw_parts(sid.cast::<u8>(), sid_len).to_vec())
+        }
+    }
+
+    fn current_user_sid() -> Option<Vec<u8>> {
+        process_user_sid(unsafe { GetCurrentProcess() })
+    }
+
+    /// 只允许当前用户访问的安全描述符，用 `LocalFree` 释放。
+    fn owner_only_descriptor() -> Result<*mut c_void, String> {
+        let mut sid = current_user_sid().ok_or_else(|| "读取当前用户 SID 失败".to_string())?;
+        unsafe {
+            let mut sid_string = std::ptr::null_mut();
+            if ConvertSidToStringSidW(sid.as_mut_ptr().cast(), &mut sid_string) == 0 {
+                return Err(format!("转换用户 SID 失败: {}", GetLastError()));
+            }
+            let len = (0..).take_while(|i| *sid_string.add(*i) != 0).count();
+            let sid_text = String::from_utf16_lossy(std::slice::from_raw_parts(sid_string, len));
+            LocalFree(sid_string.cast());
+
+            let sddl: Vec<u16> = format!("D:P(A;;GA;;;{sid_text})")
+                .encode_utf16()
+                .chain(std::iter::once(0))
+                .collect();
+            let mut descriptor = std::ptr::null_mut();
+            if ConvertStringSecurityDescriptorToSecurityDescriptorW(
+                sddl.as_ptr(),
+                SDDL_REVISION_1,
+                &mut descriptor,
+                std::ptr::null_mut(),
+            ) == 0
+            {
+                return Err(format!("创建管道安全描述符失败: {}", GetLastError()));
+            }
+            Ok(descriptor)
+        }
+    }
+
+    /// 管道服务端进程须属于当前用户，否则可能是其他用户抢先创建的同名管道，不能把令牌发过去。
+    fn served_by_current_user(pipe: &File) -> bool {
+        let mut pid = 0u32;
+        if unsafe { GetNamedPipeServerProcessId(pipe.as_raw_handle(), &mut pid) } == 0 {
+            return false;
+        }
+        let process = unsafe { OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, 0, pid) };
+        if process.is_null() {
+            return false;
+        }
+        let server = process_user_sid(process);
+        unsafe { CloseHandle(process) };
+        server.is_some() && server == current_user_sid()
+    }
+
+    /// 应用数据目录位于用户配置目录下，新文件继承其仅当前用户可访问的 ACL；已有的旧文件先删除。
+    pub(super) fn write_private(path: &Path, contents: &str) -> std::io::Result<()> {
+        match fs::remove_file(path) {
+            Err(err) if err.kind() != ErrorKind::NotFound => return Err(err),
+            _ => {}
+        }
+        OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(path)?
+            .write_all(contents.as_bytes())
+    }
+
+    pub(super) fn connect(_root: &Path) -> Option<(File, File)> {
+        let stream = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(pipe_name())
+            .ok()?;
+        if !served_by_current_user(&stream) {
+            return None;
+        }
+        Some((stream.try_clone().ok()?, stream))
+    }
+
+    pub(super) fn listen(
+        _root: &Path,
+        mut on_client: impl FnMut(File, File),
+    ) -> Result<(), String> {
+        let name: Vec<u16> = pipe_name()
+            .encode_utf16()
+            .chain(std::iter::once(0))
+            .collect();
+        let descriptor = owner_only_descriptor()?;
+        let mut attributes = SecurityAttributes {
+            length: size_of::<SecurityAttributes>() as u32,
+            security_descriptor: descriptor,
+            inherit_handle: 0,
+        };
+        let mut first = true;
+        loop {
+            let open_mode = if first {
+                PIPE_ACCESS_DUPLEX | FILE_FLAG_FIRST_PIPE_INSTANCE
+            } else {
+                PIPE_ACCESS_DUPLEX
+            };
+            let pipe = unsafe {
+                CreateNamedPipeW(
+                    name.as_ptr(),
+                    open_mode,
+                    PIPE_TYPE_BYTE | PIPE_WAIT | PIPE_REJECT_REMOTE_CLIENTS,
+                    PIPE_UNLIMITED_INSTANCES,
+                    BUFFER_SIZE,
+                    BUFFER_SIZE,
+                    0,
+                    &mut attributes,
+                )
+            };
+            if pipe.is_null() || pipe as isize == -1 {
+                let err = unsafe { GetLastError() };
+                unsafe { LocalFree(descriptor) };
+                return Err(format!("创建命名管道失败: {err}"));
+            }
+            first = false;
+
+            let connected = unsafe { ConnectNamedPipe(pipe, std::ptr::null_mut()) } != 0
+                || unsafe { GetLastError() } == ERROR_PIPE_CONNECTED;
+            if !connected {
+                unsafe { CloseHandle(pipe) };
+                continue;
+            }
+            let stream = unsafe { File::from_raw_handle(pipe) };
+            if let Ok(reader) = stream.try_clone() {
+                on_client(reader, stream);
+            }
+        }
+    }
+}
@@ -5,39 +5,100 @@ use image::{DynamicImage, ImageFormat, RgbaImage};
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
 use std::borrow::Cow;
+use std::collections::HashMap;
 use std::fs;
 use std::fs::OpenOptions;
 use std::io::Cursor;
 use std::io::Write;
 use std::path::{Path, PathBuf};
-use std::sync::Mutex;
+use std::sync::{Mutex, MutexGuard};
 use std::thread;
 use std::time::Duration;
 use std::time::Instant;
 use std::time::{SystemTime, UNIX_EPOCH};
 use tauri::menu::{Menu, MenuItem};
 use tauri::tray::{MouseButton, MouseButtonState, TrayIconBuilder, TrayIconEvent};
-use tauri::{AppHandle, Manager, PhysicalPosition, Position, State, WebviewWindow, WindowEvent};
+use tauri::{
+    AppHandle, Emitter, Manager, PhysicalPosition, Position, State, WebviewWindow, WindowEvent,
+};
 use tauri_plugin_autostart::ManagerExt as AutostartExt;
 use tauri_plugin_clipboard_manager::ClipboardExt;
-use tauri_plugin_global_shortcut::{GlobalShortcutExt, Shortcut, ShortcutState};
+use tauri_plugin_global_shortcut::ShortcutState;
 use tauri_plugin_opener::OpenerExt;
 
+mod archive;
+mod autopaste;
+mod cli;
+mod crypto;
+mod images;
+mod importers;
+mod integrity;
+mod ipc;
+#[cfg(target_os = "linux")]
+mod ownership;
+mod paste_queue;
+mod retention;
+mod search;
+mod selection;
+mod sensitive;
+mod shortcuts;
+mod snapshot;
+mod snippets;
+mod source;
+mod storage;
+mod template;
+mod transform;
+mod watcher;
+#[cfg(target_os = "linux")]
+mod wayland;
+
 const HISTORY_FILE_NAME: &str = "clipboard-history.json";
 const SETTINGS_FILE_NAME: &str = "settings.json";
 const IMAGE_DIR_NAME: &str = "clipboard-images";
 const LOG_FILE_NAME: &str = "clipboard-history.log";
 const AUTOSTART_LAUNCH_ARG: &str = "--autostart";
+const CAPTURE_PAUSED_EVENT: &str = "clipboard://capture-paused";
+/// 新内容挤出了超出历史上限的旧条目，负载为移除的条数。
+const EVICTED_EVENT: &str = "clipboard://evicted";
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
+#[serde(default, rename_all = "camelCase")]
 struct AppSettings {
     poll_interval_ms: u64,
+    /// 未收藏条目的上限，收藏不计入。
     history_limit: usize,
+    /// 收藏条目的上限，达到后需先取消收藏才能收藏新条目。
+    favorite_limit: usize,
     storage_dir: String,
     global_shortcut: String,
     launch_at_startup: bool,
     always_on_top: bool,
+    sensitive_action: String,
+    sensitive_expiry_minutes: u64,
+    sensitive_detectors: Vec<String>,
+    ignored_apps: Vec<String>,
+    retention_days: u64,
+    image_retention_days: u64,
+    max_image_disk_mb: u64,
+    expand_templates: bool,
+    /// 粘贴队列取下一条的快捷键，空字符串表示不注册。
+    paste_queue_shortcut: String,
+    /// 在弹窗中选中条目后自动切回之前的窗口并粘贴。
+    paste_on_select: bool,
+    /// 自动粘贴时改用 Ctrl+Shift+V 的程序（通常是终端）。
+    shift_paste_apps: Vec<String>,
+    /// 额外的全局快捷键，每项把一个组合键绑定到一个动作。
+    keymap: Vec<shortcuts::ShortcutBinding>,
+    /// 采集时额外保存剪贴板提供的全部格式，回填时一并恢复。
+    full_snapshot: bool,
+    /// 完整快照中单个格式的大小上限，超出的格式不保存。
+    snapshot_format_limit_kb: u64,
+    /// 同时采集 Linux 的 PRIMARY 选区（选中即记录），条目标记为来自选区。
+    capture_primary: bool,
+    /// PRIMARY 与剪贴板之间的文本同步方向：`off`、`to-clipboard`、`to-primary` 或 `both`。
+    selection_sync: String,
+    /// 复制内容的程序退出后由本程序接管剪贴板，重新提供最新一条历史。
+    keep_clipboard: bool,
 }
 
 impl Default for AppSettings {
@@ -45,10 +106,47 @@ impl Default for AppSettings {
         Self {
             poll_interval_ms: 800,
             history_limit: 300,
+            favorite_limit: 1000,
             storage_dir: String::new(),
             global_shortcut: "Alt+Shift+V".to_string(),
             launch_at_startup: false,
             always_on_top: false,
+            sensitive_action: sensitive::ACTION_MARK.to_string(),
+            sensitive_expiry_minutes: 10,
+            sensitive_detectors: sensitive::builtin_detector_names()
+                .into_iter()
+                .map(str::to_string)
+                .collect(),
+            ignored_apps: ["KeePassXC", "1Password", "Bitwarden"]
+                .into_iter()
+                .map(str::to_string)
+                .collect(),
+            retention_days: 0,
+            image_retention_days: 0,
+            max_image_disk_mb: 0,
+            expand_templates: false,
+            paste_queue_shortcut: "Alt+Shift+N".to_string(),
+            paste_on_select: false,
+            shift_paste_apps: [
+                "Gnome-terminal",
+                "konsole",
+                "Xfce4-terminal",
+                "Alacritty",
+                "kitty",
+                "foot",
+                "org.wezfurlong.wezterm",
+                "Tilix",
+                "Terminator",
+            ]
+            .into_iter()
+            .map(str::to_string)
+            .collect(),
+            keymap: Vec::new(),
+            full_snapshot: false,
+            snapshot_format_limit_kb: 1024,
+            capture_primary: false,
+            selection_sync: selection::SYNC_OFF.to_string(),
+            keep_clipboard: true,
         }
     }
 }
@@ -58,10 +156,38 @@ impl Default for AppSettings {
 struct UpdateSettingsPayload {
     poll_interval_ms: Option<u64>,
     history_limit: Option<usize>,
+    favorite_limit: Option<usize>,
     storage_dir: Option<String>,
     global_shortcut: Option<String>,
     launch_at_startup: Option<bool>,
     always_on_top: Option<bool>,
+    sensitive_action: Option<String>,
+    sensitive_expiry_minutes: Option<u64>,
+    sensitive_detectors: Option<Vec<String>>,
+    ignored_apps: Option<Vec<String>>,
+    retention_days: Option<u64>,
+    image_retention_days: Option<u64>,
+    max_image_disk_mb: Option<u64>,
+    expand_templates: Option<bool>,
+    paste_queue_shortcut: Option<String>,
+    paste_on_select: Option<bool>,
+    shift_paste_apps: Option<Vec<String>>,
+    keymap: Option<Vec<shortcuts::ShortcutBinding>>,
+    full_snapshot: Option<bool>,
+    snapshot_format_limit_kb: Option<u64>,
+    capture_primary: Option<bool>,
+    selection_sync: Option<String>,
+    keep_clipboard: Option<bool>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct FileEntry {
+    path: String,
+    is_dir: bool,
+    size: Option<u64>,
+    modified_at: Option<u64>,
+    exists: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -70,6 +196,8 @@ struct ClipboardItem {
     #[serde(rename = "type")]
     item_type: String,
     text: Option<String>,
+    html: Option<String>,
+    files: Option<Vec<FileEntry>>,
     #[serde(rename = "imagePath")]
     image_path: Option<String>,
     #[serde(rename = "imagePreviewDataUrl")]
@@ -82,6 +210,15 @@ struct ClipboardItem {
     created_at: u64,
     #[serde(rename = "updatedAt")]
     updated_at: u64,
+    #[serde(rename = "isSensitive", default)]
+    is_sensitive: bool,
+    #[serde(rename = "expiresAt", default)]
+    expires_at: Option<u64>,
+    #[serde(rename = "sourceApp", default)]
+    source_app: Option<String>,
+    /// 采集自哪个选区；`None` 为普通剪贴板，`primary` 为 Linux 的 PRIMARY 选区。
+    #[serde(default)]
+    selection: Option<String>,
 }
 
 struct AppState {
@@ -89,6 +226,16 @@ struct AppState {
     history_lock: Mutex<()>,
     last_diagnostic_log_at: Mutex<u64>,
     suppress_auto_hide_until: Mutex<u64>,
+    encryption_key: Mutex<Option<crypto::Key>>,
+    /// 已完成目录创建与旧版历史导入的存储目录；切换目录或解锁前为 `None` 或旧目录，需要重新检查。
+    storage_ready: Mutex<Option<PathBuf>>,
+    paste_queue: Mutex<paste_queue::PasteQueue>,
+    /// 快捷键唤起弹窗前获得焦点的窗口，自动粘贴时把焦点还给它。
+    paste_target: Mutex<Option<source::ActiveWindow>>,
+    /// 暂停期间后台监听不采集任何内容。
+    capture_paused: Mutex<bool>,
+    /// 后台监听实际使用的方式。
+    watcher_backend: Mutex<watcher::BackendStatus>,
 }
 
 impl Default for AppState {
@@ -98,10 +245,34 @@ impl Default for AppState {
             history_lock: Mutex::new(()),
             last_diagnostic_log_at: Mutex::new(0),
             suppress_auto_hide_until: Mutex::new(0),
+            encryption_key: Mutex::new(None),
+            storage_ready: Mutex::new(None),
+            paste_queue: Mutex::new(paste_queue::PasteQueue::default()),
+            paste_target: Mutex::new(None),
+            capture_paused: Mutex::new(false),
+            watcher_backend: Mutex::new(watcher::BackendStatus::default()),
         }
     }
 }
 
+/// 修改历史时持有：进程内互斥锁串行化各线程，数据目录下的文件锁串行化与命令行等其他进程的写入。
+struct HistoryGuard<'a> {
+    _thread: MutexGuard<'a, ()>,
+    _process: fs::File,
+}
+
+fn lock_history<'a>(app: &AppHandle, state: &'a AppState) -> Result<HistoryGuard<'a>, String> {
+    let thread = state
+        .history_lock
+        .lock()
+        .map_err(|_| "历史锁获取失败".to_string())?;
+    let process = storage::lock_dir(&data_dir(app)?)?;
+    Ok(HistoryGuard {
+        _thread: thread,
+        _process: process,
+    })
+}
+
 fn now_ms() -> u64 {
     SystemTime::now()
         .duration_since(UNIX_EPOCH)
@@ -166,14 +337,67 @@ fn sanitize_shortcut(shortcut: &str) -> String {
     }
 }
 
+fn normalize_app_names(names: &[String]) -> Vec<String> {
+    let mut normalized: Vec<String> = Vec::new();
+    for name in names {
+        let name = name.trim();
+        if !name.is_empty() && !normalized.iter().any(|v| v.eq_ignore_ascii_case(name)) {
+            normalized.push(name.to_string());
+        }
+    }
+    normalized
+}
+
 fn normalize_settings(mut settings: AppSettings) -> AppSettings {
     settings.poll_interval_ms = settings.poll_interval_ms.clamp(300, 5000);
     settings.history_limit = settings.history_limit.clamp(50, 5000);
+    settings.favorite_limit = settings.favorite_limit.clamp(10, 10000);
     settings.storage_dir = settings.storage_dir.trim().to_string();
     settings.global_shortcut = sanitize_shortcut(&settings.global_shortcut);
     if settings.global_shortcut.is_empty() {
         settings.global_shortcut = "Alt+Shift+V".to_string();
     }
+    settings.paste_queue_shortcut = if settings.paste_queue_shortcut.trim().is_empty() {
+        String::new()
+    } else {
+        sanitize_shortcut(&settings.paste_queue_shortcut)
+    };
+    if ![
+        sensitive::ACTION_OFF,
+        sensitive::ACTION_MARK,
+        sensitive::ACTION_MASK,
+        sensitive::ACTION_SKIP,
+    ]
+    .contains(&settings.sensitive_action.as_str())
+    {
+        settings.sensitive_action = sensitive::ACTION_MARK.to_string();
+    }
+    settings.sensitive_expiry_minutes = settings.sensitive_expiry_minutes.clamp(1, 1440);
+    let known = sensitive::builtin_detector_names();
+    settings
+        .sensitive_detectors
+        .retain(|name| known.contains(&name.as_str()));
+    settings.sensitive_detectors.sort();
+    settings.sensitive_detectors.dedup();
+    settings.ignored_apps = normalize_app_names(&settings.ignored_apps);
+    settings.shift_paste_apps = normalize_app_names(&settings.shift_paste_apps);
+    settings.keymap = settings
+        .keymap
+        .into_iter()
+        .filter(|binding| !binding.accelerator.trim().is_empty())
+        .map(|binding| shortcuts::ShortcutBinding {
+            accelerator: sanitize_shortcut(&binding.accelerator),
+            action: binding.action.trim().to_string(),
+            ..binding
+        })
+        .collect();
+    settings.retention_days = settings.retention_days.min(3650);
+    settings.image_retention_days = settings.image_retention_days.min(3650);
+    settings.max_image_disk_mb = settings.max_image_disk_mb.min(1024 * 1024);
+    settings.snapshot_format_limit_kb = settings.snapshot_format_limit_kb.clamp(16, 16 * 1024);
+    if !selection::is_sync_mode(&settings.selection_sync) {
+        settings.selection_sync = selection::SYNC_OFF.to_string();
+    }
     settings
 }
 
@@ -254,6 +478,11 @@ fn show_main_window_at_cursor(app: &AppHandle) {
             return;
         }
 
+        if load_settings(app).is_ok_and(|settings| settings.paste_on_select) {
+            if let Ok(mut target) = app.state::<AppState>().paste_target.lock() {
+                *target = source::active_window();
+            }
+        }
         if let Ok(cursor) = app.cursor_position() {
             let x = (cursor.x.round() as i32).saturating_add(12);
             let y = (cursor.y.round() as i32).saturating_add(12);
@@ -341,20 +570,6 @@ fn setup_tray(app: &AppHandle) -> Result<(), String> {
     Ok(())
 }
 
-fn register_global_shortcut(app: &AppHandle, accelerator: &str) -> Result<(), String> {
-    let shortcut: Shortcut = accelerator
-        .parse()
-        .map_err(|e| format!("快捷键格式无效: {e}"))?;
-
-    app.global_shortcut()
-        .unregister_all()
-        .map_err(|e| format!("清理旧快捷键失败: {e}"))?;
-
-    app.global_shortcut()
-        .register(shortcut)
-        .map_err(|e| format!("注册快捷键失败: {e}"))
-}
-
 fn app_root_dir(app: &AppHandle) -> Result<PathBuf, String> {
     let path = app
         .path()
@@ -380,7 +595,7 @@ fn append_log(app: &AppHandle, level: &str, message: &str) {
     let _ = file.write_all(line.as_bytes());
 }
 
-fn append_diagnostic_log_throttled(app: &AppHandle, state: &State<AppState>, message: &str) {
+fn append_diagnostic_log_throttled(app: &AppHandle, state: &AppState, message: &str) {
     let now = now_ms();
     let Ok(mut last) = state.last_diagnostic_log_at.lock() else {
         return;
@@ -395,7 +610,11 @@ fn append_diagnostic_log_throttled(app: &AppHandle, state: &State<AppState>, mes
 }
 
 fn load_settings(app: &AppHandle) -> Result<AppSettings, String> {
-    let path = settings_file(app)?;
+    load_settings_from(&app_root_dir(app)?)
+}
+
+fn load_settings_from(root: &Path) -> Result<AppSettings, String> {
+    let path = root.join(SETTINGS_FILE_NAME);
     if !path.exists() {
         return Ok(AppSettings::default());
     }
@@ -413,8 +632,12 @@ fn save_settings(app: &AppHandle, settings: &AppSettings) -> Result<(), String>
 }
 
 fn data_dir_from_settings(app: &AppHandle, settings: &AppSettings) -> Result<PathBuf, String> {
+    resolve_data_dir(&app_root_dir(app)?, settings)
+}
+
+fn resolve_data_dir(root: &Path, settings: &AppSettings) -> Result<PathBuf, String> {
     let path = if settings.storage_dir.is_empty() {
-        app_root_dir(app)?
+        root.to_path_buf()
     } else {
         PathBuf::from(&settings.storage_dir)
     };
@@ -427,24 +650,58 @@ fn data_dir(app: &AppHandle) -> Result<PathBuf, String> {
     data_dir_from_settings(app, &settings)
 }
 
-fn image_dir(app: &AppHandle) -> Result<PathBuf, String> {
-    let dir = data_dir(app)?.join(IMAGE_DIR_NAME);
-    fs::create_dir_all(&dir).map_err(|e| format!("创建图片目录失败: {e}"))?;
-    Ok(dir)
+fn open_history_db(app: &AppHandle) -> Result<rusqlite::Connection, String> {
+    storage::open(&data_dir(app)?)
 }
 
-fn history_file(app: &AppHandle) -> Result<PathBuf, String> {
-    Ok(data_dir(app)?.join(HISTORY_FILE_NAME))
+fn unlocked_key(app: &AppHandle) -> Result<Option<crypto::Key>, String> {
+    Ok(app
+        .state::<AppState>()
+        .encryption_key
+        .lock()
+        .map_err(|_| "密钥锁获取失败".to_string())?
+        .clone())
+}
+
+fn storage_key(app: &AppHandle) -> Result<Option<crypto::Key>, String> {
+    if !crypto::is_enabled(&data_dir(app)?) {
+        return Ok(None);
+    }
+    unlocked_key(app)?
+        .map(Some)
+        .ok_or_else(|| "存储已加密，请先解锁".to_string())
 }
 
+/// 创建存储目录并导入旧版历史；同一目录只做一次，之后的调用直接返回。
 fn ensure_storage_layout(app: &AppHandle) -> Result<(), String> {
-    let base = data_dir(app)?;
+    let settings = load_settings(app)?;
+    let base = data_dir_from_settings(app, &settings)?;
+    let state = app.state::<AppState>();
+    let mut ready = state
+        .storage_ready
+        .lock()
+        .map_err(|_| "存储状态锁获取失败".to_string())?;
+    if ready.as_deref() == Some(base.as_path()) {
+        return Ok(());
+    }
     fs::create_dir_all(base.join(IMAGE_DIR_NAME)).map_err(|e| format!("创建图片目录失败: {e}"))?;
 
-    let history = base.join(HISTORY_FILE_NAME);
-    if !history.exists() {
-        fs::write(&history, "[]").map_err(|e| format!("初始化历史文件失败: {e}"))?;
+    let key = unlocked_key(app)?;
+    if crypto::is_enabled(&base) && key.is_none() {
+        return Ok(());
+    }
+
+    let mut conn = storage::open(&base)?;
+    let imported =
+        storage::import_legacy_json(&mut conn, key.as_ref(), &base, settings.history_limit)?;
+    if imported > 0 {
+        append_log(
+            app,
+            "INFO",
+            &format!("imported {imported} items from legacy {HISTORY_FILE_NAME}"),
+        );
     }
+    *ready = Some(base);
     Ok(())
 }
 
@@ -455,6 +712,14 @@ fn migrate_storage_if_needed(old_dir: &Path, new_dir: &Path) -> Result<(), Strin
 
     fs::create_dir_all(new_dir).map_err(|e| format!("创建新目录失败: {e}"))?;
 
+    storage::copy_database(old_dir, new_dir)?;
+
+    let old_key_file = old_dir.join(crypto::KEY_FILE_NAME);
+    let new_key_file = new_dir.join(crypto::KEY_FILE_NAME);
+    if old_key_file.exists() && !new_key_file.exists() {
+        fs::copy(&old_key_file, &new_key_file).map_err(|e| format!("迁移密钥文件失败: {e}"))?;
+    }
+
     let old_history = old_dir.join(HISTORY_FILE_NAME);
     let new_history = new_dir.join(HISTORY_FILE_NAME);
     if old_history.exists() && !new_history.exists() {
@@ -481,26 +746,13 @@ fn migrate_storage_if_needed(old_dir: &Path, new_dir: &Path) -> Result<(), Strin
     Ok(())
 }
 
-fn load_history(app: &AppHandle) -> Result<Vec<ClipboardItem>, String> {
-    let path = history_file(app)?;
-    if !path.exists() {
-        return Ok(Vec::new());
-    }
-
-    let raw = fs::read_to_string(&path).map_err(|e| format!("读取历史失败: {e}"))?;
-    let mut items: Vec<ClipboardItem> =
-        serde_json::from_str(&raw).map_err(|e| format!("解析历史失败: {e}"))?;
-    items.sort_by(|a, b| b.updated_at.cmp(&a.updated_at));
-    Ok(items)
-}
-
 fn clean_history(items: Vec<ClipboardItem>, history_limit: usize) -> Vec<ClipboardItem> {
     let mut sorted = items;
     sorted.sort_by(|a, b| b.updated_at.cmp(&a.updated_at));
 
     let mut cleaned: Vec<ClipboardItem> = Vec::new();
     for mut item in sorted {
-        if item.item_type == "text" {
+        if item.item_type == "text" || item.item_type == "html" {
             if let Some(text) = item.text.as_deref() {
                 item.text = Some(normalize_text(text));
             }
@@ -523,21 +775,55 @@ fn clean_history(items: Vec<ClipboardItem>, history_limit: usize) -> Vec<Clipboa
         }
     }
 
+    // 收藏不受上限影响，只截断较旧的未收藏条目。
     cleaned.sort_by(|a, b| b.updated_at.cmp(&a.updated_at));
-    if cleaned.len() > history_limit {
-        cleaned.truncate(history_limit);
-    }
+    let mut kept = 0usize;
+    cleaned.retain(|item| {
+        if item.is_favorite {
+            return true;
+        }
+        kept += 1;
+        kept <= history_limit
+    });
     cleaned
 }
 
-fn load_history_clean(app: &AppHandle) -> Result<Vec<ClipboardItem>, String> {
-    let settings = load_settings(app)?;
-    let items = load_history(app)?;
-    let mut cleaned = clean_history(items, settings.history_limit);
-    for item in &mut cleaned {
-        item.image_preview_data_url = None;
+/// 删除被截断条目引用的图片，并通知界面有旧条目因历史上限被移除。
+fn release_evicted(
+    app: &AppHandle,
+    conn: &rusqlite::Connection,
+    evicted: storage::Evicted,
+) -> Result<(), String> {
+    images::release_all(conn, &data_dir(app)?, &evicted.image_paths)?;
+    if evicted.count > 0 {
+        append_log(
+            app,
+            "INFO",
+            &format!("history limit evicted {} item(s)", evicted.count),
+        );
+        let _ = app.emit(EVICTED_EVENT, evicted.count);
     }
-    Ok(cleaned)
+    Ok(())
+}
+
+fn load_history(app: &AppHandle) -> Result<Vec<ClipboardItem>, String> {
+    let conn = open_history_db(app)?;
+    storage::purge_expired(&conn, now_ms())?;
+    let mut items = storage::load_items(&conn, storage_key(app)?.as_ref())?;
+    items.iter_mut().for_each(refresh_file_entries);
+    Ok(items)
+}
+
+fn find_history_item(app: &AppHandle, id: &str) -> Result<ClipboardItem, String> {
+    lookup_history_item(app, id)?.ok_or_else(|| "未找到历史项".to_string())
+}
+
+/// 与 `find_history_item` 相同，但条目不存在时返回 `None`，便于调用方区分删除与读取失败。
+fn lookup_history_item(app: &AppHandle, id: &str) -> Result<Option<ClipboardItem>, String> {
+    let conn = open_history_db(app)?;
+    let mut item = storage::find_item(&conn, storage_key(app)?.as_ref(), id)?;
+    item.iter_mut().for_each(refresh_file_entries);
+    Ok(item)
 }
 
 fn build_image_preview_data_url(
@@ -553,28 +839,14 @@ fn build_image_preview_data_url(
     };
 
     let path = data_dir(app)?.join(rel);
-    let bytes = fs::read(path).map_err(|e| format!("读取图片预览失败: {e}"))?;
+    let bytes = crypto::read_file(storage_key(app)?.as_ref(), &path)
+        .map_err(|e| format!("读取图片预览失败: {e}"))?;
     Ok(Some(format!(
         "data:image/png;base64,{}",
         BASE64.encode(bytes)
     )))
 }
 
-fn save_history(app: &AppHandle, items: &[ClipboardItem]) -> Result<(), String> {
-    let path = history_file(app)?;
-    let to_store: Vec<ClipboardItem> = items
-        .iter()
-        .cloned()
-        .map(|mut item| {
-            item.image_preview_data_url = None;
-            item
-        })
-        .collect();
-    let json =
-        serde_json::to_string_pretty(&to_store).map_err(|e| format!("序列化历史失败: {e}"))?;
-    fs::write(path, json).map_err(|e| format!("写入历史失败: {e}"))
-}
-
 fn encode_rgba_to_png_bytes(image: &ImageData<'_>) -> Result<Vec<u8>, String> {
     let rgba = RgbaImage::from_raw(
         image.width as u32,
@@ -608,15 +880,19 @@ fn encode_dynamic_to_png_bytes(image: DynamicImage) -> Result<Vec<u8>, String> {
 }
 
 fn image_item_from_png_bytes(app: &AppHandle, png_bytes: Vec<u8>) -> Result<ClipboardItem, String> {
+    let key = storage_key(app)?;
+    image_item_in(&data_dir(app)?, key.as_ref(), png_bytes)
+}
+
+/// 把 PNG 写入指定存储目录并生成图片条目；导入时直接传入目标目录与密钥。
+fn image_item_in(
+    base: &Path,
+    key: Option<&crypto::Key>,
+    png_bytes: Vec<u8>,
+) -> Result<ClipboardItem, String> {
     let content_hash = hash_bytes(&png_bytes);
     let now = now_ms();
-    let file_name = format!("{hash}.png", hash = &content_hash[0..24]);
-    let relative_path = format!("{IMAGE_DIR_NAME}/{file_name}");
-    let full_path = image_dir(app)?.join(&file_name);
-    let is_new_file = !full_path.exists();
-    if is_new_file {
-        fs::write(&full_path, &png_bytes).map_err(|e| format!("保存图片失败: {e}"))?;
-    }
+    let (relative_path, is_new_file) = images::store(base, key, &content_hash, &png_bytes)?;
 
     let preview = if is_new_file {
         Some(format!(
@@ -631,12 +907,18 @@ fn image_item_from_png_bytes(app: &AppHandle, png_bytes: Vec<u8>) -> Result<Clip
         id: format!("img-{now}-{suffix}", suffix = &content_hash[0..8]),
         item_type: "image".to_string(),
         text: None,
+        html: None,
+        files: None,
         image_path: Some(relative_path),
         image_preview_data_url: preview,
         content_hash,
         is_favorite: false,
         created_at: now,
         updated_at: now,
+        is_sensitive: false,
+        expires_at: None,
+        source_app: None,
+        selection: None,
     })
 }
 
@@ -889,12 +1171,140 @@ fn to_text_item(text: String) -> ClipboardItem {
         id: format!("txt-{now}-{suffix}", suffix = &content_hash[0..8]),
         item_type: "text".to_string(),
         text: Some(normalized),
+        html: None,
+        files: None,
+        image_path: None,
+        image_preview_data_url: None,
+        content_hash,
+        is_favorite: false,
+        created_at: now,
+        updated_at: now,
+        is_sensitive: false,
+        expires_at: None,
+        source_app: None,
+        selection: None,
+    }
+}
+
+fn html_to_plain_text(html: &str) -> String {
+    let mut plain = String::with_capacity(html.len());
+    let mut rest = html;
+    while let Some(open) = rest.find('<') {
+        plain.push_str(&rest[..open]);
+        let tag_src = &rest[open..];
+        let close = tag_src.find('>').map_or(tag_src.len(), |idx| idx + 1);
+        let tag = tag_src[..close].to_ascii_lowercase();
+        rest = &tag_src[close..];
+
+        for skipped in ["script", "style"] {
+            if tag.starts_with(&format!("<{skipped}")) {
+                let end_tag = format!("</{skipped}");
+                let end = rest
+                    .to_ascii_lowercase()
+                    .find(&end_tag)
+                    .unwrap_or(rest.len());
+                rest = &rest[end..];
+            }
+        }
+        if tag.starts_with("<br")
+            || tag.starts_with("</p")
+            || tag.starts_with("</div")
+            || tag.starts_with("</li")
+            || tag.starts_with("</tr")
+        {
+            plain.push('\n');
+        }
+    }
+    plain.push_str(rest);
+
+    let decoded = plain
+        .replace("&nbsp;", " ")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&amp;", "&");
+    normalize_text(&decoded)
+}
+
+fn to_html_item(html: String, text: String) -> ClipboardItem {
+    let now = now_ms();
+    let content_hash = hash_bytes(html.as_bytes());
+
+    ClipboardItem {
+        id: format!("html-{now}-{suffix}", suffix = &content_hash[0..8]),
+        item_type: "html".to_string(),
+        text: Some(text),
+        html: Some(html),
+        files: None,
+        image_path: None,
+        image_preview_data_url: None,
+        content_hash,
+        is_favorite: false,
+        created_at: now,
+        updated_at: now,
+        is_sensitive: false,
+        expires_at: None,
+        source_app: None,
+        selection: None,
+    }
+}
+
+fn file_entry_from_path(path: &Path) -> FileEntry {
+    let meta = fs::metadata(path).ok();
+    FileEntry {
+        path: path.to_string_lossy().to_string(),
+        is_dir: meta.as_ref().is_some_and(|m| m.is_dir()),
+        size: meta.as_ref().filter(|m| m.is_file()).map(|m| m.len()),
+        modified_at: meta
+            .as_ref()
+            .and_then(|m| m.modified().ok())
+            .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+            .map(|d| d.as_millis() as u64),
+        exists: meta.is_some(),
+    }
+}
+
+fn file_list_text(paths: &[PathBuf]) -> String {
+    paths
+        .iter()
+        .map(|path| path.to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn to_files_item(paths: &[PathBuf]) -> ClipboardItem {
+    let text = file_list_text(paths);
+    let now = now_ms();
+    let content_hash = hash_bytes(text.as_bytes());
+
+    ClipboardItem {
+        id: format!("files-{now}-{suffix}", suffix = &content_hash[0..8]),
+        item_type: "files".to_string(),
+        text: Some(text),
+        html: None,
+        files: Some(
+            paths
+                .iter()
+                .map(|path| file_entry_from_path(path))
+                .collect(),
+        ),
         image_path: None,
         image_preview_data_url: None,
         content_hash,
         is_favorite: false,
         created_at: now,
         updated_at: now,
+        is_sensitive: false,
+        expires_at: None,
+        source_app: None,
+        selection: None,
+    }
+}
+
+fn refresh_file_entries(item: &mut ClipboardItem) {
+    for entry in item.files.iter_mut().flatten() {
+        entry.exists = Path::new(&entry.path).exists();
     }
 }
 
@@ -919,6 +1329,21 @@ fn fingerprint_from_current_clipboard() -> Option<String> {
         }
     }
 
+    if let Ok(paths) = clipboard.get().file_list() {
+        if !paths.is_empty() && paths.iter().all(|p| ImageFormat::from_path(p).is_err()) {
+            let content_hash = hash_bytes(file_list_text(&paths).as_bytes());
+            return Some(format!("files:{content_hash}"));
+        }
+    }
+
+    if let Ok(html) = clipboard.get().html() {
+        let normalized = normalize_text(&html);
+        if !normalized.is_empty() {
+            let content_hash = hash_bytes(normalized.as_bytes());
+            return Some(format!("html:{content_hash}"));
+        }
+    }
+
     if let Ok(text) = clipboard.get_text() {
         let normalized = normalize_text(&text);
         if !normalized.is_empty() && !is_internal_log_text(&normalized) {
@@ -930,31 +1355,31 @@ fn fingerprint_from_current_clipboard() -> Option<String> {
     None
 }
 
-fn dedupe_and_upsert(
-    items: &mut Vec<ClipboardItem>,
-    incoming: ClipboardItem,
-    history_limit: usize,
-) {
-    if let Some(idx) = items.iter().position(|it| {
-        it.item_type == incoming.item_type && it.content_hash == incoming.content_hash
-    }) {
-        let mut merged = items.remove(idx);
-        merged.updated_at = now_ms();
-        merged.image_preview_data_url = incoming
-            .image_preview_data_url
-            .or(merged.image_preview_data_url);
-        items.insert(0, merged);
-    } else {
-        items.insert(0, incoming);
-    }
-
-    if items.len() > history_limit {
-        items.truncate(history_limit);
+/// 快照中有文本或 PNG 时按条目的哈希核对是否为同一份内容；没有可核对的格式时返回 `None`。
+fn snapshot_matches(item: &ClipboardItem, formats: &[snapshot::Format]) -> Option<bool> {
+    match item.item_type.as_str() {
+        "text" => snapshot::plain_text(formats)
+            .map(|text| hash_bytes(normalize_text(&text).as_bytes()) == item.content_hash),
+        "html" => snapshot::plain_text(formats)
+            .map(|text| item.text.as_deref() == Some(normalize_text(&text).as_str())),
+        "image" => snapshot::png(formats).map(|png| {
+            image::load_from_memory_with_format(png, ImageFormat::Png)
+                .ok()
+                .map(|img| img.to_rgba8())
+                .and_then(|rgba| {
+                    encode_rgba_raw_to_png_bytes(rgba.width(), rgba.height(), rgba.into_raw()).ok()
+                })
+                .is_some_and(|png_bytes| hash_bytes(&png_bytes) == item.content_hash)
+        }),
+        _ => None,
     }
 }
 
-fn load_image_for_clipboard(path: &Path) -> Result<ImageData<'static>, String> {
-    let bytes = fs::read(path).map_err(|e| format!("读取图片失败: {e}"))?;
+fn load_image_for_clipboard(
+    key: Option<&crypto::Key>,
+    path: &Path,
+) -> Result<ImageData<'static>, String> {
+    let bytes = crypto::read_file(key, path).map_err(|e| format!("读取图片失败: {e}"))?;
     let img = image::load_from_memory(&bytes).map_err(|e| format!("解析图片失败: {e}"))?;
     let rgba = img.to_rgba8();
     let width = rgba.width() as usize;
@@ -994,6 +1419,12 @@ fn open_storage_dir(app: AppHandle) -> Result<(), String> {
 fn update_settings(payload: UpdateSettingsPayload, app: AppHandle) -> Result<AppSettings, String> {
     let current = load_settings(&app)?;
     let old_dir = data_dir_from_settings(&app, &current)?;
+    let old_history_limit = current.history_limit;
+    let old_retention = (
+        current.retention_days,
+        current.image_retention_days,
+        current.max_image_disk_mb,
+    );
 
     let mut next = current;
     if let Some(v) = payload.poll_interval_ms {
@@ -1002,6 +1433,9 @@ fn update_settings(payload: UpdateSettingsPayload, app: AppHandle) -> Result<App
     if let Some(v) = payload.history_limit {
         next.history_limit = v;
     }
+    if let Some(v) = payload.favorite_limit {
+        next.favorite_limit = v;
+    }
     if let Some(v) = payload.storage_dir {
         next.storage_dir = v;
     }
@@ -1014,14 +1448,74 @@ fn update_settings(payload: UpdateSettingsPayload, app: AppHandle) -> Result<App
     if let Some(v) = payload.always_on_top {
         next.always_on_top = v;
     }
+    if let Some(v) = payload.sensitive_action {
+        next.sensitive_action = v;
+    }
+    if let Some(v) = payload.sensitive_expiry_minutes {
+        next.sensitive_expiry_minutes = v;
+    }
+    if let Some(v) = payload.sensitive_detectors {
+        next.sensitive_detectors = v;
+    }
+    if let Some(v) = payload.ignored_apps {
+        next.ignored_apps = v;
+    }
+    if let Some(v) = payload.retention_days {
+        next.retention_days = v;
+    }
+    if let Some(v) = payload.image_retention_days {
+        next.image_retention_days = v;
+    }
+    if let Some(v) = payload.max_image_disk_mb {
+        next.max_image_disk_mb = v;
+    }
+    if let Some(v) = payload.expand_templates {
+        next.expand_templates = v;
+    }
+    if let Some(v) = payload.paste_queue_shortcut {
+        next.paste_queue_shortcut = v;
+    }
+    if let Some(v) = payload.paste_on_select {
+        next.paste_on_select = v;
+    }
+    if let Some(v) = payload.shift_paste_apps {
+        next.shift_paste_apps = v;
+    }
+    if let Some(v) = payload.keymap {
+        next.keymap = v;
+    }
+    if let Some(v) = payload.full_snapshot {
+        next.full_snapshot = v;
+    }
+    if let Some(v) = payload.snapshot_format_limit_kb {
+        next.snapshot_format_limit_kb = v;
+    }
+    if let Some(v) = payload.capture_primary {
+        next.capture_primary = v;
+    }
+    if let Some(v) = payload.selection_sync {
+        next.selection_sync = v;
+    }
+    if let Some(v) = payload.keep_clipboard {
+        next.keep_clipboard = v;
+    }
     next = normalize_settings(next);
+    shortcuts::validate(&next)?;
 
     save_settings(&app, &next)?;
 
     let new_dir = data_dir_from_settings(&app, &next)?;
     migrate_storage_if_needed(&old_dir, &new_dir)?;
+    if next.history_limit < old_history_limit {
+        // 调低上限后立即截断，界面不必等到下一次采集才按新上限显示。
+        let state = app.state::<AppState>();
+        let _guard = lock_history(&app, &state)?;
+        let mut conn = open_history_db(&app)?;
+        let evicted = storage::enforce_history_limit(&mut conn, next.history_limit)?;
+        release_evicted(&app, &conn, evicted)?;
+    }
 
-    register_global_shortcut(&app, &next.global_shortcut)?;
+    shortcuts::register(&app, &next)?;
     if let Err(err) = set_autostart_enabled(&app, next.launch_at_startup) {
         append_log(
             &app,
@@ -1036,6 +1530,15 @@ fn update_settings(payload: UpdateSettingsPayload, app: AppHandle) -> Result<App
             &format!("apply always-on-top setting failed: {err}"),
         );
     }
+    if old_retention
+        != (
+            next.retention_days,
+            next.image_retention_days,
+            next.max_image_disk_mb,
+        )
+    {
+        retention::sweep_and_notify(&app);
+    }
 
     Ok(next)
 }
@@ -1043,27 +1546,121 @@ fn update_settings(payload: UpdateSettingsPayload, app: AppHandle) -> Result<App
 #[tauri::command]
 fn get_history(app: AppHandle) -> Result<Vec<ClipboardItem>, String> {
     ensure_storage_layout(&app)?;
-    load_history_clean(&app)
+    load_history(&app)
 }
 
 #[tauri::command]
-fn get_image_preview(id: String, app: AppHandle) -> Result<Option<String>, String> {
+fn search_history(
+    payload: search::SearchPayload,
+    app: AppHandle,
+) -> Result<Vec<search::SearchResult>, String> {
     ensure_storage_layout(&app)?;
-    let item = load_history_clean(&app)?
-        .into_iter()
-        .find(|it| it.id == id)
-        .ok_or_else(|| "未找到历史项".to_string())?;
-    build_image_preview_data_url(&app, &item)
+    let conn = open_history_db(&app)?;
+    storage::purge_expired(&conn, now_ms())?;
+    let item_type = payload
+        .item_type
+        .as_deref()
+        .map(str::trim)
+        .filter(|v| !v.is_empty() && *v != "all");
+    let candidates = storage::load_filtered_items(
+        &conn,
+        storage_key(&app)?.as_ref(),
+        item_type,
+        payload.favorite_only.unwrap_or(false),
+        payload.from,
+        payload.to,
+    )?;
+    Ok(search::rank(candidates, &payload.query, payload.limit))
 }
 
 #[tauri::command]
-fn poll_clipboard(app: AppHandle, state: State<AppState>) -> Result<Option<ClipboardItem>, String> {
+fn get_image_preview(id: String, app: AppHandle) -> Result<Option<String>, String> {
     ensure_storage_layout(&app)?;
-    let poll_started_at = Instant::now();
-    let _guard = state
-        .history_lock
+    let item = find_history_item(&app, &id)?;
+    build_image_preview_data_url(&app, &item)
+}
+
+/// 对文本类条目运行启用的敏感检测器并按设置处理：`skip` 不记录，`mask` 打码后以纯文本记录，
+/// `mark` 原样记录但标记为敏感并设置过期时间。返回处理后的条目和命中的检测器名。
+fn apply_sensitive_policy(
+    item: ClipboardItem,
+    settings: &AppSettings,
+) -> (Option<ClipboardItem>, Vec<&'static str>) {
+    if settings.sensitive_action == sensitive::ACTION_OFF
+        || (item.item_type != "text" && item.item_type != "html")
+    {
+        return (Some(item), Vec::new());
+    }
+    let Some(text) = item.text.as_deref() else {
+        return (Some(item), Vec::new());
+    };
+
+    let findings = sensitive::scan(text, &settings.sensitive_detectors);
+    if findings.is_empty() {
+        return (Some(item), Vec::new());
+    }
+    let mut detectors: Vec<&'static str> = findings.iter().map(|f| f.detector).collect();
+    detectors.sort_unstable();
+    detectors.dedup();
+
+    let mut item = match settings.sensitive_action.as_str() {
+        sensitive::ACTION_SKIP => return (None, detectors),
+        sensitive::ACTION_MASK => to_text_item(sensitive::mask(text, &findings)),
+        _ => item,
+    };
+    item.is_sensitive = true;
+    item.expires_at = Some(item.updated_at + settings.sensitive_expiry_minutes * 60_000);
+    (Some(item), detectors)
+}
+
+fn capture_clipboard(app: &AppHandle, state: &AppState) -> Result<Option<ClipboardItem>, String> {
+    ensure_storage_layout(app)?;
+    let key = match storage_key(app) {
+        Ok(key) => key,
+        Err(err) => {
+            append_diagnostic_log_throttled(app, state, &format!("poll skipped: {err}"));
+            return Ok(None);
+        }
+    };
+    if *state
+        .capture_paused
         .lock()
-        .map_err(|_| "历史锁获取失败".to_string())?;
+        .map_err(|_| "采集状态锁获取失败".to_string())?
+    {
+        return Ok(None);
+    }
+    let poll_started_at = Instant::now();
+    let _guard = lock_history(app, state)?;
+
+    let settings = load_settings(app)?;
+    let source = source::detect();
+    let ignored = source::is_ignored(source.app.as_deref(), &settings.ignored_apps);
+    if source.secret_hint || ignored {
+        // 剪贴板里已不是上一次采集的条目，所有者退出后不能拿旧条目顶替。
+        *state
+            .last_capture_fingerprint
+            .lock()
+            .map_err(|_| "指纹锁获取失败".to_string())? = None;
+    }
+    if source.secret_hint {
+        append_diagnostic_log_throttled(
+            app,
+            state,
+            "poll skipped: clipboard marked as secret by source app",
+        );
+        return Ok(None);
+    }
+    if ignored {
+        append_diagnostic_log_throttled(
+            app,
+            state,
+            &format!(
+                "poll skipped: source app {} is ignored",
+                source.app.as_deref().unwrap_or_default()
+            ),
+        );
+        return Ok(None);
+    }
 
     let mut clipboard = Clipboard::new().map_err(|e| format!("访问系统剪贴板失败: {e}"))?;
 
@@ -1084,7 +1681,7 @@ fn poll_clipboard(app: AppHandle, state: State<AppState>) -> Result<Option<Clipb
         Ok(img) => {
             plugin_image_available = true;
             match image_item_from_rgba_bytes(
-                &app,
+                app,
                 img.width() as u32,
                 img.height() as u32,
                 img.rgba().to_vec(),
@@ -1111,7 +1708,7 @@ fn poll_clipboard(app: AppHandle, state: State<AppState>) -> Result<Option<Clipb
             match clipboard.get_image() {
                 Ok(image) => {
                     arboard_image_available = true;
-                    image_captured = Some(to_image_item(&app, &image)?);
+                    image_captured = Some(to_image_item(app, &image)?);
                     capture_source = "arboard-image";
                     break;
                 }
@@ -1127,7 +1724,7 @@ fn poll_clipboard(app: AppHandle, state: State<AppState>) -> Result<Option<Clipb
 
     if image_captured.is_none() {
         if let Some((w, h, rgba)) = read_clipboard_image_win32() {
-            match image_item_from_rgba_bytes(&app, w, h, rgba) {
+            match image_item_from_rgba_bytes(app, w, h, rgba) {
                 Ok(item) => {
                     capture_source = "win32-dib";
                     image_captured = Some(item);
@@ -1144,40 +1741,71 @@ fn poll_clipboard(app: AppHandle, state: State<AppState>) -> Result<Option<Clipb
 
         if let Ok(paths) = clipboard.get().file_list() {
             file_list_count = paths.len();
-            for path in paths {
-                if let Some(item) = image_item_from_path(&app, &path) {
+            for path in &paths {
+                if let Some(item) = image_item_from_path(app, path) {
                     capture_source = "file-list-image";
                     capture_debug = path.display().to_string();
                     from_other_formats = Some(item);
                     break;
                 }
             }
+            if from_other_formats.is_none() && !paths.is_empty() {
+                capture_source = "file-list";
+                capture_debug = format!("{} paths", paths.len());
+                from_other_formats = Some(to_files_item(&paths));
+            }
         }
 
+        let mut html_markup: Option<String> = None;
         if from_other_formats.is_none() {
             if let Ok(html) = clipboard.get().html() {
                 let normalized_html = normalize_text(&html);
                 html_len = normalized_html.len();
                 if !normalized_html.is_empty() {
-                    if let Some(item) = try_image_item_from_text_source(&app, &normalized_html) {
+                    if let Some(item) = try_image_item_from_text_source(app, &normalized_html) {
                         capture_source = "html-image";
                         from_other_formats = Some(item);
+                    } else {
+                        html_markup = Some(normalized_html);
                     }
                 }
             }
         }
 
+        let html_with_text = html_markup.and_then(|html| {
+            let plain = clipboard
+                .get_text()
+                .map(|text| normalize_text(&text))
+                .unwrap_or_default();
+            let plain = if plain.is_empty() {
+                html_to_plain_text(&html)
+            } else {
+                plain
+            };
+            (!plain.is_empty()).then_some((html, plain))
+        });
+
         if let Some(item) = from_other_formats {
             Some(item)
+        } else if let Some((html, plain)) = html_with_text {
+            text_len = plain.len();
+            if is_internal_log_text(&plain) {
+                append_log(app, "INFO", "ignored internal log text in clipboard");
+                None
+            } else {
+                capture_source = "html";
+                capture_debug = text_preview_for_log(&plain, 120);
+                Some(to_html_item(html, plain))
+            }
         } else if let Ok(text) = clipboard.get_text() {
             let normalized = normalize_text(&text);
             text_len = normalized.len();
             if normalized.is_empty() {
                 None
             } else if is_internal_log_text(&normalized) {
-                append_log(&app, "INFO", "ignored internal log text in clipboard");
+                append_log(app, "INFO", "ignored internal log text in clipboard");
                 None
-            } else if let Some(image_item) = try_image_item_from_text_source(&app, &normalized) {
+            } else if let Some(image_item) = try_image_item_from_text_source(app, &normalized) {
                 capture_source = "text-parsed-image";
                 Some(image_item)
             } else {
@@ -1192,16 +1820,16 @@ fn poll_clipboard(app: AppHandle, state: State<AppState>) -> Result<Option<Clipb
 
     let Some(item) = incoming else {
         append_diagnostic_log_throttled(
-            &app,
-            &state,
+            app,
+            state,
             &format!(
                 "poll no-capture plugin_image={plugin_image_available} arboard_image={arboard_image_available} file_list_count={file_list_count} html_len={html_len} text_len={text_len}"
             ),
         );
         if !plugin_error.is_empty() || !arboard_last_error.is_empty() {
             append_diagnostic_log_throttled(
-                &app,
-                &state,
+                app,
+                state,
                 &format!(
                     "poll no-capture details plugin_waited={plugin_to_arboard_waited} arboard_attempts={arboard_attempts} poll_ms={} plugin_error={} arboard_error={}",
                     poll_started_at.elapsed().as_millis(),
@@ -1225,126 +1853,504 @@ fn poll_clipboard(app: AppHandle, state: State<AppState>) -> Result<Option<Clipb
         *last = Some(fp);
     }
 
-    let settings = load_settings(&app)?;
-    let mut items = load_history(&app)?;
-    dedupe_and_upsert(&mut items, item, settings.history_limit);
-    save_history(&app, &items)?;
-    let item_type = &items[0].item_type;
-    if capture_debug.is_empty() {
+    store_captured_item(
+        app,
+        state,
+        &settings,
+        key.as_ref(),
+        Captured {
+            item,
+            source_app: source.app,
+            selection: None,
+            origin: capture_source,
+            detail: capture_debug,
+        },
+    )
+}
+
+/// 一次采集读到的条目及其来源，交给 [`store_captured_item`] 入库。
+struct Captured {
+    item: ClipboardItem,
+    source_app: Option<String>,
+    /// 采集自 PRIMARY 等选区时的选区名；剪贴板条目为 `None`，并会尝试保存完整快照。
+    selection: Option<&'static str>,
+    /// 日志中的采集途径，如 `plugin-image`、`primary`。
+    origin: &'static str,
+    /// 日志中的附加信息，加密存储时不记录。
+    detail: String,
+}
+
+/// 剪贴板与选区采集共用的入库流程：敏感内容策略、完整快照、写入与截断、图片预览、粘贴队列与日志。
+/// 调用方需已完成暂停、来源与去重检查，并持有历史锁。
+fn store_captured_item(
+    app: &AppHandle,
+    state: &AppState,
+    settings: &AppSettings,
+    key: Option<&crypto::Key>,
+    captured: Captured,
+) -> Result<Option<ClipboardItem>, String> {
+    let Captured {
+        item,
+        source_app,
+        selection,
+        origin: capture_source,
+        detail: capture_debug,
+    } = captured;
+    let (item, detectors) = apply_sensitive_policy(item, settings);
+    let Some(mut item) = item else {
         append_log(
-            &app,
+            app,
+            "INFO",
+            &format!(
+                "skipped sensitive clipboard content, source={capture_source}, detectors={}",
+                detectors.join(",")
+            ),
+        );
+        return Ok(None);
+    };
+
+    item.source_app = source_app;
+    item.selection = selection.map(str::to_string);
+
+    // 敏感内容的原始格式里同样含有被打码的部分，不保存快照；快照只能读取剪贴板，选区条目没有。
+    let formats = if selection.is_none() && settings.full_snapshot && detectors.is_empty() {
+        match snapshot::read(settings.snapshot_format_limit_kb as usize * 1024) {
+            // 快照与条目分两次读取，中间剪贴板可能已被改写；无法按内容核对时重新比较指纹。
+            Ok(formats)
+                if snapshot_matches(&item, &formats).unwrap_or_else(|| {
+                    fingerprint_from_current_clipboard() == Some(fingerprint(&item))
+                }) =>
+            {
+                formats
+            }
+            Ok(_) => {
+                append_diagnostic_log_throttled(
+                    app,
+                    state,
+                    "snapshot dropped: clipboard changed while reading",
+                );
+                Vec::new()
+            }
+            Err(err) => {
+                append_diagnostic_log_throttled(
+                    app,
+                    state,
+                    &format!("snapshot read failed: {err}"),
+                );
+                Vec::new()
+            }
+        }
+    } else {
+        Vec::new()
+    };
+
+    let mut conn = open_history_db(app)?;
+    storage::purge_expired(&conn, now_ms())?;
+    let (mut latest, evicted) = storage::upsert_item(&mut conn, key, item, settings.history_limit)?;
+    release_evicted(app, &conn, evicted)?;
+    if !formats.is_empty() {
+        storage::save_snapshot(&conn, key, &latest.id, &formats)?;
+    }
+    let item_type = &latest.item_type;
+    if !detectors.is_empty() {
+        append_log(
+            app,
+            "INFO",
+            &format!(
+                "history updated with sensitive {item_type} item, source={capture_source}, action={}, detectors={}",
+                settings.sensitive_action,
+                detectors.join(",")
+            ),
+        );
+    } else if capture_debug.is_empty() || key.is_some() {
+        append_log(
+            app,
             "INFO",
             &format!("history updated with {item_type} item, source={capture_source}"),
         );
     } else {
         append_log(
-            &app,
+            app,
             "INFO",
             &format!("history updated with {item_type} item, source={capture_source}, detail={capture_debug}"),
         );
     }
-    let mut latest = items.first().cloned();
-    if let Some(item) = &mut latest {
-        item.image_preview_data_url = build_image_preview_data_url(&app, item).ok().flatten();
+    latest.image_preview_data_url = build_image_preview_data_url(app, &latest).ok().flatten();
+    paste_queue::collect(app, state, &latest.id);
+
+    Ok(Some(latest))
+}
+
+/// 暂停或恢复采集；恢复时把当前剪贴板记为已采集，暂停期间复制的内容不会在恢复后补录。
+fn set_capture_paused(app: &AppHandle, paused: bool) -> Result<(), String> {
+    let state = app.state::<AppState>();
+    if !paused {
+        let current = fingerprint_from_current_clipboard();
+        *state
+            .last_capture_fingerprint
+            .lock()
+            .map_err(|_| "指纹锁获取失败".to_string())? = current;
     }
+    *state
+        .capture_paused
+        .lock()
+        .map_err(|_| "采集状态锁获取失败".to_string())? = paused;
+    append_log(
+        app,
+        "INFO",
+        if paused {
+            "clipboard capture paused"
+        } else {
+            "clipboard capture resumed"
+        },
+    );
+    let _ = app.emit(CAPTURE_PAUSED_EVENT, paused);
+    Ok(())
+}
 
-    Ok(latest)
+#[tauri::command]
+fn get_watcher_backend(state: State<AppState>) -> Result<watcher::BackendStatus, String> {
+    state
+        .watcher_backend
+        .lock()
+        .map(|status| status.clone())
+        .map_err(|_| "监听状态锁获取失败".to_string())
 }
 
 #[tauri::command]
-fn copy_history_item(id: String, app: AppHandle, state: State<AppState>) -> Result<(), String> {
-    let item = load_history_clean(&app)?
-        .into_iter()
-        .find(|it| it.id == id)
-        .ok_or_else(|| "未找到历史项".to_string())?;
+fn get_capture_paused(state: State<AppState>) -> Result<bool, String> {
+    state
+        .capture_paused
+        .lock()
+        .map(|paused| *paused)
+        .map_err(|_| "采集状态锁获取失败".to_string())
+}
+
+#[tauri::command]
+fn update_capture_paused(paused: bool, app: AppHandle) -> Result<(), String> {
+    set_capture_paused(&app, paused)
+}
+
+#[tauri::command]
+fn poll_clipboard(app: AppHandle, state: State<AppState>) -> Result<Option<ClipboardItem>, String> {
+    capture_clipboard(&app, &state)
+}
 
+#[tauri::command]
+fn copy_history_item(
+    id: String,
+    fields: Option<HashMap<String, String>>,
+    app: AppHandle,
+    state: State<AppState>,
+) -> Result<Option<usize>, String> {
+    let item = find_history_item(&app, &id)?;
+    let expand = load_settings(&app)?.expand_templates;
+    let cursor = copy_item_to_clipboard(&app, &state, &item, expand, &fields.unwrap_or_default())?;
+    paste_after_select(&app, &state, cursor)?;
+    Ok(cursor)
+}
+
+/// “选中即粘贴”：弹窗中选中条目并写入剪贴板后，隐藏弹窗、把焦点还给唤起前的窗口并模拟粘贴。
+/// 弹窗不可见时（例如通过本地 IPC 调用）不做任何事。
+fn paste_after_select(
+    app: &AppHandle,
+    state: &AppState,
+    cursor_from_end: Option<usize>,
+) -> Result<(), String> {
+    let settings = load_settings(app)?;
+    let Some(window) = app.get_webview_window("main") else {
+        return Ok(());
+    };
+    if !settings.paste_on_select || !window.is_visible().unwrap_or(false) {
+        return Ok(());
+    }
+    let target = state
+        .paste_target
+        .lock()
+        .map_err(|_| "粘贴目标锁获取失败".to_string())?
+        .take();
+    let _ = window.hide();
+
+    let shift = autopaste::needs_shift(target.as_ref(), &settings.shift_paste_apps);
+    let handle = app.clone();
+    thread::Builder::new()
+        .name("auto-paste".to_string())
+        .spawn(move || {
+            // 等弹窗隐藏、焦点离开本程序后再切换窗口。
+            thread::sleep(Duration::from_millis(120));
+            if let Err(err) = autopaste::paste(target.as_ref(), shift, cursor_from_end) {
+                append_log(&handle, "WARN", &format!("auto paste failed: {err}"));
+            }
+        })
+        .map(|_| ())
+        .map_err(|e| format!("启动自动粘贴失败: {e}"))
+}
+
+/// 回填历史条目或片段，并记下指纹，避免后台监听把它当作新内容再次采集。
+/// `expand` 为真时先展开模板占位符，返回 `{{cursor}}` 距末尾的字符数。
+fn copy_item_to_clipboard(
+    app: &AppHandle,
+    state: &AppState,
+    item: &ClipboardItem,
+    expand: bool,
+    values: &HashMap<String, String>,
+) -> Result<Option<usize>, String> {
     let mut clipboard = Clipboard::new().map_err(|e| format!("访问系统剪贴板失败: {e}"))?;
+    let original_hash = item.content_hash.clone();
+    let (item, cursor) = if expand {
+        expand_item_templates(&mut clipboard, item, values)?
+    } else {
+        (item.clone(), None)
+    };
+    let key = storage_key(app)?;
+    // 模板展开改变了内容时快照已不对应，只写入展开后的内容；旧版本可能存下了与条目不符的快照，同样跳过。
+    let formats = if item.content_hash == original_hash {
+        storage::load_snapshot(&open_history_db(app)?, key.as_ref(), &item.id)?
+            .filter(|formats| snapshot_matches(&item, formats) != Some(false))
+    } else {
+        None
+    };
+    let restored = match formats {
+        Some(formats) => match snapshot::write(formats) {
+            Ok(()) => true,
+            Err(err) => {
+                append_log(app, "WARN", &format!("snapshot restore failed: {err}"));
+                false
+            }
+        },
+        None => false,
+    };
+    if !restored {
+        write_item_to_clipboard(clipboard.set(), &item, &data_dir(app)?, key.as_ref())?;
+    }
+
+    let mut last = state
+        .last_capture_fingerprint
+        .lock()
+        .map_err(|_| "指纹锁获取失败".to_string())?;
+    *last = Some(fingerprint(&item));
+
+    Ok(cursor)
+}
+
+/// 展开文本类条目中的模板占位符，富文本的 HTML 部分对替换值做转义；没有可识别的占位符时原样返回。
+fn expand_item_templates(
+    clipboard: &mut Clipboard,
+    item: &ClipboardItem,
+    values: &HashMap<String, String>,
+) -> Result<(ClipboardItem, Option<usize>), String> {
+    let text = item.text.as_deref().unwrap_or_default();
+    let html = item.html.as_deref().filter(|_| item.item_type == "html");
+    let applicable = matches!(item.item_type.as_str(), "text" | "html")
+        && (template::has_placeholders(text) || html.is_some_and(template::has_placeholders));
+    if !applicable {
+        return Ok((item.clone(), None));
+    }
+
+    let current = clipboard.get_text().ok();
+    let ctx = template::Context::new(current.as_deref(), values);
+    let expanded_text = template::expand(text, &ctx)?;
+    let mut expanded = item.clone();
+    if let Some(html) = html {
+        let html = template::expand_with(html, &ctx, template::escape_html)?.text;
+        expanded.content_hash = hash_bytes(html.as_bytes());
+        expanded.html = Some(html);
+    } else {
+        expanded.content_hash = hash_bytes(normalize_text(&expanded_text.text).as_bytes());
+    }
+    expanded.text = Some(expanded_text.text);
+    Ok((expanded, expanded_text.cursor_from_end))
+}
 
+/// 按条目类型写入剪贴板；图形界面与命令行共用。
+fn write_item_to_clipboard(
+    set: arboard::Set<'_>,
+    item: &ClipboardItem,
+    base: &Path,
+    key: Option<&crypto::Key>,
+) -> Result<(), String> {
     if item.item_type == "text" {
         let text = item.text.as_deref().unwrap_or_default().to_string();
-        clipboard
-            .set_text(text)
+        set.text(text)
             .map_err(|e| format!("写入文本到剪贴板失败: {e}"))?;
+    } else if item.item_type == "html" {
+        let html = item.html.clone().unwrap_or_default();
+        set.html(html, item.text.clone())
+            .map_err(|e| format!("写入富文本到剪贴板失败: {e}"))?;
+    } else if item.item_type == "files" {
+        let paths: Vec<PathBuf> = item
+            .files
+            .iter()
+            .flatten()
+            .filter(|entry| entry.exists)
+            .map(|entry| PathBuf::from(&entry.path))
+            .collect();
+        if paths.is_empty() {
+            return Err("文件已不存在".to_string());
+        }
+        set.file_list(&paths)
+            .map_err(|e| format!("写入文件列表到剪贴板失败: {e}"))?;
     } else {
         let rel = item
             .image_path
             .as_deref()
             .ok_or_else(|| "图片路径缺失".to_string())?;
-        let path = data_dir(&app)?.join(rel);
-        let image = load_image_for_clipboard(&path)?;
-        clipboard
-            .set_image(image)
+        let image = load_image_for_clipboard(key, &base.join(rel))?;
+        set.image(image)
             .map_err(|e| format!("写入图片到剪贴板失败: {e}"))?;
     }
-
-    let mut last = state
-        .last_capture_fingerprint
-        .lock()
-        .map_err(|_| "指纹锁获取失败".to_string())?;
-    *last = Some(fingerprint(&item));
-
     Ok(())
 }
 
 #[tauri::command]
-fn copy_text(text: String) -> Result<(), String> {
+fn copy_text(
+    text: String,
+    fields: Option<HashMap<String, String>>,
+    app: AppHandle,
+    state: State<AppState>,
+) -> Result<Option<usize>, String> {
     let mut clipboard = Clipboard::new().map_err(|e| format!("访问系统剪贴板失败: {e}"))?;
+    // 划选的片段可能带有首尾空白或 CRLF，直接展开原文，不经过历史条目的规范化。
+    let (text, cursor) =
+        if load_settings(&app)?.expand_templates && template::has_placeholders(&text) {
+            let current = clipboard.get_text().ok();
+            let values = fields.unwrap_or_default();
+            let expanded =
+                template::expand(&text, &template::Context::new(current.as_deref(), &values))?;
+            (expanded.text, expanded.cursor_from_end)
+        } else {
+            (text, None)
+        };
     clipboard
         .set_text(text)
-        .map_err(|e| format!("写入文本到剪贴板失败: {e}"))
+        .map_err(|e| format!("写入文本到剪贴板失败: {e}"))?;
+    paste_after_select(&app, &state, cursor)?;
+    Ok(cursor)
 }
 
+/// 对文本类历史条目应用内置变换并写入剪贴板；`save` 为真时结果另存为新的历史记录（同样经过敏感内容检测）。
 #[tauri::command]
-fn toggle_favorite(id: String, app: AppHandle) -> Result<Option<ClipboardItem>, String> {
-    let mut items = load_history_clean(&app)?;
-    let mut updated: Option<ClipboardItem> = None;
-
-    for item in &mut items {
-        if item.id == id {
-            item.is_favorite = !item.is_favorite;
-            item.updated_at = now_ms();
-            updated = Some(item.clone());
-            break;
-        }
+fn transform_and_copy(
+    payload: transform::TransformPayload,
+    app: AppHandle,
+    state: State<AppState>,
+) -> Result<transform::TransformResult, String> {
+    let item = find_history_item(&app, &payload.id)?;
+    if item.item_type != "text" && item.item_type != "html" {
+        return Err("只能变换文本或富文本条目".to_string());
+    }
+    let text = transform::apply(&payload.transform, item.text.as_deref().unwrap_or_default())?;
+    if text.trim().is_empty() {
+        return Err("变换结果为空".to_string());
+    }
+
+    let mut clipboard = Clipboard::new().map_err(|e| format!("访问系统剪贴板失败: {e}"))?;
+    clipboard
+        .set_text(text.clone())
+        .map_err(|e| format!("写入文本到剪贴板失败: {e}"))?;
+    let transformed = to_text_item(text.clone());
+    {
+        let mut last = state
+            .last_capture_fingerprint
+            .lock()
+            .map_err(|_| "指纹锁获取失败".to_string())?;
+        *last = Some(fingerprint(&transformed));
     }
 
-    if let Some(ref changed) = updated {
-        items.sort_by(|a, b| b.updated_at.cmp(&a.updated_at));
-        if let Some(idx) = items.iter().position(|it| it.id == changed.id) {
-            let item = items.remove(idx);
-            items.insert(0, item);
+    let saved = if payload.save {
+        let _guard = lock_history(&app, &state)?;
+        let settings = load_settings(&app)?;
+        match apply_sensitive_policy(transformed, &settings).0 {
+            Some(item) => {
+                let mut conn = open_history_db(&app)?;
+                let (stored, evicted) = storage::upsert_item(
+                    &mut conn,
+                    storage_key(&app)?.as_ref(),
+                    item,
+                    settings.history_limit,
+                )?;
+                release_evicted(&app, &conn, evicted)?;
+                Some(stored)
+            }
+            None => None,
+        }
+    } else {
+        None
+    };
+
+    Ok(transform::TransformResult { text, saved })
+}
+
+#[tauri::command]
+fn get_paste_queue(state: State<AppState>) -> Result<paste_queue::PasteQueue, String> {
+    paste_queue::status(&state)
+}
+
+#[tauri::command]
+fn start_paste_queue(
+    payload: paste_queue::StartPayload,
+    app: AppHandle,
+) -> Result<paste_queue::PasteQueue, String> {
+    paste_queue::start(&app, payload)
+}
+
+#[tauri::command]
+fn stop_paste_queue(app: AppHandle) -> Result<paste_queue::PasteQueue, String> {
+    paste_queue::stop(&app)
+}
+
+/// 与队列快捷键相同：把队列中的下一条写入剪贴板。
+#[tauri::command]
+fn paste_queue_next(app: AppHandle) -> Result<Option<ClipboardItem>, String> {
+    paste_queue::advance(&app)
+}
+
+/// 文本中需要用户填写的模板字段，界面据此在回填前弹出输入框。
+#[tauri::command]
+fn get_template_fields(text: String, html: Option<String>) -> Vec<template::TemplateField> {
+    let mut fields = template::fields(&text);
+    for field in template::fields(html.as_deref().unwrap_or_default()) {
+        if !fields.iter().any(|f| f.name == field.name) {
+            fields.push(field);
         }
-        save_history(&app, &items)?;
     }
+    fields
+}
 
-    Ok(updated)
+#[tauri::command]
+fn toggle_favorite(
+    id: String,
+    app: AppHandle,
+    state: State<AppState>,
+) -> Result<Option<ClipboardItem>, String> {
+    let _guard = lock_history(&app, &state)?;
+
+    let settings = load_settings(&app)?;
+    let mut conn = open_history_db(&app)?;
+    let (item, evicted) = storage::toggle_favorite(
+        &mut conn,
+        storage_key(&app)?.as_ref(),
+        &id,
+        now_ms(),
+        settings.favorite_limit,
+        settings.history_limit,
+    )?;
+    release_evicted(&app, &conn, evicted)?;
+    Ok(item)
 }
 
 #[tauri::command]
 fn delete_history_item(id: String, app: AppHandle, state: State<AppState>) -> Result<(), String> {
-    let _guard = state
-        .history_lock
-        .lock()
-        .map_err(|_| "历史锁获取失败".to_string())?;
+    let _guard = lock_history(&app, &state)?;
 
-    let mut items = load_history_clean(&app)?;
-    let idx = items
-        .iter()
-        .position(|it| it.id == id)
+    let conn = open_history_db(&app)?;
+    let removed = storage::delete_item(&conn, storage_key(&app)?.as_ref(), &id)?
         .ok_or_else(|| "未找到历史项".to_string())?;
-    let removed = items.remove(idx);
 
-    if removed.item_type == "image" {
-        if let Some(rel) = removed.image_path.as_deref() {
-            let path = data_dir(&app)?.join(rel);
-            if path.exists() {
-                fs::remove_file(path).map_err(|e| format!("删除图片失败: {e}"))?;
-            }
-        }
+    if let Some(rel) = removed.image_path.as_deref() {
+        images::release(&conn, &data_dir(&app)?, rel)?;
     }
 
-    save_history(&app, &items)?;
-
     let mut last = state
         .last_capture_fingerprint
         .lock()
@@ -1358,24 +2364,12 @@ fn delete_history_item(id: String, app: AppHandle, state: State<AppState>) -> Re
 
 #[tauri::command]
 fn clear_history(app: AppHandle, state: State<AppState>) -> Result<(), String> {
-    let _guard = state
-        .history_lock
-        .lock()
-        .map_err(|_| "历史锁获取失败".to_string())?;
+    let _guard = lock_history(&app, &state)?;
 
-    save_history(&app, &[])?;
-
-    let img_dir = image_dir(&app)?;
-    if img_dir.exists() {
-        let entries = fs::read_dir(&img_dir).map_err(|e| format!("读取图片目录失败: {e}"))?;
-        for entry in entries {
-            let entry = entry.map_err(|e| format!("读取目录项失败: {e}"))?;
-            let path = entry.path();
-            if path.is_file() {
-                fs::remove_file(path).map_err(|e| format!("删除图片失败: {e}"))?;
-            }
-        }
-    }
+    let conn = open_history_db(&app)?;
+    storage::clear(&conn)?;
+    // 片段库不随历史清空，仍被片段引用的图片保留。
+    images::remove_orphans(&conn, &data_dir(&app)?)?;
 
     let current_fingerprint = fingerprint_from_current_clipboard();
 
@@ -1388,6 +2382,189 @@ fn clear_history(app: AppHandle, state: State<AppState>) -> Result<(), String> {
     Ok(())
 }
 
+#[tauri::command]
+fn verify_storage(repair: Option<bool>, app: AppHandle) -> Result<integrity::VerifyReport, String> {
+    let report = integrity::verify(&app, repair.unwrap_or(false))?;
+    if report.repaired {
+        append_log(
+            &app,
+            "INFO",
+            &format!("storage verify repaired {} issues", report.issue_count()),
+        );
+    }
+    Ok(report)
+}
+
+#[tauri::command]
+fn export_history(
+    payload: archive::ExportPayload,
+    app: AppHandle,
+) -> Result<archive::ExportReport, String> {
+    archive::export(&app, payload)
+}
+
+#[tauri::command]
+fn import_history(path: String, app: AppHandle) -> Result<archive::ImportReport, String> {
+    let report = archive::import(&app, Path::new(&path))?;
+    append_log(&app, "INFO", &format!("history import {report:?}"));
+    Ok(report)
+}
+
+#[tauri::command]
+fn list_import_sources() -> Vec<importers::ImportSource> {
+    importers::sources()
+}
+
+#[tauri::command]
+fn import_from_manager(
+    source: String,
+    path: Option<String>,
+    app: AppHandle,
+) -> Result<archive::ImportReport, String> {
+    let report = importers::import(&app, &source, path.as_deref().map(Path::new))?;
+    append_log(&app, "INFO", &format!("{source} import {report:?}"));
+    Ok(report)
+}
+
+#[tauri::command]
+fn get_snippets(app: AppHandle) -> Result<Vec<snippets::Snippet>, String> {
+    snippets::list(&app)
+}
+
+#[tauri::command]
+fn save_snippet(
+    payload: snippets::SnippetPayload,
+    app: AppHandle,
+) -> Result<snippets::Snippet, String> {
+    snippets::save(&app, payload)
+}
+
+#[tauri::command]
+fn promote_to_snippet(
+    id: String,
+    payload: Option<snippets::SnippetPayload>,
+    app: AppHandle,
+) -> Result<snippets::Snippet, String> {
+    snippets::promote(&app, &id, payload.unwrap_or_default())
+}
+
+#[tauri::command]
+fn delete_snippet(id: String, app: AppHandle) -> Result<(), String> {
+    snippets::delete(&app, &id)
+}
+
+#[tauri::command]
+fn copy_snippet(
+    id: String,
+    fields: Option<HashMap<String, String>>,
+    app: AppHandle,
+    state: State<AppState>,
+) -> Result<Option<usize>, String> {
+    let snippet = snippets::find(&app, &id)?;
+    let cursor = copy_item_to_clipboard(
+        &app,
+        &state,
+        &snippet.content,
+        true,
+        &fields.unwrap_or_default(),
+    )?;
+    paste_after_select(&app, &state, cursor)?;
+    Ok(cursor)
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct EncryptionStatus {
+    enabled: bool,
+    unlocked: bool,
+}
+
+fn encryption_status(app: &AppHandle) -> Result<EncryptionStatus, String> {
+    Ok(EncryptionStatus {
+        enabled: crypto::is_enabled(&data_dir(app)?),
+        unlocked: unlocked_key(app)?.is_some(),
+    })
+}
+
+fn set_encryption_key(state: &AppState, key: Option<crypto::Key>) -> Result<(), String> {
+    let mut current = state
+        .encryption_key
+        .lock()
+        .map_err(|_| "密钥锁获取失败".to_string())?;
+    *current = key;
+    Ok(())
+}
+
+fn finish_encryption_migration(app: &AppHandle, key: &crypto::Key) -> Result<(), String> {
+    let mut conn = open_history_db(app)?;
+    let rows = storage::encrypt_existing_rows(&mut conn, key)?;
+    let files = images::encrypt_existing(&mut conn, &data_dir(app)?, key)?;
+
+    // 导入旧版历史后留下的副本含有明文内容，覆盖删除。
+    let legacy = storage::legacy_imported_path(&data_dir(app)?);
+    if legacy.exists() {
+        crypto::shred_file(&legacy)?;
+    }
+    if rows > 0 || files > 0 {
+        append_log(
+            app,
+            "INFO",
+            &format!("encrypted {rows} history records and {files} image files"),
+        );
+    }
+    Ok(())
+}
+
+#[tauri::command]
+fn get_encryption_status(app: AppHandle) -> Result<EncryptionStatus, String> {
+    encryption_status(&app)
+}
+
+#[tauri::command]
+fn enable_encryption(
+    passphrase: String,
+    app: AppHandle,
+    state: State<AppState>,
+) -> Result<EncryptionStatus, String> {
+    let _guard = lock_history(&app, &state)?;
+
+    let dir = data_dir(&app)?;
+    if crypto::is_enabled(&dir) {
+        return Err("加密存储已启用".to_string());
+    }
+    ensure_storage_layout(&app)?;
+
+    let key = crypto::create_key_file(&dir, &passphrase)?;
+    set_encryption_key(&state, Some(key.clone()))?;
+    // 启用前的日志可能记录了剪贴板内容摘要，加密模式下不再写入，旧日志一并覆盖删除。
+    let log = app_root_dir(&app)?.join(LOG_FILE_NAME);
+    if log.exists() {
+        crypto::shred_file(&log)?;
+    }
+    finish_encryption_migration(&app, &key)?;
+    encryption_status(&app)
+}
+
+#[tauri::command]
+fn unlock_storage(
+    passphrase: String,
+    app: AppHandle,
+    state: State<AppState>,
+) -> Result<EncryptionStatus, String> {
+    let _guard = lock_history(&app, &state)?;
+
+    let key = crypto::unlock(&data_dir(&app)?, &passphrase)?;
+    set_encryption_key(&state, Some(key.clone()))?;
+    finish_encryption_migration(&app, &key)?;
+    encryption_status(&app)
+}
+
+#[tauri::command]
+fn lock_storage(app: AppHandle, state: State<AppState>) -> Result<EncryptionStatus, String> {
+    set_encryption_key(&state, None)?;
+    encryption_status(&app)
+}
+
 #[tauri::command]
 fn suppress_auto_hide(state: State<AppState>) -> Result<(), String> {
     let mut until = state
@@ -1398,6 +2575,11 @@ fn suppress_auto_hide(state: State<AppState>) -> Result<(), String> {
     Ok(())
 }
 
+/// 命令行入口：第一个参数是子命令时执行并返回退出码，否则返回 `None` 由调用方启动图形界面。
+pub fn run_cli() -> Option<i32> {
+    cli::run(std::env::args().skip(1).collect())
+}
+
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
     tauri::Builder::default()
@@ -1409,9 +2591,9 @@ pub fn run() {
         )
         .plugin(
             tauri_plugin_global_shortcut::Builder::new()
-                .with_handler(|app, _shortcut, event| {
+                .with_handler(|app, shortcut, event| {
                     if event.state() == ShortcutState::Pressed {
-                        show_main_window_at_cursor(app);
+                        shortcuts::handle(app, shortcut);
                     }
                 })
                 .build(),
@@ -1439,10 +2621,14 @@ pub fn run() {
             }
             ensure_storage_layout(&app.handle())?;
             let settings = load_settings(&app.handle())?;
-            if let Err(err) = register_global_shortcut(&app.handle(), &settings.global_shortcut) {
+            if let Err(err) = shortcuts::register(&app.handle(), &settings) {
                 eprintln!("global shortcut setup failed: {err}");
-                let fallback = "Alt+Shift+V";
-                register_global_shortcut(&app.handle(), fallback)?;
+                append_log(
+                    &app.handle(),
+                    "WARN",
+                    &format!("setup global shortcuts failed: {err}"),
+                );
+                shortcuts::register_fallback(&app.handle(), &settings)?;
             }
             if let Err(err) = set_autostart_enabled(&app.handle(), settings.launch_at_startup) {
                 append_log(
@@ -1458,6 +2644,16 @@ pub fn run() {
                     &format!("setup always-on-top failed: {err}"),
                 );
             }
+            let verify_handle = app.handle().clone();
+            let spawned = thread::Builder::new()
+                .name("storage-verify".to_string())
+                .spawn(move || integrity::run_startup_check(&verify_handle));
+            if let Err(err) = spawned {
+                eprintln!("storage verify spawn failed: {err}");
+            }
+            watcher::spawn(app.handle().clone());
+            retention::spawn(app.handle().clone());
+            ipc::spawn(app.handle().clone());
             Ok(())
         })
         .on_window_event(|window, event| {
@@ -1487,13 +2683,37 @@ pub fn run() {
             open_storage_dir,
             update_settings,
             get_history,
+            search_history,
             get_image_preview,
             poll_clipboard,
             copy_history_item,
             copy_text,
+            get_template_fields,
+            transform_and_copy,
+            get_paste_queue,
+            start_paste_queue,
+            stop_paste_queue,
+            paste_queue_next,
+            get_capture_paused,
+            get_watcher_backend,
+            update_capture_paused,
             toggle_favorite,
             delete_history_item,
             clear_history,
+            verify_storage,
+            export_history,
+            import_history,
+            list_import_sources,
+            import_from_manager,
+            get_snippets,
+            save_snippet,
+            promote_to_snippet,
+            delete_snippet,
+            copy_snippet,
+            get_encryption_status,
+            enable_encryption,
+            unlock_storage,
+            lock_storage,
             suppress_auto_hide
         ])
         .run(tauri::generate_context!())
@@ -0,0 +1,65 @@
+use crate::{
+    append_log, copy_item_to_clipboard, fingerprint, load_history, load_settings, AppState,
+};
+use arboard::Clipboard;
+use std::collections::HashMap;
+use tauri::{AppHandle, Manager};
+
+/// 常驻的剪贴板实例。arboard 在 Linux 上由共享的后台线程应答粘贴请求，最后一个实例释放时
+/// 才会交出所有权，因此只要它还在，回填写入的内容在短暂创建的实例释放后依然可以粘贴。
+pub(crate) struct Owner {
+    _clipboard: Clipboard,
+}
+
+impl Owner {
+    pub(crate) fn new() -> Result<Self, String> {
+        Ok(Self {
+            _clipboard: Clipboard::new().map_err(|e| format!("访问系统剪贴板失败: {e}"))?,
+        })
+    }
+}
+
+/// 剪贴板的所有者退出后内容随之消失，此时若剪贴板里原本就是最新一条历史，把它重新写回。
+pub(crate) fn take_over(app: &AppHandle) {
+    if let Err(err) = restore_latest(app) {
+        append_log(app, "WARN", &format!("clipboard take over failed: {err}"));
+    }
+}
+
+fn restore_latest(app: &AppHandle) -> Result<(), String> {
+    if !load_settings(app)?.keep_clipboard {
+        return Ok(());
+    }
+    let state = app.state::<AppState>();
+    if *state
+        .capture_paused
+        .lock()
+        .map_err(|_| "采集状态锁获取失败".to_string())?
+    {
+        return Ok(());
+    }
+    let last = state
+        .last_capture_fingerprint
+        .lock()
+        .map_err(|_| "指纹锁获取失败".to_string())?
+        .clone();
+    // 加密存储未解锁时读不到历史，也就无从恢复。
+    let Ok(history) = load_history(app) else {
+        return Ok(());
+    };
+    // 最后一次的内容没有被记录（来自忽略的程序、敏感内容被跳过等）时不去恢复更早的条目。
+    let Some(item) = history
+        .into_iter()
+        .next()
+        .filter(|item| last.as_deref() == Some(fingerprint(item).as_str()))
+    else {
+        return Ok(());
+    };
+    copy_item_to_clipboard(app, &state, &item, false, &HashMap::new())?;
+    append_log(
+        app,
+        "INFO",
+        "clipboard owner exited, restored latest history item",
+    );
+    Ok(())
+}
@@ -0,0 +1,174 @@
+use crate::{append_log, copy_item_to_clipboard, lookup_history_item, AppState, ClipboardItem};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::sync::MutexGuard;
+use tauri::{AppHandle, Emitter, Manager};
+
+pub(crate) const CHANGED_EVENT: &str = "clipboard://paste-queue-changed";
+
+const ORDER_FIFO: &str = "fifo";
+const ORDER_LIFO: &str = "lifo";
+
+/// 粘贴队列：每按一次队列快捷键，就把下一条写入剪贴板。只保存历史条目 id，
+/// 取出时再读取内容，期间被删除的条目直接跳过。
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct PasteQueue {
+    active: bool,
+    order: String,
+    /// 为真时队列开启期间新采集的内容自动追加到队尾。
+    collect: bool,
+    ids: VecDeque<String>,
+}
+
+impl Default for PasteQueue {
+    fn default() -> Self {
+        Self {
+            active: false,
+            order: ORDER_FIFO.to_string(),
+            collect: false,
+            ids: VecDeque::new(),
+        }
+    }
+}
+
+impl PasteQueue {
+    fn pop(&mut self) -> Option<String> {
+        let id = if self.order == ORDER_LIFO {
+            self.ids.pop_back()
+        } else {
+            self.ids.pop_front()
+        };
+        // 选定条目的队列取空即结束；收集模式要等用户手动停止。
+        if self.ids.is_empty() && !self.collect {
+            self.active = false;
+        }
+        id
+    }
+
+    /// 取出的条目读取或写入失败时放回原处，下次按快捷键时重试。
+    fn restore(&mut self, id: String) {
+        if self.order == ORDER_LIFO {
+            self.ids.push_back(id);
+        } else {
+            self.ids.push_front(id);
+        }
+        self.active = true;
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct StartPayload {
+    /// 按选择顺序排列的历史条目 id；为空时只收集之后采集到的内容。
+    #[serde(default)]
+    ids: Vec<String>,
+    #[serde(default)]
+    order: Option<String>,
+    #[serde(default)]
+    collect: bool,
+}
+
+fn lock(state: &AppState) -> Result<MutexGuard<'_, PasteQueue>, String> {
+    state
+        .paste_queue
+        .lock()
+        .map_err(|_| "粘贴队列锁获取失败".to_string())
+}
+
+fn notify(app: &AppHandle, queue: &PasteQueue) {
+    let _ = app.emit(CHANGED_EVENT, queue);
+}
+
+pub(crate) fn status(state: &AppState) -> Result<PasteQueue, String> {
+    Ok(lock(state)?.clone())
+}
+
+pub(crate) fn start(app: &AppHandle, payload: StartPayload) -> Result<PasteQueue, String> {
+    let order = match payload.order.as_deref().map(str::trim) {
+        None | Some("") | Some(ORDER_FIFO) => ORDER_FIFO,
+        Some(ORDER_LIFO) => ORDER_LIFO,
+        Some(other) => return Err(format!("未知的队列顺序: {other}")),
+    };
+    if payload.ids.is_empty() && !payload.collect {
+        return Err("请先选择要加入队列的历史项".to_string());
+    }
+    let state = app.state::<AppState>();
+    let mut queue = lock(&state)?;
+    *queue = PasteQueue {
+        active: true,
+        order: order.to_string(),
+        collect: payload.collect,
+        ids: payload.ids.into_iter().collect(),
+    };
+    notify(app, &queue);
+    Ok(queue.clone())
+}
+
+pub(crate) fn stop(app: &AppHandle) -> Result<PasteQueue, String> {
+    let state = app.state::<AppState>();
+    let mut queue = lock(&state)?;
+    *queue = PasteQueue {
+        order: queue.order.clone(),
+        ..PasteQueue::default()
+    };
+    notify(app, &queue);
+    Ok(queue.clone())
+}
+
+/// 收集模式下把新采集的条目追加到队尾；同一条目重复采集时不重复入队。
+pub(crate) fn collect(app: &AppHandle, state: &AppState, id: &str) {
+    let Ok(mut queue) = lock(state) else {
+        return;
+    };
+    if !queue.active || !queue.collect || queue.ids.iter().any(|queued| queued == id) {
+        return;
+    }
+    queue.ids.push_back(id.to_string());
+    notify(app, &queue);
+}
+
+/// 取出下一条写入剪贴板；队列未开启或已取空时返回 `None`。
+pub(crate) fn advance(app: &AppHandle) -> Result<Option<ClipboardItem>, String> {
+    let state = app.state::<AppState>();
+    loop {
+        let next = {
+            let mut queue = lock(&state)?;
+            if !queue.active {
+                return Ok(None);
+            }
+            queue.pop()
+        };
+        let Some(id) = next else {
+            notify(app, &lock(&state)?);
+            return Ok(None);
+        };
+        let copied = lookup_history_item(app, &id).and_then(|item| {
+            let Some(item) = item else {
+                return Ok(None);
+            };
+            copy_item_to_clipboard(app, &state, &item, false, &HashMap::new())?;
+            Ok(Some(item))
+        });
+        match copied {
+            Ok(Some(item)) => {
+                notify(app, &lock(&state)?);
+                return Ok(Some(item));
+            }
+            Ok(None) => {
+                append_log(
+                    app,
+                    "INFO",
+                    &format!("paste queue skipped missing item {id}"),
+                );
+                notify(app, &lock(&state)?);
+            }
+            Err(err) => {
+                let mut queue = lock(&state)?;
+                queue.restore(id);
+                notify(app, &queue);
+                return Err(err);
+            }
+        }
+    }
+}
@@ -0,0 +1,142 @@
+use crate::{
+    append_log, data_dir, ensure_storage_layout, images, load_settings, lock_history, now_ms,
+    open_history_db, storage, AppState,
+};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::thread;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager};
+
+pub(crate) const PRUNED_EVENT: &str = "clipboard://pruned";
+
+const SWEEP_INTERVAL: Duration = Duration::from_secs(10 * 60);
+const DAY_MS: u64 = 24 * 60 * 60 * 1000;
+
+#[derive(Debug, Default, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct SweepReport {
+    pub(crate) expired: usize,
+    pub(crate) aged_out: usize,
+    pub(crate) images_aged_out: usize,
+    pub(crate) images_over_quota: usize,
+    pub(crate) orphan_files: usize,
+}
+
+impl SweepReport {
+    fn removed_items(&self) -> usize {
+        self.expired + self.aged_out + self.images_aged_out + self.images_over_quota
+    }
+}
+
+pub(crate) fn spawn(app: AppHandle) {
+    let spawned = thread::Builder::new()
+        .name("retention-sweeper".to_string())
+        .spawn(move || loop {
+            sweep_and_notify(&app);
+            thread::sleep(SWEEP_INTERVAL);
+        });
+    if let Err(err) = spawned {
+        eprintln!("retention sweeper spawn failed: {err}");
+    }
+}
+
+/// 执行一次清理；有条目被删除时记录日志并通知界面刷新。
+pub(crate) fn sweep_and_notify(app: &AppHandle) {
+    match sweep(app) {
+        Ok(report) => {
+            if report.removed_items() > 0 || report.orphan_files > 0 {
+                append_log(
+                    app,
+                    "INFO",
+                    &format!(
+                        "retention sweep expired={} aged_out={} images_aged_out={} images_over_quota={} orphan_files={}",
+                        report.expired,
+                        report.aged_out,
+                        report.images_aged_out,
+                        report.images_over_quota,
+                        report.orphan_files
+                    ),
+                );
+            }
+            if report.removed_items() > 0 {
+                let _ = app.emit(PRUNED_EVENT, &report);
+            }
+        }
+        Err(err) => append_log(app, "WARN", &format!("retention sweep failed: {err}")),
+    }
+}
+
+/// 按设置中的保留规则删除条目，再清掉不再被任何条目引用的图片文件。
+/// 只操作未加密的元数据列，存储锁定时同样可以执行。
+pub(crate) fn sweep(app: &AppHandle) -> Result<SweepReport, String> {
+    ensure_storage_layout(app)?;
+    let state = app.state::<AppState>();
+    let _guard = lock_history(app, &state)?;
+
+    let settings = load_settings(app)?;
+    let base = data_dir(app)?;
+    let conn = open_history_db(app)?;
+    let now = now_ms();
+    let mut report = SweepReport {
+        expired: storage::purge_expired(&conn, now)?,
+        ..SweepReport::default()
+    };
+
+    if settings.retention_days > 0 {
+        let cutoff = now.saturating_sub(settings.retention_days * DAY_MS);
+        report.aged_out = storage::delete_unfavorited_before(&conn, None, cutoff)?;
+    }
+    if settings.image_retention_days > 0 {
+        let cutoff = now.saturating_sub(settings.image_retention_days * DAY_MS);
+        report.images_aged_out = storage::delete_unfavorited_before(&conn, Some("image"), cutoff)?;
+    }
+    if settings.max_image_disk_mb > 0 {
+        report.images_over_quota =
+            enforce_image_quota(&conn, &base, settings.max_image_disk_mb * 1024 * 1024)?;
+    }
+    report.orphan_files = images::remove_orphans(&conn, &base)?;
+    Ok(report)
+}
+
+/// 图片总占用超过上限时，从最旧的未收藏图片开始删除；多个条目或片段共用的文件在最后一个引用删除后才计入释放。
+fn enforce_image_quota(
+    conn: &rusqlite::Connection,
+    base: &Path,
+    limit_bytes: u64,
+) -> Result<usize, String> {
+    let rows = storage::image_rows(conn)?;
+    // 片段引用的图片不会随历史条目删除而释放。
+    let snippet_paths = storage::snippet_image_paths(conn)?;
+    let mut refs: HashMap<&str, usize> = HashMap::new();
+    for path in rows
+        .iter()
+        .map(|row| row.image_path.as_str())
+        .chain(snippet_paths.iter().map(String::as_str))
+    {
+        *refs.entry(path).or_default() += 1;
+    }
+    let size_of = |rel: &str| {
+        images::resolve(base, rel)
+            .and_then(|path| fs::metadata(path).ok())
+            .map_or(0, |meta| meta.len())
+    };
+    let mut total: u64 = refs.keys().map(|rel| size_of(rel)).sum();
+
+    let mut removed = 0usize;
+    for row in rows.iter().filter(|row| !row.is_favorite) {
+        if total <= limit_bytes {
+            break;
+        }
+        storage::delete_by_id(conn, &row.id)?;
+        removed += 1;
+        let count = refs.entry(row.image_path.as_str()).or_default();
+        *count = count.saturating_sub(1);
+        if *count == 0 {
+            total = total.saturating_sub(size_of(&row.image_path));
+        }
+    }
+    Ok(removed)
+}
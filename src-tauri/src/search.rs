@@ -0,0 +1,313 @@
+use crate::ClipboardItem;
+use serde::{Deserialize, Serialize};
+use unicode_normalization::char::is_combining_mark;
+use unicode_normalization::UnicodeNormalization;
+
+const DEFAULT_RESULT_LIMIT: usize = 200;
+const MAX_HIGHLIGHTS: usize = 64;
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct SearchPayload {
+    pub(crate) query: String,
+    pub(crate) item_type: Option<String>,
+    pub(crate) favorite_only: Option<bool>,
+    pub(crate) from: Option<u64>,
+    pub(crate) to: Option<u64>,
+    pub(crate) limit: Option<usize>,
+}
+
+/// 高亮区间使用 UTF-16 偏移，方便前端直接对 JS 字符串切片。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub(crate) struct MatchRange {
+    pub(crate) start: usize,
+    pub(crate) end: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct SearchResult {
+    pub(crate) item: ClipboardItem,
+    pub(crate) score: f64,
+    pub(crate) highlights: Vec<MatchRange>,
+}
+
+/// 折叠后的文本：逐字符做 NFKD 分解、去掉附加符号并转小写，同时记录每个折叠字符对应的原文区间。
+struct Folded {
+    chars: Vec<char>,
+    spans: Vec<(usize, usize)>,
+}
+
+fn fold_char(ch: char) -> impl Iterator<Item = char> {
+    ch.nfkd()
+        .filter(|c| !is_combining_mark(*c))
+        .flat_map(char::to_lowercase)
+}
+
+fn fold(text: &str) -> Folded {
+    let mut chars = Vec::with_capacity(text.len());
+    let mut spans = Vec::with_capacity(text.len());
+    let mut offset = 0usize;
+    for ch in text.chars() {
+        let end = offset + ch.len_utf16();
+        for folded in fold_char(ch) {
+            chars.push(folded);
+            spans.push((offset, end));
+        }
+        offset = end;
+    }
+    Folded { chars, spans }
+}
+
+fn is_cjk(ch: char) -> bool {
+    matches!(ch as u32,
+        0x3040..=0x30FF
+        | 0x3400..=0x4DBF
+        | 0x4E00..=0x9FFF
+        | 0xAC00..=0xD7AF
+        | 0xF900..=0xFAFF
+        | 0x20000..=0x2FA1F)
+}
+
+fn is_word_char(ch: char) -> bool {
+    ch.is_alphanumeric() && !is_cjk(ch)
+}
+
+/// 拉丁等文字按非字母数字切词；中日韩连续字符切成重叠的二元组，单字则保留单字。
+fn tokenize(folded: &[char]) -> Vec<Vec<char>> {
+    let mut tokens: Vec<Vec<char>> = Vec::new();
+    let mut push = |token: Vec<char>| {
+        if !token.is_empty() && !tokens.contains(&token) {
+            tokens.push(token);
+        }
+    };
+
+    let mut idx = 0usize;
+    while idx < folded.len() {
+        let ch = folded[idx];
+        if is_cjk(ch) {
+            let start = idx;
+            while idx < folded.len() && is_cjk(folded[idx]) {
+                idx += 1;
+            }
+            let run = &folded[start..idx];
+            if run.len() == 1 {
+                push(run.to_vec());
+            } else {
+                for pair in run.windows(2) {
+                    push(pair.to_vec());
+                }
+            }
+        } else if is_word_char(ch) {
+            let start = idx;
+            while idx < folded.len() && is_word_char(folded[idx]) {
+                idx += 1;
+            }
+            push(folded[start..idx].to_vec());
+        } else {
+            idx += 1;
+        }
+    }
+    tokens
+}
+
+fn find_all(haystack: &[char], needle: &[char]) -> Vec<usize> {
+    if needle.is_empty() || needle.len() > haystack.len() {
+        return Vec::new();
+    }
+    haystack
+        .windows(needle.len())
+        .enumerate()
+        .filter(|(_, window)| *window == needle)
+        .map(|(pos, _)| pos)
+        .collect()
+}
+
+fn merge_ranges(mut ranges: Vec<MatchRange>) -> Vec<MatchRange> {
+    ranges.sort_by_key(|range| (range.start, range.end));
+    let mut merged: Vec<MatchRange> = Vec::new();
+    for range in ranges {
+        match merged.last_mut() {
+            Some(last) if range.start <= last.end => last.end = last.end.max(range.end),
+            _ => merged.push(range),
+        }
+    }
+    merged.truncate(MAX_HIGHLIGHTS);
+    merged
+}
+
+fn score_text(text: &str, phrase: &[char], tokens: &[Vec<char>]) -> Option<(f64, Vec<MatchRange>)> {
+    let folded = fold(text);
+    let mut score = 0.0;
+    let mut ranges: Vec<MatchRange> = Vec::new();
+    let to_range = |pos: usize, len: usize| MatchRange {
+        start: folded.spans[pos].0,
+        end: folded.spans[pos + len - 1].1,
+    };
+
+    for token in tokens {
+        let hits = find_all(&folded.chars, token);
+        if hits.is_empty() {
+            return None;
+        }
+        let boundary_hits = hits
+            .iter()
+            .filter(|pos| **pos == 0 || !is_word_char(folded.chars[**pos - 1]))
+            .count();
+        score += 1.0 + (hits.len() as f64).ln() + 0.5 * (boundary_hits.min(1) as f64);
+        ranges.extend(hits.into_iter().map(|pos| to_range(pos, token.len())));
+    }
+
+    let phrase_hits = find_all(&folded.chars, phrase);
+    if !phrase_hits.is_empty() {
+        score += 3.0;
+        if phrase_hits[0] == 0 {
+            score += 1.0;
+        }
+        ranges.extend(
+            phrase_hits
+                .into_iter()
+                .map(|pos| to_range(pos, phrase.len())),
+        );
+    }
+
+    score += 1.0 / (1.0 + (folded.chars.len() as f64).ln().max(0.0));
+    Some((score, merge_ranges(ranges)))
+}
+
+/// 对已按类型/收藏/时间过滤并按 `updated_at` 倒序排列的候选项做匹配与排序。
+pub(crate) fn rank(
+    items: Vec<ClipboardItem>,
+    query: &str,
+    limit: Option<usize>,
+) -> Vec<SearchResult> {
+    let limit = limit.unwrap_or(DEFAULT_RESULT_LIMIT).max(1);
+    let folded_query = fold(query.trim()).chars;
+    let tokens = tokenize(&folded_query);
+
+    if tokens.is_empty() {
+        return items
+            .into_iter()
+            .take(limit)
+            .map(|item| SearchResult {
+                item,
+                score: 0.0,
+                highlights: Vec::new(),
+            })
+            .collect();
+    }
+
+    let mut phrase: Vec<char> = Vec::with_capacity(folded_query.len());
+    for ch in folded_query {
+        if ch.is_whitespace() {
+            if phrase.last().is_some_and(|last| *last != ' ') {
+                phrase.push(' ');
+            }
+        } else {
+            phrase.push(ch);
+        }
+    }
+
+    let mut results: Vec<SearchResult> = items
+        .into_iter()
+        .filter_map(|item| {
+            let (score, highlights) = score_text(item.text.as_deref()?, &phrase, &tokens)?;
+            Some(SearchResult {
+                item,
+                score,
+                highlights,
+            })
+        })
+        .collect();
+
+    results.sort_by(|a, b| {
+        b.score
+            .total_cmp(&a.score)
+            .then(b.item.updated_at.cmp(&a.item.updated_at))
+    });
+    results.truncate(limit);
+    results
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::to_text_item;
+
+    fn folded(text: &str) -> String {
+        fold(text).chars.into_iter().collect()
+    }
+
+    fn tokens(text: &str) -> Vec<String> {
+        tokenize(&fold(text).chars)
+            .into_iter()
+            .map(|token| token.into_iter().collect())
+            .collect()
+    }
+
+    fn ranges(result: &SearchResult) -> Vec<(usize, usize)> {
+        result
+            .highlights
+            .iter()
+            .map(|range| (range.start, range.end))
+            .collect()
+    }
+
+    #[test]
+    fn fold_strips_accents_and_keeps_spans() {
+        assert_eq!(folded("Café ÉCOLE naïve"), "cafe ecole naive");
+        assert_eq!(folded("ﬁle"), "file");
+
+        let folded = fold("ﬁé");
+        assert_eq!(folded.chars, vec!['f', 'i', 'e']);
+        assert_eq!(folded.spans, vec![(0, 1), (0, 1), (1, 2)]);
+    }
+
+    #[test]
+    fn tokenize_splits_words_and_cjk_bigrams() {
+        assert_eq!(
+            tokens("Hello, 世界和平 hello x"),
+            vec!["hello", "世界", "界和", "和平", "x"]
+        );
+        assert_eq!(tokens("中 test"), vec!["中", "test"]);
+        assert_eq!(tokens("𠀀𠀁"), vec!["𠀀𠀁"]);
+        assert!(tokens(" ,.;").is_empty());
+    }
+
+    #[test]
+    fn rank_matches_folded_text_and_orders_by_score() {
+        let items = vec![
+            to_text_item("tea".to_string()),
+            to_text_item("a naïve café order".to_string()),
+            to_text_item("Cafe au lait".to_string()),
+        ];
+        let results = rank(items, "CAFE", None);
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].item.text.as_deref(), Some("Cafe au lait"));
+        assert_eq!(ranges(&results[0]), vec![(0, 4)]);
+        assert_eq!(ranges(&results[1]), vec![(8, 12)]);
+    }
+
+    #[test]
+    fn highlights_use_utf16_offsets() {
+        let items = vec![to_text_item("🙂 café 🙂 cafe".to_string())];
+        let results = rank(items, "cafe", None);
+        assert_eq!(ranges(&results[0]), vec![(3, 7), (11, 15)]);
+
+        let items = vec![to_text_item("你好𠀀𠀁世界".to_string())];
+        let results = rank(items, "𠀀𠀁", None);
+        assert_eq!(ranges(&results[0]), vec![(2, 6)]);
+    }
+
+    #[test]
+    fn empty_query_keeps_order_and_limit() {
+        let items = vec![
+            to_text_item("one".to_string()),
+            to_text_item("two".to_string()),
+            to_text_item("three".to_string()),
+        ];
+        let results = rank(items, "  ", Some(2));
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[1].item.text.as_deref(), Some("two"));
+        assert!(results.iter().all(|result| result.highlights.is_empty()));
+    }
+}
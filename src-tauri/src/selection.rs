@@ -0,0 +1,183 @@
+/// PRIMARY 与 CLIPBOARD 之间的同步方向。
+pub(crate) const SYNC_OFF: &str = "off";
+pub(crate) const SYNC_TO_CLIPBOARD: &str = "to-clipboard";
+pub(crate) const SYNC_TO_PRIMARY: &str = "to-primary";
+pub(crate) const SYNC_BOTH: &str = "both";
+
+/// 历史条目 `selection` 字段的取值，表示采集自 PRIMARY 选区。
+#[cfg(target_os = "linux")]
+const PRIMARY: &str = "primary";
+
+pub(crate) fn is_sync_mode(mode: &str) -> bool {
+    [SYNC_OFF, SYNC_TO_CLIPBOARD, SYNC_TO_PRIMARY, SYNC_BOTH].contains(&mode)
+}
+
+#[cfg(target_os = "linux")]
+pub(crate) use linux::Selections;
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use super::{PRIMARY, SYNC_BOTH, SYNC_TO_CLIPBOARD, SYNC_TO_PRIMARY};
+    use crate::watcher::CAPTURED_EVENT;
+    use crate::{
+        append_log, ensure_storage_layout, fingerprint, is_internal_log_text, load_settings,
+        lock_history, normalize_text, source, storage_key, store_captured_item, to_text_item,
+        AppSettings, AppState, Captured, ClipboardItem,
+    };
+    use arboard::{Clipboard, GetExtLinux, LinuxClipboardKind, SetExtLinux};
+    use tauri::{AppHandle, Emitter, Manager};
+
+    /// 由后台监听线程持有：采集 PRIMARY 并在两个选区之间同步文本。
+    pub(crate) struct Selections {
+        clipboard: Clipboard,
+        /// 上一次采集的 PRIMARY 指纹；拖选文本时同一内容会反复设置选区。
+        last_primary: Option<String>,
+    }
+
+    impl Selections {
+        pub(crate) fn new() -> Result<Self, String> {
+            Ok(Self {
+                clipboard: Clipboard::new().map_err(|e| format!("访问系统剪贴板失败: {e}"))?,
+                last_primary: None,
+            })
+        }
+
+        /// 读取选区中的原始文本，同步时原样写入另一个选区；只有空白的选区视为空。
+        fn read_text(&mut self, kind: LinuxClipboardKind) -> Option<String> {
+            let text = self.clipboard.get().clipboard(kind).text().ok()?;
+            (!normalize_text(&text).is_empty()).then_some(text)
+        }
+
+        /// 目标选区已经是同样的文本时不再写入，双向同步时自己写入引起的变化也就不会来回传递。
+        fn needs_sync(&mut self, target: LinuxClipboardKind, text: &str) -> bool {
+            self.read_text(target).as_deref() != Some(text)
+        }
+
+        pub(crate) fn primary_changed(&mut self, app: &AppHandle) {
+            let Ok(settings) = load_settings(app) else {
+                return;
+            };
+            let to_clipboard =
+                [SYNC_TO_CLIPBOARD, SYNC_BOTH].contains(&settings.selection_sync.as_str());
+            if !settings.capture_primary && !to_clipboard {
+                return;
+            }
+            let Some(text) = self.read_text(LinuxClipboardKind::Primary) else {
+                return;
+            };
+
+            if settings.capture_primary {
+                match self.capture(app, &settings, &text) {
+                    Ok(Some(item)) => {
+                        let _ = app.emit(CAPTURED_EVENT, &item);
+                    }
+                    Ok(None) => {}
+                    Err(err) => append_log(app, "WARN", &format!("primary capture failed: {err}")),
+                }
+            }
+
+            if to_clipboard && self.needs_sync(LinuxClipboardKind::Clipboard, &text) {
+                // 同步过去的内容已经作为选区记录（或用户未开启选区采集），不再当作新的剪贴板内容采集。
+                if let Ok(mut last) = app.state::<AppState>().last_capture_fingerprint.lock() {
+                    *last = Some(fingerprint(&to_text_item(text.clone())));
+                }
+                if let Err(err) = self.clipboard.set_text(text) {
+                    append_log(
+                        app,
+                        "WARN",
+                        &format!("sync primary to clipboard failed: {err}"),
+                    );
+                }
+            }
+        }
+
+        pub(crate) fn clipboard_changed(&mut self, app: &AppHandle) {
+            let Ok(settings) = load_settings(app) else {
+                return;
+            };
+            if ![SYNC_TO_PRIMARY, SYNC_BOTH].contains(&settings.selection_sync.as_str()) {
+                return;
+            }
+            let Some(text) = self.read_text(LinuxClipboardKind::Clipboard) else {
+                return;
+            };
+            if !self.needs_sync(LinuxClipboardKind::Primary, &text) {
+                return;
+            }
+            let written = self
+                .clipboard
+                .set()
+                .clipboard(LinuxClipboardKind::Primary)
+                .text(text);
+            if let Err(err) = written {
+                append_log(
+                    app,
+                    "WARN",
+                    &format!("sync clipboard to primary failed: {err}"),
+                );
+            }
+        }
+
+        /// 与剪贴板采集走同样的检查与入库流程：加密未解锁、暂停、来源忽略、去重与敏感内容。
+        fn capture(
+            &mut self,
+            app: &AppHandle,
+            settings: &AppSettings,
+            text: &str,
+        ) -> Result<Option<ClipboardItem>, String> {
+            if is_internal_log_text(&normalize_text(text)) {
+                return Ok(None);
+            }
+            ensure_storage_layout(app)?;
+            let Ok(key) = storage_key(app) else {
+                return Ok(None);
+            };
+            let state = app.state::<AppState>();
+            if *state
+                .capture_paused
+                .lock()
+                .map_err(|_| "采集状态锁获取失败".to_string())?
+            {
+                return Ok(None);
+            }
+
+            // 来源检查先于记录指纹，被忽略的选区不会挡住之后从其他程序选中的同样内容。
+            let source = source::detect_primary();
+            if source.secret_hint
+                || source::is_ignored(source.app.as_deref(), &settings.ignored_apps)
+            {
+                self.last_primary = None;
+                return Ok(None);
+            }
+
+            let item = to_text_item(text.to_string());
+            let fp = fingerprint(&item);
+            // 先选中再复制时两个选区内容相同，剪贴板那一条已经记录过。
+            let copied = state
+                .last_capture_fingerprint
+                .lock()
+                .map_err(|_| "指纹锁获取失败".to_string())?
+                .as_deref()
+                == Some(fp.as_str());
+            if copied || self.last_primary.as_deref() == Some(fp.as_str()) {
+                return Ok(None);
+            }
+            self.last_primary = Some(fp);
+
+            let _guard = lock_history(app, &state)?;
+            store_captured_item(
+                app,
+                &state,
+                settings,
+                key.as_ref(),
+                Captured {
+                    item,
+                    source_app: source.app,
+                    selection: Some(PRIMARY),
+                    origin: "primary",
+                    detail: String::new(),
+                },
+            )
+        }
+    }
+}
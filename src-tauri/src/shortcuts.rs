@@ -0,0 +1,254 @@
+use crate::ipc::HISTORY_CHANGED_EVENT;
+use crate::{
+    append_log, autopaste, clear_history, copy_item_to_clipboard, load_history, load_settings,
+    paste_queue, set_capture_paused, show_main_window_at_cursor, source, AppSettings, AppState,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::thread;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager};
+use tauri_plugin_global_shortcut::{GlobalShortcutExt, Shortcut};
+
+const ACTION_OPEN_POPUP: &str = "open-popup";
+const ACTION_PASTE_PREVIOUS: &str = "paste-previous";
+const ACTION_PASTE_NTH: &str = "paste-nth";
+const ACTION_TOGGLE_CAPTURE: &str = "toggle-capture";
+const ACTION_OPEN_SNIPPETS: &str = "open-snippets";
+const ACTION_CLEAR_HISTORY: &str = "clear-history";
+const ACTION_PASTE_QUEUE_NEXT: &str = "paste-queue-next";
+
+/// 通知界面切换到指定页面，负载为页面名（目前只有 `snippets`）。
+pub(crate) const OPEN_PAGE_EVENT: &str = "clipboard://open-page";
+
+const FALLBACK_POPUP_SHORTCUT: &str = "Alt+Shift+V";
+const MAX_PASTE_INDEX: usize = 50;
+/// 快捷键触发时修饰键仍被按住，等用户松开后再模拟粘贴，避免变成 Ctrl+Alt+V 之类的组合。
+const RELEASE_DELAY: Duration = Duration::from_millis(300);
+
+/// 键位表中的一项；`index` 只用于 `paste-nth`，1 表示最新一条。
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ShortcutBinding {
+    pub(crate) accelerator: String,
+    pub(crate) action: String,
+    #[serde(default)]
+    pub(crate) index: Option<usize>,
+}
+
+fn action_label(binding: &ShortcutBinding) -> String {
+    match binding.action.as_str() {
+        ACTION_OPEN_POPUP => "唤起弹窗".to_string(),
+        ACTION_PASTE_PREVIOUS => "粘贴上一条".to_string(),
+        ACTION_PASTE_NTH => format!("粘贴第 {} 条", binding.index.unwrap_or_default()),
+        ACTION_TOGGLE_CAPTURE => "暂停 / 恢复采集".to_string(),
+        ACTION_OPEN_SNIPPETS => "打开片段库".to_string(),
+        ACTION_CLEAR_HISTORY => "清空历史".to_string(),
+        ACTION_PASTE_QUEUE_NEXT => "粘贴队列下一条".to_string(),
+        other => other.to_string(),
+    }
+}
+
+/// 实际生效的全部绑定：唤起快捷键与粘贴队列快捷键在前，之后是键位表。
+pub(crate) fn bindings(settings: &AppSettings) -> Vec<ShortcutBinding> {
+    let mut bindings = vec![ShortcutBinding {
+        accelerator: settings.global_shortcut.clone(),
+        action: ACTION_OPEN_POPUP.to_string(),
+        index: None,
+    }];
+    if !settings.paste_queue_shortcut.is_empty() {
+        bindings.push(ShortcutBinding {
+            accelerator: settings.paste_queue_shortcut.clone(),
+            action: ACTION_PASTE_QUEUE_NEXT.to_string(),
+            index: None,
+        });
+    }
+    bindings.extend(settings.keymap.iter().cloned());
+    bindings
+}
+
+/// 检查格式、动作与序号，并找出绑定到同一组合键的冲突；返回全部问题。
+pub(crate) fn validate(settings: &AppSettings) -> Result<(), String> {
+    let mut errors = Vec::new();
+    let mut seen: Vec<(u32, ShortcutBinding)> = Vec::new();
+    for binding in bindings(settings) {
+        let shortcut = match binding.accelerator.parse::<Shortcut>() {
+            Ok(shortcut) => shortcut,
+            Err(e) => {
+                errors.push(format!("快捷键格式无效: {} ({e})", binding.accelerator));
+                continue;
+            }
+        };
+        match binding.action.as_str() {
+            ACTION_OPEN_POPUP
+            | ACTION_PASTE_PREVIOUS
+            | ACTION_TOGGLE_CAPTURE
+            | ACTION_OPEN_SNIPPETS
+            | ACTION_CLEAR_HISTORY
+            | ACTION_PASTE_QUEUE_NEXT => {}
+            ACTION_PASTE_NTH => {
+                if !binding
+                    .index
+                    .is_some_and(|index| (1..=MAX_PASTE_INDEX).contains(&index))
+                {
+                    errors.push(format!(
+                        "{} 的序号需在 1 到 {MAX_PASTE_INDEX} 之间",
+                        binding.accelerator
+                    ));
+                }
+            }
+            other => errors.push(format!("未知的快捷键动作: {other}")),
+        }
+        if let Some((_, existing)) = seen.iter().find(|(id, _)| *id == shortcut.id()) {
+            errors.push(format!(
+                "快捷键冲突: {} 同时绑定了「{}」和「{}」",
+                binding.accelerator,
+                action_label(existing),
+                action_label(&binding)
+            ));
+            continue;
+        }
+        seen.push((shortcut.id(), binding));
+    }
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors.join("；"))
+    }
+}
+
+/// 清理旧快捷键后注册全部绑定；单个绑定失败（例如已被其他程序占用）不影响其余绑定。
+pub(crate) fn register(app: &AppHandle, settings: &AppSettings) -> Result<(), String> {
+    app.global_shortcut()
+        .unregister_all()
+        .map_err(|e| format!("清理旧快捷键失败: {e}"))?;
+
+    let mut errors = Vec::new();
+    for binding in bindings(settings) {
+        let registered = binding
+            .accelerator
+            .parse::<Shortcut>()
+            .map_err(|e| format!("快捷键格式无效: {e}"))
+            .and_then(|shortcut| {
+                app.global_shortcut()
+                    .register(shortcut)
+                    .map_err(|e| format!("注册快捷键失败: {e}"))
+            });
+        if let Err(err) = registered {
+            errors.push(format!("{} ({})", err, binding.accelerator));
+        }
+    }
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors.join("；"))
+    }
+}
+
+/// 唤起快捷键没能注册时退回默认的 Alt+Shift+V，保证总能打开窗口。
+pub(crate) fn register_fallback(app: &AppHandle, settings: &AppSettings) -> Result<(), String> {
+    let registered = settings
+        .global_shortcut
+        .parse::<Shortcut>()
+        .is_ok_and(|shortcut| app.global_shortcut().is_registered(shortcut));
+    let fallback: Shortcut = FALLBACK_POPUP_SHORTCUT
+        .parse()
+        .map_err(|e| format!("快捷键格式无效: {e}"))?;
+    if registered || app.global_shortcut().is_registered(fallback) {
+        return Ok(());
+    }
+    app.global_shortcut()
+        .register(fallback)
+        .map_err(|e| format!("注册快捷键失败: {e}"))
+}
+
+/// 按下的组合键对应的动作；找不到绑定（例如退回的默认快捷键）时唤起弹窗。
+pub(crate) fn handle(app: &AppHandle, shortcut: &Shortcut) {
+    let binding = load_settings(app).ok().and_then(|settings| {
+        bindings(&settings).into_iter().find(|binding| {
+            binding
+                .accelerator
+                .parse::<Shortcut>()
+                .is_ok_and(|parsed| parsed.id() == shortcut.id())
+        })
+    });
+    let binding = binding.unwrap_or_else(|| ShortcutBinding {
+        accelerator: FALLBACK_POPUP_SHORTCUT.to_string(),
+        action: ACTION_OPEN_POPUP.to_string(),
+        index: None,
+    });
+    if let Err(err) = run(app, &binding) {
+        append_log(
+            app,
+            "WARN",
+            &format!("shortcut action {} failed: {err}", binding.action),
+        );
+    }
+}
+
+fn run(app: &AppHandle, binding: &ShortcutBinding) -> Result<(), String> {
+    match binding.action.as_str() {
+        ACTION_OPEN_POPUP => show_main_window_at_cursor(app),
+        ACTION_PASTE_PREVIOUS => paste_history_entry(app, 2)?,
+        ACTION_PASTE_NTH => paste_history_entry(app, binding.index.unwrap_or(1))?,
+        ACTION_TOGGLE_CAPTURE => {
+            let paused = *app
+                .state::<AppState>()
+                .capture_paused
+                .lock()
+                .map_err(|_| "采集状态锁获取失败".to_string())?;
+            set_capture_paused(app, !paused)?;
+        }
+        ACTION_OPEN_SNIPPETS => {
+            let visible = app
+                .get_webview_window("main")
+                .is_some_and(|window| window.is_visible().unwrap_or(false));
+            if !visible {
+                show_main_window_at_cursor(app);
+            }
+            let _ = app.emit(OPEN_PAGE_EVENT, "snippets");
+        }
+        ACTION_CLEAR_HISTORY => {
+            clear_history(app.clone(), app.state::<AppState>())?;
+            let _ = app.emit(HISTORY_CHANGED_EVENT, ());
+        }
+        ACTION_PASTE_QUEUE_NEXT => {
+            if paste_queue::advance(app)?.is_some() {
+                paste_into_focused(app)?;
+            }
+        }
+        other => return Err(format!("未知的快捷键动作: {other}")),
+    }
+    Ok(())
+}
+
+/// 把第 `index` 新的历史条目写入剪贴板（1 为最新一条，2 即“上一条”）。
+fn paste_history_entry(app: &AppHandle, index: usize) -> Result<(), String> {
+    let item = load_history(app)?
+        .into_iter()
+        .nth(index.saturating_sub(1))
+        .ok_or_else(|| format!("历史中没有第 {index} 条"))?;
+    copy_item_to_clipboard(app, &app.state::<AppState>(), &item, false, &HashMap::new())?;
+    paste_into_focused(app)
+}
+
+/// 开启“选中后自动粘贴”时，直接向当前获得焦点的窗口模拟粘贴。
+fn paste_into_focused(app: &AppHandle) -> Result<(), String> {
+    let settings = load_settings(app)?;
+    if !settings.paste_on_select {
+        return Ok(());
+    }
+    let handle = app.clone();
+    thread::Builder::new()
+        .name("shortcut-paste".to_string())
+        .spawn(move || {
+            thread::sleep(RELEASE_DELAY);
+            let target = source::active_window();
+            let shift = autopaste::needs_shift(target.as_ref(), &settings.shift_paste_apps);
+            if let Err(err) = autopaste::paste(None, shift, None) {
+                append_log(&handle, "WARN", &format!("auto paste failed: {err}"));
+            }
+        })
+        .map(|_| ())
+        .map_err(|e| format!("启动自动粘贴失败: {e}"))
+}
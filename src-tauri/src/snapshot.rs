@@ -0,0 +1,647 @@
+/// 单个条目最多保存的格式数，防止某些程序提供大量转换格式时快照无限膨胀。
+const MAX_FORMATS: usize = 32;
+
+/// 完整快照中的一种格式：Linux 上是 MIME 类型或 X11 目标名，Windows 上是剪贴板格式名。
+#[derive(Debug, Clone)]
+pub(crate) struct Format {
+    pub(crate) mime: String,
+    pub(crate) data: Vec<u8>,
+    /// X11 属性的类型名（如 `UTF8_STRING`、`ATOM`），恢复时按原类型提供；Windows 上没有。
+    pub(crate) property_type: Option<String>,
+    /// X11 属性的单元位数（8、16 或 32），`ATOM` 等类型按 32 位提供；Windows 上没有。
+    pub(crate) property_format: Option<u8>,
+}
+
+/// 读取剪贴板当前提供的全部格式；超过 `max_format_bytes` 的格式跳过，不影响其余格式。
+/// X11 下恢复时整块写入请求方的属性，上限还会收紧到服务器单个请求能容纳的大小。
+pub(crate) fn read(max_format_bytes: usize) -> Result<Vec<Format>, String> {
+    let mut formats = platform::read(max_format_bytes)?;
+    formats.truncate(MAX_FORMATS);
+    Ok(formats)
+}
+
+/// 把快照中的全部格式一起写回剪贴板。
+pub(crate) fn write(formats: Vec<Format>) -> Result<(), String> {
+    if formats.is_empty() {
+        return Err("快照中没有可恢复的格式".to_string());
+    }
+    platform::write(formats)
+}
+
+/// 快照中的纯文本，用于核对快照与同时采集到的条目是否为同一份内容。
+pub(crate) fn plain_text(formats: &[Format]) -> Option<String> {
+    platform::plain_text(formats)
+}
+
+/// 快照中的 PNG 图片。
+pub(crate) fn png(formats: &[Format]) -> Option<&[u8]> {
+    formats
+        .iter()
+        .find(|format| format.mime == platform::PNG_FORMAT)
+        .map(|format| format.data.as_slice())
+}
+
+#[cfg(target_os = "linux")]
+mod platform {
+    use super::Format;
+    use std::thread;
+    use std::time::{Duration, Instant};
+    use x11rb::connection::{Connection, RequestConnection};
+    use x11rb::errors::ConnectionError;
+    use x11rb::protocol::xproto::{
+        Atom, AtomEnum, ConnectionExt as _, CreateWindowAux, EventMask, PropMode, Property,
+        SelectionNotifyEvent, SelectionRequestEvent, Window, WindowClass, SELECTION_NOTIFY_EVENT,
+    };
+    use x11rb::protocol::Event;
+    use x11rb::rust_connection::RustConnection;
+    use x11rb::wrapper::ConnectionExt as _;
+    use x11rb::{COPY_DEPTH_FROM_PARENT, CURRENT_TIME, NONE};
+
+    const CONVERT_TIMEOUT: Duration = Duration::from_millis(500);
+    /// 读取全部格式的总时长上限；采集期间持有历史锁，不能被响应缓慢的程序拖住。
+    const READ_BUDGET: Duration = Duration::from_secs(2);
+    const MAX_TARGETS_BYTES: usize = 64 * 1024;
+    /// 描述剪贴板本身而不是内容的目标，恢复时由我们自己应答 `TARGETS`。
+    const SKIPPED_TARGETS: &[&str] = &[
+        "TARGETS",
+        "MULTIPLE",
+        "TIMESTAMP",
+        "SAVE_TARGETS",
+        "DELETE",
+        "INSERT_SELECTION",
+        "INSERT_PROPERTY",
+        "INCR",
+    ];
+    pub(super) const PNG_FORMAT: &str = "image/png";
+
+    fn x11_error(e: impl std::fmt::Display) -> String {
+        format!("X11 请求失败: {e}")
+    }
+
+    fn intern(conn: &RustConnection, name: &str) -> Result<Atom, String> {
+        Ok(conn
+            .intern_atom(false, name.as_bytes())
+            .map_err(x11_error)?
+            .reply()
+            .map_err(x11_error)?
+            .atom)
+    }
+
+    /// 连接 X11 并创建一个接收属性变化事件的隐藏窗口，用于转换或持有剪贴板。
+    fn connect() -> Result<(RustConnection, Window), String> {
+        if std::env::var_os("DISPLAY").is_none() {
+            return Err("没有可用的 X11 显示，无法使用完整快照".to_string());
+        }
+        // XWayland 只在它的窗口获得焦点时同步 Wayland 剪贴板，读到的可能是旧内容。
+        if std::env::var_os("WAYLAND_DISPLAY").is_some() {
+            return Err("Wayland 会话暂不支持完整快照".to_string());
+        }
+        let (conn, screen_num) = x11rb::connect(None).map_err(|e| format!("连接 X11 失败: {e}"))?;
+        let root = conn.setup().roots[screen_num].root;
+        let window = conn.generate_id().map_err(x11_error)?;
+        conn.create_window(
+            COPY_DEPTH_FROM_PARENT,
+            window,
+            root,
+            0,
+            0,
+            1,
+            1,
+            0,
+            WindowClass::INPUT_ONLY,
+            x11rb::COPY_FROM_PARENT,
+            &CreateWindowAux::new().event_mask(EventMask::PROPERTY_CHANGE),
+        )
+        .map_err(x11_error)?;
+        Ok((conn, window))
+    }
+
+    struct Reader {
+        conn: RustConnection,
+        window: Window,
+        clipboard: Atom,
+        property: Atom,
+        incr: Atom,
+    }
+
+    impl Reader {
+        /// 请求剪贴板所有者把 `target` 转换到我们的窗口属性上，返回数据、属性类型与单元位数；
+        /// 失败、超时或超出大小时返回 `None`。
+        fn convert(
+            &self,
+            target: Atom,
+            max_bytes: usize,
+        ) -> Result<Option<(Vec<u8>, Atom, u8)>, String> {
+            let conn = &self.conn;
+            conn.convert_selection(
+                self.window,
+                self.clipboard,
+                target,
+                self.property,
+                CURRENT_TIME,
+            )
+            .map_err(x11_error)?;
+            conn.flush().map_err(x11_error)?;
+
+            let deadline = Instant::now() + CONVERT_TIMEOUT;
+            loop {
+                match conn.poll_for_event().map_err(x11_error)? {
+                    Some(Event::SelectionNotify(event)) if event.requestor == self.window => {
+                        if event.property == NONE {
+                            return Ok(None);
+                        }
+                        break;
+                    }
+                    Some(_) => {}
+                    None if Instant::now() >= deadline => return Ok(None),
+                    None => thread::sleep(Duration::from_millis(5)),
+                }
+            }
+
+            let head = conn
+                .get_property(false, self.window, self.property, AtomEnum::ANY, 0, 0)
+                .map_err(x11_error)?
+                .reply()
+                .map_err(x11_error)?;
+            if head.type_ == self.incr {
+                return self.read_incr(max_bytes);
+            }
+            let len = head.bytes_after as usize;
+            let reply = conn
+                .get_property(
+                    true,
+                    self.window,
+                    self.property,
+                    AtomEnum::ANY,
+                    0,
+                    if len > max_bytes {
+                        0
+                    } else {
+                        (len as u32).div_ceil(4)
+                    },
+                )
+                .map_err(x11_error)?
+                .reply()
+                .map_err(x11_error)?;
+            Ok((len <= max_bytes).then_some((reply.value, reply.type_, reply.format)))
+        }
+
+        /// 大块数据按 ICCCM 的 INCR 协议分段传输：每删除一次属性，所有者写入下一段，空段表示结束。
+        fn read_incr(&self, max_bytes: usize) -> Result<Option<(Vec<u8>, Atom, u8)>, String> {
+            let conn = &self.conn;
+            conn.delete_property(self.window, self.property)
+                .map_err(x11_error)?;
+            conn.flush().map_err(x11_error)?;
+
+            let mut data = Vec::new();
+            let mut kind = NONE;
+            let mut format = 8;
+            let mut deadline = Instant::now() + CONVERT_TIMEOUT;
+            loop {
+                match conn.poll_for_event().map_err(x11_error)? {
+                    Some(Event::PropertyNotify(event))
+                        if event.window == self.window
+                            && event.atom == self.property
+                            && event.state == Property::NEW_VALUE =>
+                    {
+                        let reply = conn
+                            .get_property(
+                                true,
+                                self.window,
+                                self.property,
+                                AtomEnum::ANY,
+                                0,
+                                u32::MAX / 4,
+                            )
+                            .map_err(x11_error)?
+                            .reply()
+                            .map_err(x11_error)?;
+                        if reply.type_ == NONE {
+                            continue;
+                        }
+                        if reply.value.is_empty() {
+                            return Ok(Some((data, kind, format)));
+                        }
+                        kind = reply.type_;
+                        format = reply.format;
+                        data.extend_from_slice(&reply.value);
+                        if data.len() > max_bytes {
+                            return Ok(None);
+                        }
+                        deadline = Instant::now() + CONVERT_TIMEOUT;
+                    }
+                    Some(_) => {}
+                    None if Instant::now() >= deadline => return Ok(None),
+                    None => thread::sleep(Duration::from_millis(5)),
+                }
+            }
+        }
+    }
+
+    /// 单个 `ChangeProperty` 请求能携带的数据量；恢复时不分段发送，超过的格式无法提供。
+    fn max_property_bytes(conn: &RustConnection) -> usize {
+        conn.maximum_request_bytes().saturating_sub(64)
+    }
+
+    fn atom_name(conn: &RustConnection, atom: Atom) -> Result<String, String> {
+        let name = conn
+            .get_atom_name(atom)
+            .map_err(x11_error)?
+            .reply()
+            .map_err(x11_error)?
+            .name;
+        Ok(String::from_utf8_lossy(&name).to_string())
+    }
+
+    pub(super) fn plain_text(formats: &[Format]) -> Option<String> {
+        formats
+            .iter()
+            .find(|format| {
+                format.mime == "UTF8_STRING"
+                    || format.mime.eq_ignore_ascii_case("text/plain;charset=utf-8")
+            })
+            .and_then(|format| String::from_utf8(format.data.clone()).ok())
+    }
+
+    pub(super) fn read(max_bytes: usize) -> Result<Vec<Format>, String> {
+        let (conn, window) = connect()?;
+        let max_bytes = max_bytes.min(max_property_bytes(&conn));
+        let reader = Reader {
+            clipboard: intern(&conn, "CLIPBOARD")?,
+            property: intern(&conn, "CLIPBOARD_HISTORY_SNAPSHOT")?,
+            incr: intern(&conn, "INCR")?,
+            conn,
+            window,
+        };
+        let targets = intern(&reader.conn, "TARGETS")?;
+        let (raw, _, _) = reader
+            .convert(targets, MAX_TARGETS_BYTES)?
+            .ok_or_else(|| "剪贴板没有提供格式列表".to_string())?;
+        let mut atoms: Vec<Atom> = raw
+            .chunks_exact(4)
+            .map(|chunk| u32::from_ne_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]))
+            .collect();
+        atoms.dedup();
+
+        let cookies = atoms
+            .iter()
+            .map(|atom| reader.conn.get_atom_name(*atom))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(x11_error)?;
+        let mut named = Vec::with_capacity(atoms.len());
+        for (atom, cookie) in atoms.into_iter().zip(cookies) {
+            let name = cookie.reply().map_err(x11_error)?.name;
+            named.push((atom, String::from_utf8_lossy(&name).to_string()));
+        }
+
+        // 浏览器等会把同一张图片转换成多种图片格式，有 PNG 时只保留 PNG。
+        let has_png = named.iter().any(|(_, name)| name == PNG_FORMAT);
+        let started = Instant::now();
+        let mut formats = Vec::new();
+        for (atom, name) in named {
+            if started.elapsed() >= READ_BUDGET {
+                break;
+            }
+            if SKIPPED_TARGETS.contains(&name.as_str())
+                || (has_png && name.starts_with("image/") && name != PNG_FORMAT)
+            {
+                continue;
+            }
+            if let Some((data, kind, property_format)) = reader.convert(atom, max_bytes)? {
+                let property_type = (kind != NONE)
+                    .then(|| atom_name(&reader.conn, kind))
+                    .transpose()?;
+                formats.push(Format {
+                    mime: name,
+                    data,
+                    property_type,
+                    property_format: Some(property_format),
+                });
+            }
+        }
+        Ok(formats)
+    }
+
+    /// 取得剪贴板所有权，并在后台线程中持续应答，直到其他程序写入新内容。
+    pub(super) fn write(formats: Vec<Format>) -> Result<(), String> {
+        let (conn, window) = connect()?;
+        let clipboard = intern(&conn, "CLIPBOARD")?;
+        let targets = intern(&conn, "TARGETS")?;
+        let mut offered = Vec::with_capacity(formats.len());
+        for format in formats {
+            let target = intern(&conn, &format.mime)?;
+            // 旧快照没有记录属性类型，沿用目标名。
+            let kind = match format.property_type.as_deref() {
+                Some(name) => intern(&conn, name)?,
+                None => target,
+            };
+            // 旧快照没有记录单元位数，按 8 位写回；属性长度必须是单元的整数倍。
+            let unit = match format.property_format {
+                Some(bits @ (16 | 32)) if format.data.len() % (bits as usize / 8) == 0 => bits,
+                _ => 8,
+            };
+            offered.push(Offered {
+                target,
+                kind,
+                unit,
+                data: format.data,
+            });
+        }
+
+        conn.set_selection_owner(window, clipboard, CURRENT_TIME)
+            .map_err(x11_error)?;
+        let owner = conn
+            .get_selection_owner(clipboard)
+            .map_err(x11_error)?
+            .reply()
+            .map_err(x11_error)?
+            .owner;
+        if owner != window {
+            return Err("无法取得剪贴板所有权".to_string());
+        }
+
+        thread::Builder::new()
+            .name("clipboard-snapshot".to_string())
+            .spawn(move || serve(&conn, clipboard, targets, &offered))
+            .map(|_| ())
+            .map_err(|e| format!("启动剪贴板快照线程失败: {e}"))
+    }
+
+    struct Offered {
+        target: Atom,
+        kind: Atom,
+        /// 属性的单元位数。
+        unit: u8,
+        data: Vec<u8>,
+    }
+
+    fn serve(conn: &RustConnection, clipboard: Atom, targets: Atom, offered: &[Offered]) {
+        let max_bytes = max_property_bytes(conn);
+        while let Ok(event) = conn.wait_for_event() {
+            match event {
+                Event::SelectionClear(event) if event.selection == clipboard => break,
+                Event::SelectionRequest(request) => {
+                    let _ = answer(conn, &request, targets, offered, max_bytes);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn answer(
+        conn: &RustConnection,
+        request: &SelectionRequestEvent,
+        targets: Atom,
+        offered: &[Offered],
+        max_bytes: usize,
+    ) -> Result<(), ConnectionError> {
+        // ICCCM：旧客户端不指定属性时用目标名作属性。
+        let property = if request.property == NONE {
+            request.target
+        } else {
+            request.property
+        };
+        let found = offered
+            .iter()
+            .find(|format| format.target == request.target && format.data.len() <= max_bytes);
+        let stored = if request.target == targets {
+            let mut atoms = vec![targets];
+            atoms.extend(offered.iter().map(|format| format.target));
+            conn.change_property32(
+                PropMode::REPLACE,
+                request.requestor,
+                property,
+                AtomEnum::ATOM,
+                &atoms,
+            )?;
+            true
+        } else if let Some(format) = found {
+            conn.change_property(
+                PropMode::REPLACE,
+                request.requestor,
+                property,
+                format.kind,
+                format.unit,
+                (format.data.len() / (format.unit as usize / 8)) as u32,
+                &format.data,
+            )?;
+            true
+        } else {
+            false
+        };
+
+        let notify = SelectionNotifyEvent {
+            response_type: SELECTION_NOTIFY_EVENT,
+            sequence: 0,
+            time: request.time,
+            requestor: request.requestor,
+            selection: request.selection,
+            target: request.target,
+            property: if stored { property } else { NONE },
+        };
+        conn.send_event(false, request.requestor, EventMask::NO_EVENT, notify)?;
+        conn.flush()
+    }
+}
+
+#[cfg(target_os = "windows")]
+mod platform {
+    use super::Format;
+    use std::ffi::c_void;
+    use std::thread;
+    use std::time::Duration;
+
+    /// 浏览器等程序注册的 PNG 格式名。
+    pub(super) const PNG_FORMAT: &str = "PNG";
+    const GMEM_MOVEABLE: u32 = 0x0002;
+    const OPEN_ATTEMPTS: usize = 5;
+    /// 预定义格式没有注册名，用固定名称保存，恢复时再换回编号。
+    const STANDARD_FORMATS: &[(u32, &str)] = &[
+        (1, "CF_TEXT"),
+        (4, "CF_SYLK"),
+        (5, "CF_DIF"),
+        (6, "CF_TIFF"),
+        (7, "CF_OEMTEXT"),
+        (8, "CF_DIB"),
+        (10, "CF_PENDATA"),
+        (11, "CF_RIFF"),
+        (12, "CF_WAVE"),
+        (13, "CF_UNICODETEXT"),
+        (15, "CF_HDROP"),
+        (16, "CF_LOCALE"),
+        (17, "CF_DIBV5"),
+    ];
+
+    extern "system" {
+        fn OpenClipboard(hwnd: *mut c_void) -> i32;
+        fn CloseClipboard() -> i32;
+        fn EmptyClipboard() -> i32;
+        fn EnumClipboardFormats(format: u32) -> u32;
+        fn GetClipboardData(format: u32) -> *mut c_void;
+        fn SetClipboardData(format: u32, hmem: *mut c_void) -> *mut c_void;
+        fn GetClipboardFormatNameW(format: u32, name: *mut u16, max: i32) -> i32;
+        fn RegisterClipboardFormatW(name: *const u16) -> u32;
+        fn GlobalAlloc(flags: u32, bytes: usize) -> *mut c_void;
+        fn GlobalFree(hmem: *mut c_void) -> *mut c_void;
+        fn GlobalLock(hmem: *mut c_void) -> *mut c_void;
+        fn GlobalUnlock(hmem: *mut c_void) -> i32;
+        fn GlobalSize(hmem: *mut c_void) -> usize;
+    }
+
+    /// 位图、图元文件、调色板等格式的数据是 GDI 句柄而不是全局内存，无法按字节保存。
+    fn is_global_memory(format: u32) -> bool {
+        !matches!(
+            format,
+            2 | 3 | 9 | 14 | 0x80 | 0x82 | 0x83 | 0x8E | 0x200..=0x3FF
+        )
+    }
+
+    fn format_name(format: u32) -> String {
+        if let Some((_, name)) = STANDARD_FORMATS.iter().find(|(id, _)| *id == format) {
+            return name.to_string();
+        }
+        let mut buf = [0u16; 256];
+        let len = unsafe { GetClipboardFormatNameW(format, buf.as_mut_ptr(), buf.len() as i32) };
+        if len > 0 {
+            String::from_utf16_lossy(&buf[..len as usize])
+        } else {
+            format!("CF_{format}")
+        }
+    }
+
+    fn format_id(name: &str) -> u32 {
+        if let Some((id, _)) = STANDARD_FORMATS.iter().find(|(_, n)| *n == name) {
+            return *id;
+        }
+        if let Some(id) = name.strip_prefix("CF_").and_then(|id| id.parse().ok()) {
+            return id;
+        }
+        let wide: Vec<u16> = name.encode_utf16().chain(std::iter::once(0)).collect();
+        unsafe { RegisterClipboardFormatW(wide.as_ptr()) }
+    }
+
+    /// 剪贴板可能正被其他程序打开，短暂重试几次。
+    fn open_clipboard() -> Result<(), String> {
+        for _ in 0..OPEN_ATTEMPTS {
+            if unsafe { OpenClipboard(std::ptr::null_mut()) } != 0 {
+                return Ok(());
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        Err("剪贴板正被其他程序占用".to_string())
+    }
+
+    unsafe fn read_global(hmem: *mut c_void, max_bytes: usize) -> Option<Vec<u8>> {
+        let size = GlobalSize(hmem);
+        if size > max_bytes {
+            return None;
+        }
+        let ptr = GlobalLock(hmem);
+        if ptr.is_null() {
+            return None;
+        }
+        let data = std::slice::from_raw_parts(ptr as *const u8, size).to_vec();
+        GlobalUnlock(hmem);
+        Some(data)
+    }
+
+    /// `CF_UNICODETEXT` 是以 NUL 结尾的 UTF-16LE。
+    pub(super) fn plain_text(formats: &[Format]) -> Option<String> {
+        let format = formats
+            .iter()
+            .find(|format| format.mime == "CF_UNICODETEXT")?;
+        let units: Vec<u16> = format
+            .data
+            .chunks_exact(2)
+            .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+            .take_while(|unit| *unit != 0)
+            .collect();
+        String::from_utf16(&units).ok()
+    }
+
+    pub(super) fn read(max_bytes: usize) -> Result<Vec<Format>, String> {
+        open_clipboard()?;
+        let mut formats = Vec::new();
+        let mut format = 0;
+        unsafe {
+            loop {
+                format = EnumClipboardFormats(format);
+                if format == 0 {
+                    break;
+                }
+                if !is_global_memory(format) {
+                    continue;
+                }
+                let hmem = GetClipboardData(format);
+                if hmem.is_null() {
+                    continue;
+                }
+                if let Some(data) = read_global(hmem, max_bytes) {
+                    formats.push(Format {
+                        mime: format_name(format),
+                        data,
+                        property_type: None,
+                        property_format: None,
+                    });
+                }
+            }
+            CloseClipboard();
+        }
+        Ok(formats)
+    }
+
+    pub(super) fn write(formats: Vec<Format>) -> Result<(), String> {
+        open_clipboard()?;
+        let mut written = 0usize;
+        unsafe {
+            EmptyClipboard();
+            for format in &formats {
+                let hmem = GlobalAlloc(GMEM_MOVEABLE, format.data.len().max(1));
+                if hmem.is_null() {
+                    continue;
+                }
+                let ptr = GlobalLock(hmem);
+                if ptr.is_null() {
+                    GlobalFree(hmem);
+                    continue;
+                }
+                std::ptr::copy_nonoverlapping(
+                    format.data.as_ptr(),
+                    ptr as *mut u8,
+                    format.data.len(),
+                );
+                GlobalUnlock(hmem);
+                // 写入成功后内存归系统所有，失败时才需要自己释放。
+                if SetClipboardData(format_id(&format.mime), hmem).is_null() {
+                    GlobalFree(hmem);
+                } else {
+                    written += 1;
+                }
+            }
+            CloseClipboard();
+        }
+        if written == 0 {
+            return Err("写入剪贴板快照失败".to_string());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "windows")))]
+mod platform {
+    use super::Format;
+
+    pub(super) const PNG_FORMAT: &str = "image/png";
+
+    pub(super) fn plain_text(_formats: &[Format]) -> Option<String> {
+        None
+    }
+
+    pub(super) fn read(_max_bytes: usize) -> Result<Vec<Format>, String> {
+        Err("当前平台不支持完整快照".to_string())
+    }
+
+    pub(super) fn write(_formats: Vec<Format>) -> Result<(), String> {
+        Err("当前平台不支持完整快照".to_string())
+    }
+}
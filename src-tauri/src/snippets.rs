@@ -0,0 +1,279 @@
+use crate::{
+    build_image_preview_data_url, data_dir, ensure_storage_layout, find_history_item, hash_bytes,
+    images, lock_history, now_ms, open_history_db, refresh_file_entries, storage, storage_key,
+    to_text_item, AppState, ClipboardItem,
+};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+
+const MAX_TITLE_CHARS: usize = 80;
+
+/// 片段库中的一条：独立于历史保存，不受历史上限、保留策略与清空历史影响。
+/// `content` 沿用历史条目的结构，回填时与历史条目走同一条写剪贴板路径。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct Snippet {
+    pub(crate) id: String,
+    pub(crate) title: String,
+    /// 以 `/` 分隔的文件夹路径，空字符串表示根目录。
+    #[serde(default)]
+    pub(crate) folder: String,
+    #[serde(default)]
+    pub(crate) tags: Vec<String>,
+    /// 快捷别名，不区分大小写且全库唯一，可在搜索框中直接输入以定位片段。
+    #[serde(default)]
+    pub(crate) alias: Option<String>,
+    pub(crate) content: ClipboardItem,
+    pub(crate) created_at: u64,
+    pub(crate) updated_at: u64,
+}
+
+/// 新建或修改片段；未提供的字段保持不变。`text` 只用于新建或修改文本片段。
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct SnippetPayload {
+    id: Option<String>,
+    title: Option<String>,
+    folder: Option<String>,
+    tags: Option<Vec<String>>,
+    alias: Option<String>,
+    text: Option<String>,
+}
+
+fn normalize_folder(folder: &str) -> String {
+    folder
+        .split(['/', '\\'])
+        .map(str::trim)
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+fn normalize_tags(tags: Vec<String>) -> Vec<String> {
+    let mut normalized: Vec<String> = Vec::new();
+    for tag in tags {
+        let tag = tag.trim().trim_start_matches('#').trim().to_string();
+        if !tag.is_empty() && !normalized.iter().any(|t| t.eq_ignore_ascii_case(&tag)) {
+            normalized.push(tag);
+        }
+    }
+    normalized
+}
+
+fn normalize_alias(alias: &str) -> Result<Option<String>, String> {
+    let alias = alias.trim();
+    if alias.is_empty() {
+        return Ok(None);
+    }
+    if alias.chars().any(char::is_whitespace) {
+        return Err("别名不能包含空白字符".to_string());
+    }
+    Ok(Some(alias.to_string()))
+}
+
+/// 未填写标题时取内容的第一行。
+fn default_title(content: &ClipboardItem) -> String {
+    let title = match content.item_type.as_str() {
+        "image" => "图片".to_string(),
+        "files" => content
+            .files
+            .iter()
+            .flatten()
+            .next()
+            .map(|entry| entry.path.clone())
+            .unwrap_or_else(|| "文件".to_string()),
+        _ => content
+            .text
+            .as_deref()
+            .unwrap_or_default()
+            .lines()
+            .map(str::trim)
+            .find(|line| !line.is_empty())
+            .unwrap_or("未命名片段")
+            .to_string(),
+    };
+    title.chars().take(MAX_TITLE_CHARS).collect()
+}
+
+fn apply_payload(
+    snippet: &mut Snippet,
+    payload: SnippetPayload,
+    others: &[Snippet],
+) -> Result<(), String> {
+    if let Some(title) = payload.title {
+        let title = title.trim();
+        snippet.title = if title.is_empty() {
+            default_title(&snippet.content)
+        } else {
+            title.to_string()
+        };
+    }
+    if let Some(folder) = payload.folder {
+        snippet.folder = normalize_folder(&folder);
+    }
+    if let Some(tags) = payload.tags {
+        snippet.tags = normalize_tags(tags);
+    }
+    if let Some(alias) = payload.alias {
+        snippet.alias = normalize_alias(&alias)?;
+    }
+    if let Some(alias) = snippet.alias.as_deref() {
+        let taken = others.iter().any(|other| {
+            other.id != snippet.id
+                && other
+                    .alias
+                    .as_deref()
+                    .is_some_and(|a| a.eq_ignore_ascii_case(alias))
+        });
+        if taken {
+            return Err(format!("别名已被其他片段使用: {alias}"));
+        }
+    }
+    snippet.updated_at = now_ms();
+    Ok(())
+}
+
+fn new_snippet(content: ClipboardItem) -> Snippet {
+    let now = now_ms();
+    // 条目的内容哈希可能来自导入文件，长度与字符都不可信，后缀另行计算。
+    let suffix = hash_bytes(format!("{}-{now}", content.id).as_bytes());
+    let id = format!("snip-{now}-{suffix}", suffix = &suffix[0..8]);
+    let content = ClipboardItem {
+        id: id.clone(),
+        image_preview_data_url: None,
+        is_favorite: false,
+        created_at: now,
+        updated_at: now,
+        expires_at: None,
+        selection: None,
+        ..content
+    };
+    Snippet {
+        id,
+        title: default_title(&content),
+        folder: String::new(),
+        tags: Vec::new(),
+        alias: None,
+        content,
+        created_at: now,
+        updated_at: now,
+    }
+}
+
+fn load(app: &AppHandle) -> Result<Vec<Snippet>, String> {
+    ensure_storage_layout(app)?;
+    let conn = open_history_db(app)?;
+    let mut snippets = storage::load_snippets(&conn, storage_key(app)?.as_ref())?;
+    for snippet in &mut snippets {
+        refresh_file_entries(&mut snippet.content);
+    }
+    Ok(snippets)
+}
+
+/// 全部片段，按文件夹、标题排序；片段数量通常不多，图片片段直接附带预览。
+pub(crate) fn list(app: &AppHandle) -> Result<Vec<Snippet>, String> {
+    let mut snippets = load(app)?;
+    for snippet in &mut snippets {
+        snippet.content.image_preview_data_url =
+            build_image_preview_data_url(app, &snippet.content).unwrap_or(None);
+    }
+    snippets.sort_by(|a, b| {
+        (a.folder.as_str(), a.title.to_lowercase())
+            .cmp(&(b.folder.as_str(), b.title.to_lowercase()))
+    });
+    Ok(snippets)
+}
+
+pub(crate) fn find(app: &AppHandle, id: &str) -> Result<Snippet, String> {
+    load(app)?
+        .into_iter()
+        .find(|snippet| snippet.id == id)
+        .ok_or_else(|| "未找到片段".to_string())
+}
+
+/// 带 `id` 时修改已有片段，否则以 `text` 新建文本片段。
+pub(crate) fn save(app: &AppHandle, payload: SnippetPayload) -> Result<Snippet, String> {
+    ensure_storage_layout(app)?;
+    let state = app.state::<AppState>();
+    let _guard = lock_history(app, &state)?;
+    let key = storage_key(app)?;
+    let conn = open_history_db(app)?;
+    let existing = storage::load_snippets(&conn, key.as_ref())?;
+
+    let mut snippet = match payload.id.as_deref() {
+        Some(id) => existing
+            .iter()
+            .find(|snippet| snippet.id == id)
+            .cloned()
+            .ok_or_else(|| "未找到片段".to_string())?,
+        None => {
+            let text = payload.text.as_deref().unwrap_or_default();
+            if text.trim().is_empty() {
+                return Err("片段内容不能为空".to_string());
+            }
+            new_snippet(to_text_item(text.to_string()))
+        }
+    };
+
+    if payload.id.is_some() {
+        if let Some(text) = payload.text.as_deref() {
+            if snippet.content.item_type != "text" {
+                return Err("只有文本片段可以修改内容".to_string());
+            }
+            if text.trim().is_empty() {
+                return Err("片段内容不能为空".to_string());
+            }
+            let edited = to_text_item(text.to_string());
+            snippet.content.text = edited.text;
+            snippet.content.content_hash = edited.content_hash;
+            snippet.content.updated_at = edited.updated_at;
+        }
+    }
+
+    apply_payload(&mut snippet, payload, &existing)?;
+    storage::save_snippet(&conn, key.as_ref(), &snippet)?;
+    Ok(snippet)
+}
+
+/// 把历史条目保存为片段；图片条目与历史共用同一个图片文件。
+pub(crate) fn promote(
+    app: &AppHandle,
+    history_id: &str,
+    payload: SnippetPayload,
+) -> Result<Snippet, String> {
+    ensure_storage_layout(app)?;
+    let state = app.state::<AppState>();
+    let _guard = lock_history(app, &state)?;
+    let key = storage_key(app)?;
+    let conn = open_history_db(app)?;
+    let existing = storage::load_snippets(&conn, key.as_ref())?;
+
+    let item = find_history_item(app, history_id)?;
+    let mut snippet = new_snippet(item);
+    apply_payload(
+        &mut snippet,
+        SnippetPayload {
+            id: None,
+            text: None,
+            ..payload
+        },
+        &existing,
+    )?;
+    storage::save_snippet(&conn, key.as_ref(), &snippet)?;
+    Ok(snippet)
+}
+
+pub(crate) fn delete(app: &AppHandle, id: &str) -> Result<(), String> {
+    let state = app.state::<AppState>();
+    let _guard = lock_history(app, &state)?;
+    let conn = open_history_db(app)?;
+    let snippet = storage::load_snippets(&conn, storage_key(app)?.as_ref())?
+        .into_iter()
+        .find(|snippet| snippet.id == id)
+        .ok_or_else(|| "未找到片段".to_string())?;
+    storage::delete_snippet(&conn, id)?;
+    if let Some(rel) = snippet.content.image_path.as_deref() {
+        images::release(&conn, &data_dir(app)?, rel)?;
+    }
+    Ok(())
+}
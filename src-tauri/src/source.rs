@@ -0,0 +1,364 @@
+/// 密码管理器等程序在剪贴板上附带的提示格式，出现任意一个即表示内容不应被记录。
+const SECRET_HINT_TARGETS: &[&str] = &[
+    "x-kde-passwordManagerHint",
+    "ExcludeClipboardContentFromMonitorProcessing",
+    "org.nspasteboard.ConcealedType",
+];
+
+/// 以内容表达的提示格式：只有其内容为 `secret` 时才表示内容不应被记录。
+#[cfg(target_os = "linux")]
+const SECRET_VALUE_TARGETS: &[&str] = &["CLIPBOARD_STATE"];
+#[cfg(target_os = "linux")]
+const SECRET_VALUE: &[u8] = b"secret";
+
+#[derive(Debug, Default, Clone)]
+pub(crate) struct ClipboardSource {
+    pub(crate) app: Option<String>,
+    pub(crate) secret_hint: bool,
+}
+
+/// 当前获得焦点的顶层窗口；`id` 为平台窗口句柄（X11 窗口号或 Windows `HWND`）。
+#[derive(Debug, Clone)]
+pub(crate) struct ActiveWindow {
+    pub(crate) id: u64,
+    pub(crate) app: Option<String>,
+}
+
+/// 尽力识别当前剪贴板内容的来源程序；无法识别的平台返回空来源。
+pub(crate) fn detect() -> ClipboardSource {
+    platform::detect("CLIPBOARD").unwrap_or_default()
+}
+
+/// 同 [`detect`]，但针对 X11 的 PRIMARY 选区（鼠标选中的文本）。
+#[cfg(target_os = "linux")]
+pub(crate) fn detect_primary() -> ClipboardSource {
+    platform::detect("PRIMARY").unwrap_or_default()
+}
+
+pub(crate) fn active_window() -> Option<ActiveWindow> {
+    platform::active_window()
+}
+
+/// 忽略列表按程序名不区分大小写比较。
+pub(crate) fn is_ignored(app: Option<&str>, ignored_apps: &[String]) -> bool {
+    let Some(app) = app else {
+        return false;
+    };
+    ignored_apps
+        .iter()
+        .any(|ignored| ignored.eq_ignore_ascii_case(app))
+}
+
+#[cfg(target_os = "linux")]
+mod platform {
+    use super::{
+        ActiveWindow, ClipboardSource, SECRET_HINT_TARGETS, SECRET_VALUE, SECRET_VALUE_TARGETS,
+    };
+    use std::fs;
+    use std::time::{Duration, Instant};
+    use x11rb::connection::Connection;
+    use x11rb::protocol::xproto::{
+        Atom, AtomEnum, ConnectionExt as _, CreateWindowAux, GetPropertyReply, Window, WindowClass,
+    };
+    use x11rb::protocol::Event;
+    use x11rb::rust_connection::RustConnection;
+    use x11rb::{COPY_DEPTH_FROM_PARENT, CURRENT_TIME, NONE};
+
+    const TARGETS_TIMEOUT: Duration = Duration::from_millis(200);
+
+    fn is_secret_hint(
+        conn: &RustConnection,
+        root: Window,
+        selection: Atom,
+        targets: &[String],
+    ) -> bool {
+        let matches = |hints: &[&str], target: &str| {
+            hints.iter().any(|hint| hint.eq_ignore_ascii_case(target))
+        };
+        if targets
+            .iter()
+            .any(|target| matches(SECRET_HINT_TARGETS, target))
+        {
+            return true;
+        }
+        targets
+            .iter()
+            .filter(|target| matches(SECRET_VALUE_TARGETS, target))
+            .any(|target| {
+                convert(conn, root, selection, target)
+                    .is_some_and(|reply| reply.value.trim_ascii() == SECRET_VALUE)
+            })
+    }
+
+    pub(super) fn detect(selection: &str) -> Option<ClipboardSource> {
+        std::env::var_os("DISPLAY")?;
+        let (conn, screen_num) = x11rb::connect(None).ok()?;
+        let root = conn.setup().roots[screen_num].root;
+        let selection = intern(&conn, selection)?;
+        let owner = conn
+            .get_selection_owner(selection)
+            .ok()?
+            .reply()
+            .ok()?
+            .owner;
+        if owner == NONE {
+            return None;
+        }
+
+        let app = owner_app_name(&conn, root, owner).or_else(|| active_app_name(&conn, root));
+        let targets = read_targets(&conn, root, selection);
+        let secret_hint = is_secret_hint(&conn, root, selection, &targets);
+        Some(ClipboardSource { app, secret_hint })
+    }
+
+    fn intern(conn: &RustConnection, name: &str) -> Option<Atom> {
+        Some(
+            conn.intern_atom(false, name.as_bytes())
+                .ok()?
+                .reply()
+                .ok()?
+                .atom,
+        )
+    }
+
+    fn property_bytes(
+        conn: &RustConnection,
+        window: Window,
+        property: Atom,
+        kind: Atom,
+    ) -> Option<Vec<u8>> {
+        let reply = conn
+            .get_property(false, window, property, kind, 0, 1024)
+            .ok()?
+            .reply()
+            .ok()?;
+        (!reply.value.is_empty()).then_some(reply.value)
+    }
+
+    /// `WM_CLASS` 为 “实例名\0类名\0”，优先取类名；没有时再用 `_NET_WM_PID` 对应的进程名。
+    fn window_app_name(conn: &RustConnection, window: Window) -> Option<String> {
+        if let Some(raw) = property_bytes(
+            conn,
+            window,
+            AtomEnum::WM_CLASS.into(),
+            AtomEnum::STRING.into(),
+        ) {
+            let mut parts = raw
+                .split(|b| *b == 0)
+                .filter(|part| !part.is_empty())
+                .map(|part| String::from_utf8_lossy(part).to_string());
+            let instance = parts.next();
+            if let Some(name) = parts.next().or(instance) {
+                return Some(name);
+            }
+        }
+
+        let pid_atom = intern(conn, "_NET_WM_PID")?;
+        let raw = property_bytes(conn, window, pid_atom, AtomEnum::CARDINAL.into())?;
+        let pid = u32::from_ne_bytes(raw.get(..4)?.try_into().ok()?);
+        let comm = fs::read_to_string(format!("/proc/{pid}/comm")).ok()?;
+        Some(comm.trim().to_string()).filter(|name| !name.is_empty())
+    }
+
+    /// 剪贴板所有者常是程序的隐藏窗口，自身没有 `WM_CLASS` 时沿父窗口向上查找。
+    fn owner_app_name(conn: &RustConnection, root: Window, owner: Window) -> Option<String> {
+        let mut window = owner;
+        for _ in 0..8 {
+            if let Some(name) = window_app_name(conn, window) {
+                return Some(name);
+            }
+            let parent = conn.query_tree(window).ok()?.reply().ok()?.parent;
+            if parent == root || parent == NONE {
+                return None;
+            }
+            window = parent;
+        }
+        None
+    }
+
+    fn active_window_id(conn: &RustConnection, root: Window) -> Option<Window> {
+        let active_atom = intern(conn, "_NET_ACTIVE_WINDOW")?;
+        let raw = property_bytes(conn, root, active_atom, AtomEnum::WINDOW.into())?;
+        let active = u32::from_ne_bytes(raw.get(..4)?.try_into().ok()?);
+        (active != NONE).then_some(active)
+    }
+
+    fn active_app_name(conn: &RustConnection, root: Window) -> Option<String> {
+        window_app_name(conn, active_window_id(conn, root)?)
+    }
+
+    pub(super) fn active_window() -> Option<ActiveWindow> {
+        std::env::var_os("DISPLAY")?;
+        let (conn, screen_num) = x11rb::connect(None).ok()?;
+        let root = conn.setup().roots[screen_num].root;
+        let active = active_window_id(&conn, root)?;
+        Some(ActiveWindow {
+            id: u64::from(active),
+            app: window_app_name(&conn, active),
+        })
+    }
+
+    /// 通过 `TARGETS` 转换读取剪贴板提供的全部格式名。
+    fn read_targets(conn: &RustConnection, root: Window, clipboard: Atom) -> Vec<String> {
+        try_read_targets(conn, root, clipboard).unwrap_or_default()
+    }
+
+    fn try_read_targets(
+        conn: &RustConnection,
+        root: Window,
+        clipboard: Atom,
+    ) -> Option<Vec<String>> {
+        let reply = convert(conn, root, clipboard, "TARGETS")?;
+        let cookies: Vec<_> = reply
+            .value32()?
+            .filter_map(|atom| conn.get_atom_name(atom).ok())
+            .collect();
+        Some(
+            cookies
+                .into_iter()
+                .filter_map(|cookie| cookie.reply().ok())
+                .map(|reply| String::from_utf8_lossy(&reply.name).to_string())
+                .collect(),
+        )
+    }
+
+    /// 请求剪贴板所有者把内容转换为指定格式，并读回转换结果；超时或所有者拒绝时返回 `None`。
+    fn convert(
+        conn: &RustConnection,
+        root: Window,
+        clipboard: Atom,
+        target: &str,
+    ) -> Option<GetPropertyReply> {
+        let target = intern(conn, target)?;
+        let property = intern(conn, "CLIPBOARD_HISTORY_TARGETS")?;
+        let window = conn.generate_id().ok()?;
+        conn.create_window(
+            COPY_DEPTH_FROM_PARENT,
+            window,
+            root,
+            0,
+            0,
+            1,
+            1,
+            0,
+            WindowClass::INPUT_ONLY,
+            x11rb::COPY_FROM_PARENT,
+            &CreateWindowAux::new(),
+        )
+        .ok()?;
+        conn.convert_selection(window, clipboard, target, property, CURRENT_TIME)
+            .ok()?;
+        conn.flush().ok()?;
+
+        let deadline = Instant::now() + TARGETS_TIMEOUT;
+        let converted = loop {
+            match conn.poll_for_event().ok()? {
+                Some(Event::SelectionNotify(event)) if event.requestor == window => {
+                    break event.property != NONE;
+                }
+                Some(_) => {}
+                None if Instant::now() >= deadline => break false,
+                None => std::thread::sleep(Duration::from_millis(5)),
+            }
+        };
+        let reply = converted.then(|| {
+            conn.get_property(true, window, property, AtomEnum::ANY, 0, 1024)
+                .ok()?
+                .reply()
+                .ok()
+        });
+        let _ = conn.destroy_window(window);
+        reply.flatten()
+    }
+}
+
+#[cfg(target_os = "windows")]
+mod platform {
+    use super::{ActiveWindow, ClipboardSource, SECRET_HINT_TARGETS};
+    use std::ffi::c_void;
+    use std::path::Path;
+
+    const PROCESS_QUERY_LIMITED_INFORMATION: u32 = 0x1000;
+
+    extern "system" {
+        fn GetClipboardOwner() -> *mut c_void;
+        fn GetForegroundWindow() -> *mut c_void;
+        fn GetWindowThreadProcessId(hwnd: *mut c_void, pid: *mut u32) -> u32;
+        fn OpenProcess(access: u32, inherit: i32, pid: u32) -> *mut c_void;
+        fn QueryFullProcessImageNameW(
+            process: *mut c_void,
+            flags: u32,
+            name: *mut u16,
+            size: *mut u32,
+        ) -> i32;
+        fn CloseHandle(handle: *mut c_void) -> i32;
+        fn RegisterClipboardFormatW(name: *const u16) -> u32;
+        fn IsClipboardFormatAvailable(format: u32) -> i32;
+    }
+
+    fn process_name(hwnd: *mut c_void) -> Option<String> {
+        if hwnd.is_null() {
+            return None;
+        }
+        unsafe {
+            let mut pid = 0u32;
+            GetWindowThreadProcessId(hwnd, &mut pid);
+            if pid == 0 {
+                return None;
+            }
+            let process = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, 0, pid);
+            if process.is_null() {
+                return None;
+            }
+            let mut buf = [0u16; 1024];
+            let mut len = buf.len() as u32;
+            let ok = QueryFullProcessImageNameW(process, 0, buf.as_mut_ptr(), &mut len);
+            CloseHandle(process);
+            if ok == 0 {
+                return None;
+            }
+            let path = String::from_utf16_lossy(&buf[..len as usize]);
+            Path::new(&path)
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().to_string())
+        }
+    }
+
+    /// Windows 只有一个剪贴板，忽略选区名。
+    pub(super) fn detect(_selection: &str) -> Option<ClipboardSource> {
+        let secret_hint = SECRET_HINT_TARGETS.iter().any(|name| {
+            let wide: Vec<u16> = name.encode_utf16().chain(std::iter::once(0)).collect();
+            unsafe {
+                let format = RegisterClipboardFormatW(wide.as_ptr());
+                format != 0 && IsClipboardFormatAvailable(format) != 0
+            }
+        });
+        let (owner, foreground) = unsafe { (GetClipboardOwner(), GetForegroundWindow()) };
+        let app = process_name(owner).or_else(|| process_name(foreground));
+        Some(ClipboardSource { app, secret_hint })
+    }
+
+    pub(super) fn active_window() -> Option<ActiveWindow> {
+        let foreground = unsafe { GetForegroundWindow() };
+        if foreground.is_null() {
+            return None;
+        }
+        Some(ActiveWindow {
+            id: foreground as usize as u64,
+            app: process_name(foreground),
+        })
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "windows")))]
+mod platform {
+    use super::{ActiveWindow, ClipboardSource};
+
+    pub(super) fn detect(_selection: &str) -> Option<ClipboardSource> {
+        None
+    }
+
+    pub(super) fn active_window() -> Option<ActiveWindow> {
+        None
+    }
+}
@@ -0,0 +1,987 @@
+use crate::crypto::{self, Key};
+use crate::snapshot;
+use crate::snippets::Snippet;
+use crate::{clean_history, ClipboardItem, FileEntry, HISTORY_FILE_NAME};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use rusqlite::{
+    params, Connection, OptionalExtension, Params, Row, Transaction, TransactionBehavior,
+};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+pub(crate) const DB_FILE_NAME: &str = "clipboard-history.db";
+const LOCK_FILE_NAME: &str = "clipboard-history.lock";
+const LEGACY_IMPORTED_SUFFIX: &str = ".imported";
+
+const ITEM_COLUMNS: &str =
+    "id, item_type, text, image_path, content_hash, is_favorite, created_at, \
+     updated_at, html, files, sealed, is_sensitive, expires_at, source_app, selection";
+
+/// 按顺序执行的建表/升级脚本，`PRAGMA user_version` 记录已执行到第几条。
+const MIGRATIONS: &[&str] = &[
+    "CREATE TABLE IF NOT EXISTS items (
+        id TEXT PRIMARY KEY NOT NULL,
+        item_type TEXT NOT NULL,
+        text TEXT,
+        image_path TEXT,
+        content_hash TEXT NOT NULL,
+        is_favorite INTEGER NOT NULL DEFAULT 0,
+        created_at INTEGER NOT NULL,
+        updated_at INTEGER NOT NULL
+    );
+    CREATE UNIQUE INDEX IF NOT EXISTS idx_items_type_hash ON items(item_type, content_hash);
+    CREATE INDEX IF NOT EXISTS idx_items_content_hash ON items(content_hash);
+    CREATE INDEX IF NOT EXISTS idx_items_updated_at ON items(updated_at DESC);
+    CREATE INDEX IF NOT EXISTS idx_items_is_favorite ON items(is_favorite);",
+    "ALTER TABLE items ADD COLUMN html TEXT;",
+    "ALTER TABLE items ADD COLUMN files TEXT;",
+    "ALTER TABLE items ADD COLUMN sealed BLOB;",
+    "ALTER TABLE items ADD COLUMN is_sensitive INTEGER NOT NULL DEFAULT 0;
+    ALTER TABLE items ADD COLUMN expires_at INTEGER;
+    CREATE INDEX IF NOT EXISTS idx_items_expires_at ON items(expires_at) WHERE expires_at IS NOT NULL;",
+    "ALTER TABLE items ADD COLUMN source_app TEXT;",
+    "CREATE TABLE IF NOT EXISTS snippets (
+        id TEXT PRIMARY KEY NOT NULL,
+        image_path TEXT,
+        data BLOB NOT NULL,
+        updated_at INTEGER NOT NULL
+    );",
+    "CREATE TABLE IF NOT EXISTS snapshots (
+        item_id TEXT PRIMARY KEY NOT NULL,
+        data BLOB NOT NULL
+    );
+    CREATE TRIGGER IF NOT EXISTS trg_items_delete_snapshot AFTER DELETE ON items
+    BEGIN
+        DELETE FROM snapshots WHERE item_id = OLD.id;
+    END;",
+    "ALTER TABLE items ADD COLUMN selection TEXT;",
+];
+
+pub(crate) fn open(data_dir: &Path) -> Result<Connection, String> {
+    let mut conn = Connection::open(data_dir.join(DB_FILE_NAME))
+        .map_err(|e| format!("打开历史数据库失败: {e}"))?;
+    conn.busy_timeout(Duration::from_secs(5))
+        .map_err(|e| format!("设置数据库超时失败: {e}"))?;
+    conn.pragma_update(None, "journal_mode", "WAL")
+        .map_err(|e| format!("设置数据库日志模式失败: {e}"))?;
+    conn.pragma_update(None, "synchronous", "NORMAL")
+        .map_err(|e| format!("设置数据库同步模式失败: {e}"))?;
+    conn.pragma_update(None, "secure_delete", "ON")
+        .map_err(|e| format!("设置数据库安全删除失败: {e}"))?;
+    migrate(&mut conn)?;
+    Ok(conn)
+}
+
+/// 数据目录级别的独占文件锁，图形界面与命令行共用同一数据目录时用于串行化写入；随返回的文件句柄释放。
+pub(crate) fn lock_dir(data_dir: &Path) -> Result<fs::File, String> {
+    let file = fs::OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(data_dir.join(LOCK_FILE_NAME))
+        .map_err(|e| format!("打开锁文件失败: {e}"))?;
+    file.lock()
+        .map_err(|e| format!("获取数据目录锁失败: {e}"))?;
+    Ok(file)
+}
+
+fn migrate(conn: &mut Connection) -> Result<(), String> {
+    let version: i64 = conn
+        .query_row("PRAGMA user_version", [], |row| row.get(0))
+        .map_err(|e| format!("读取数据库版本失败: {e}"))?;
+    let version = version.max(0) as usize;
+    if version >= MIGRATIONS.len() {
+        return Ok(());
+    }
+
+    let tx = conn
+        .transaction()
+        .map_err(|e| format!("开启数据库事务失败: {e}"))?;
+    for sql in &MIGRATIONS[version..] {
+        tx.execute_batch(sql)
+            .map_err(|e| format!("升级数据库结构失败: {e}"))?;
+    }
+    tx.pragma_update(None, "user_version", MIGRATIONS.len() as i64)
+        .map_err(|e| format!("写入数据库版本失败: {e}"))?;
+    tx.commit().map_err(|e| format!("提交数据库事务失败: {e}"))
+}
+
+/// 将旧版 `clipboard-history.json` 一次性导入数据库，导入后重命名为 `.imported` 以免重复导入。
+pub(crate) fn import_legacy_json(
+    conn: &mut Connection,
+    key: Option<&Key>,
+    data_dir: &Path,
+    history_limit: usize,
+) -> Result<usize, String> {
+    let path = data_dir.join(HISTORY_FILE_NAME);
+    if !path.exists() {
+        return Ok(0);
+    }
+
+    let raw = fs::read_to_string(&path).map_err(|e| format!("读取旧历史文件失败: {e}"))?;
+    let items: Vec<ClipboardItem> = if raw.trim().is_empty() {
+        Vec::new()
+    } else {
+        serde_json::from_str(&raw).map_err(|e| format!("解析旧历史文件失败: {e}"))?
+    };
+    let items = clean_history(items, history_limit);
+
+    let tx = conn
+        .transaction()
+        .map_err(|e| format!("开启数据库事务失败: {e}"))?;
+    let mut imported = 0usize;
+    for item in &items {
+        imported += insert_item(&tx, key, item, "INSERT OR IGNORE")?;
+    }
+    tx.commit()
+        .map_err(|e| format!("提交数据库事务失败: {e}"))?;
+
+    fs::rename(&path, legacy_imported_path(data_dir))
+        .map_err(|e| format!("重命名旧历史文件失败: {e}"))?;
+    Ok(imported)
+}
+
+/// 旧版历史文件导入后保留的副本，内容为明文。
+pub(crate) fn legacy_imported_path(data_dir: &Path) -> PathBuf {
+    let mut name = data_dir.join(HISTORY_FILE_NAME).into_os_string();
+    name.push(LEGACY_IMPORTED_SUFFIX);
+    PathBuf::from(name)
+}
+
+/// 把整个数据库复制到新目录（包含尚未落盘到主文件的 WAL 内容）。
+pub(crate) fn copy_database(old_dir: &Path, new_dir: &Path) -> Result<(), String> {
+    let target = new_dir.join(DB_FILE_NAME);
+    if !old_dir.join(DB_FILE_NAME).exists() || target.exists() {
+        return Ok(());
+    }
+
+    let conn = open(old_dir)?;
+    conn.execute("VACUUM INTO ?1", params![target.to_string_lossy()])
+        .map_err(|e| format!("迁移历史数据库失败: {e}"))?;
+    Ok(())
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SealedFields {
+    content_hash: String,
+    text: Option<String>,
+    html: Option<String>,
+    files: Option<Vec<FileEntry>>,
+}
+
+struct StoredRow {
+    item: ClipboardItem,
+    sealed: Option<Vec<u8>>,
+}
+
+fn row_to_stored(row: &Row<'_>) -> rusqlite::Result<StoredRow> {
+    let item = ClipboardItem {
+        id: row.get(0)?,
+        item_type: row.get(1)?,
+        text: row.get(2)?,
+        image_path: row.get(3)?,
+        image_preview_data_url: None,
+        content_hash: row.get(4)?,
+        is_favorite: row.get(5)?,
+        created_at: row.get::<_, i64>(6)? as u64,
+        updated_at: row.get::<_, i64>(7)? as u64,
+        html: row.get(8)?,
+        files: row
+            .get::<_, Option<String>>(9)?
+            .and_then(|raw| serde_json::from_str(&raw).ok()),
+        is_sensitive: row.get(11)?,
+        expires_at: row.get::<_, Option<i64>>(12)?.map(|v| v as u64),
+        source_app: row.get(13)?,
+        selection: row.get(14)?,
+    };
+    Ok(StoredRow {
+        item,
+        sealed: row.get(10)?,
+    })
+}
+
+fn unseal(key: Option<&Key>, stored: StoredRow) -> Result<ClipboardItem, String> {
+    let StoredRow { mut item, sealed } = stored;
+    let Some(sealed) = sealed else {
+        return Ok(item);
+    };
+    let key = key.ok_or_else(|| "存储已加密，请先解锁".to_string())?;
+    let fields: SealedFields = serde_json::from_slice(&crypto::open(key, &sealed)?)
+        .map_err(|e| format!("解析加密记录失败: {e}"))?;
+    item.content_hash = fields.content_hash;
+    item.text = fields.text;
+    item.html = fields.html;
+    item.files = fields.files;
+    Ok(item)
+}
+
+/// 有密钥时把内容字段整体加密进 `sealed` 列，`content_hash` 列改存带密钥的查找哈希。
+fn seal_for_storage(
+    key: Option<&Key>,
+    item: &ClipboardItem,
+) -> Result<(ClipboardItem, Option<Vec<u8>>), String> {
+    let Some(key) = key else {
+        return Ok((item.clone(), None));
+    };
+    let fields = SealedFields {
+        content_hash: item.content_hash.clone(),
+        text: item.text.clone(),
+        html: item.html.clone(),
+        files: item.files.clone(),
+    };
+    let plain = serde_json::to_vec(&fields).map_err(|e| format!("序列化历史项失败: {e}"))?;
+
+    let mut stored = item.clone();
+    stored.content_hash = crypto::lookup_hash(key, &item.content_hash);
+    stored.text = None;
+    stored.html = None;
+    stored.files = None;
+    Ok((stored, Some(crypto::seal(key, &plain)?)))
+}
+
+fn lookup_hash(key: Option<&Key>, content_hash: &str) -> String {
+    match key {
+        Some(key) => crypto::lookup_hash(key, content_hash),
+        None => content_hash.to_string(),
+    }
+}
+
+fn insert_item(
+    conn: &Connection,
+    key: Option<&Key>,
+    item: &ClipboardItem,
+    verb: &str,
+) -> Result<usize, String> {
+    let (stored, sealed) = seal_for_storage(key, item)?;
+    conn.execute(
+        &format!(
+            "{verb} INTO items ({ITEM_COLUMNS})
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15)"
+        ),
+        params![
+            stored.id,
+            stored.item_type,
+            stored.text,
+            stored.image_path,
+            stored.content_hash,
+            stored.is_favorite,
+            stored.created_at as i64,
+            stored.updated_at as i64,
+            stored.html,
+            stored
+                .files
+                .as_ref()
+                .and_then(|files| serde_json::to_string(files).ok()),
+            sealed,
+            stored.is_sensitive,
+            stored.expires_at.map(|v| v as i64),
+            stored.source_app,
+            stored.selection
+        ],
+    )
+    .map_err(|e| format!("写入历史失败: {e}"))
+}
+
+fn query_items<P: Params>(
+    conn: &Connection,
+    key: Option<&Key>,
+    sql: &str,
+    params: P,
+) -> Result<Vec<ClipboardItem>, String> {
+    let mut stmt = conn
+        .prepare(sql)
+        .map_err(|e| format!("读取历史失败: {e}"))?;
+    let rows = stmt
+        .query_map(params, row_to_stored)
+        .map_err(|e| format!("读取历史失败: {e}"))?;
+    rows.map(|row| unseal(key, row.map_err(|e| format!("解析历史失败: {e}"))?))
+        .collect()
+}
+
+pub(crate) fn load_items(
+    conn: &Connection,
+    key: Option<&Key>,
+) -> Result<Vec<ClipboardItem>, String> {
+    query_items(
+        conn,
+        key,
+        &format!("SELECT {ITEM_COLUMNS} FROM items ORDER BY updated_at DESC"),
+        [],
+    )
+}
+
+pub(crate) fn load_filtered_items(
+    conn: &Connection,
+    key: Option<&Key>,
+    item_type: Option<&str>,
+    favorite_only: bool,
+    from: Option<u64>,
+    to: Option<u64>,
+) -> Result<Vec<ClipboardItem>, String> {
+    query_items(
+        conn,
+        key,
+        &format!(
+            "SELECT {ITEM_COLUMNS} FROM items
+             WHERE (?1 IS NULL OR item_type = ?1)
+               AND (?2 = 0 OR is_favorite = 1)
+               AND (?3 IS NULL OR updated_at >= ?3)
+               AND (?4 IS NULL OR updated_at <= ?4)
+             ORDER BY updated_at DESC"
+        ),
+        params![
+            item_type,
+            favorite_only,
+            from.map(|v| v as i64),
+            to.map(|v| v as i64)
+        ],
+    )
+}
+
+pub(crate) fn find_item(
+    conn: &Connection,
+    key: Option<&Key>,
+    id: &str,
+) -> Result<Option<ClipboardItem>, String> {
+    let stored = conn
+        .query_row(
+            &format!("SELECT {ITEM_COLUMNS} FROM items WHERE id = ?1"),
+            params![id],
+            row_to_stored,
+        )
+        .optional()
+        .map_err(|e| format!("读取历史项失败: {e}"))?;
+    stored.map(|stored| unseal(key, stored)).transpose()
+}
+
+/// 按历史上限移除的条目数与它们引用的图片路径，图片由调用方在引用归零后删除。
+#[derive(Debug, Default)]
+pub(crate) struct Evicted {
+    pub(crate) count: usize,
+    pub(crate) image_paths: Vec<String>,
+}
+
+/// 按上限截断历史。上限只统计未收藏的条目，超出时从最旧的未收藏条目开始移除，收藏不会因上限被删除。
+fn prune(tx: &Transaction<'_>, history_limit: usize) -> Result<Evicted, String> {
+    let overflow =
+        "SELECT id FROM items WHERE is_favorite = 0 ORDER BY updated_at DESC LIMIT -1 OFFSET ?1";
+    let mut stmt = tx
+        .prepare(&format!(
+            "SELECT image_path FROM items WHERE image_path IS NOT NULL AND id IN ({overflow})"
+        ))
+        .map_err(|e| format!("查询超出上限的历史失败: {e}"))?;
+    let image_paths = stmt
+        .query_map(params![history_limit as i64], |row| row.get(0))
+        .map_err(|e| format!("查询超出上限的历史失败: {e}"))?
+        .collect::<rusqlite::Result<Vec<String>>>()
+        .map_err(|e| format!("查询超出上限的历史失败: {e}"))?;
+
+    let count = tx
+        .execute(
+            &format!("DELETE FROM items WHERE id IN ({overflow})"),
+            params![history_limit as i64],
+        )
+        .map_err(|e| format!("清理超出上限的历史失败: {e}"))?;
+    Ok(Evicted { count, image_paths })
+}
+
+/// 按新的上限立即截断历史，用于调低 `history_limit` 之后。
+pub(crate) fn enforce_history_limit(
+    conn: &mut Connection,
+    history_limit: usize,
+) -> Result<Evicted, String> {
+    let tx = conn
+        .transaction()
+        .map_err(|e| format!("开启数据库事务失败: {e}"))?;
+    let evicted = prune(&tx, history_limit)?;
+    tx.commit()
+        .map_err(|e| format!("提交数据库事务失败: {e}"))?;
+    Ok(evicted)
+}
+
+/// 与旧版 `dedupe_and_upsert` 语义一致：相同类型和哈希的条目只刷新时间并置顶，否则插入新条目，
+/// 最后按 `history_limit` 截断。返回写入后的条目以及截断移除的条目。
+/// 已有条目再次从选区采集时保留原来的来源选区，只有从剪贴板采集才会把它改为剪贴板条目。
+pub(crate) fn upsert_item(
+    conn: &mut Connection,
+    key: Option<&Key>,
+    incoming: ClipboardItem,
+    history_limit: usize,
+) -> Result<(ClipboardItem, Evicted), String> {
+    let tx = conn
+        .transaction()
+        .map_err(|e| format!("开启数据库事务失败: {e}"))?;
+
+    let existing: Option<String> = tx
+        .query_row(
+            "SELECT id FROM items WHERE item_type = ?1 AND content_hash = ?2",
+            params![incoming.item_type, lookup_hash(key, &incoming.content_hash)],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(|e| format!("查询重复历史失败: {e}"))?;
+
+    let id = if let Some(id) = existing {
+        tx.execute(
+            "UPDATE items SET updated_at = ?1,
+                 is_sensitive = MAX(is_sensitive, ?2),
+                 expires_at = CASE WHEN is_favorite = 1 THEN NULL ELSE COALESCE(?3, expires_at) END,
+                 source_app = COALESCE(?4, source_app),
+                 selection = CASE WHEN ?5 IS NULL THEN NULL ELSE selection END
+             WHERE id = ?6",
+            params![
+                incoming.updated_at as i64,
+                incoming.is_sensitive,
+                incoming.expires_at.map(|v| v as i64),
+                incoming.source_app,
+                incoming.selection,
+                id
+            ],
+        )
+        .map_err(|e| format!("更新历史失败: {e}"))?;
+        id
+    } else {
+        insert_item(&tx, key, &incoming, "INSERT")?;
+        incoming.id.clone()
+    };
+
+    let evicted = prune(&tx, history_limit)?;
+
+    let mut stored = find_item(&tx, key, &id)?.ok_or_else(|| "未找到历史项".to_string())?;
+    tx.commit()
+        .map_err(|e| format!("提交数据库事务失败: {e}"))?;
+
+    stored.image_preview_data_url = incoming.image_preview_data_url;
+    Ok((stored, evicted))
+}
+
+#[derive(Debug, Default, Clone, Copy, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct MergeReport {
+    pub(crate) inserted: usize,
+    pub(crate) merged: usize,
+}
+
+/// 导入用：与已有条目类型和哈希相同时合并（创建时间取较早、更新时间取较晚、收藏取并集），
+/// 否则保留原时间插入，id 冲突时换用新 id。新增的收藏不超过 `favorite_limit`，超出的按普通条目导入。
+/// 最后按 `history_limit` 截断，返回截断移除的条目。
+pub(crate) fn merge_items(
+    conn: &mut Connection,
+    key: Option<&Key>,
+    items: &[ClipboardItem],
+    history_limit: usize,
+    favorite_limit: usize,
+) -> Result<(MergeReport, Evicted), String> {
+    let tx = conn
+        .transaction()
+        .map_err(|e| format!("开启数据库事务失败: {e}"))?;
+
+    let mut report = MergeReport::default();
+    let mut favorites = favorite_count(&tx)?;
+    for item in items {
+        let mut item = item.clone();
+        if item.is_favorite {
+            let existing: Option<bool> = tx
+                .query_row(
+                    "SELECT is_favorite FROM items WHERE item_type = ?1 AND content_hash = ?2",
+                    params![item.item_type, lookup_hash(key, &item.content_hash)],
+                    |row| row.get(0),
+                )
+                .optional()
+                .map_err(|e| format!("读取收藏状态失败: {e}"))?;
+            // 已是收藏的条目不占新的名额；超出收藏上限的按普通条目导入。
+            if existing != Some(true) {
+                if favorites >= favorite_limit {
+                    item.is_favorite = false;
+                } else {
+                    favorites += 1;
+                }
+            }
+        }
+
+        let merged = tx
+            .execute(
+                "UPDATE items SET created_at = MIN(created_at, ?1),
+                     updated_at = MAX(updated_at, ?2),
+                     is_favorite = MAX(is_favorite, ?3),
+                     expires_at = CASE WHEN MAX(is_favorite, ?3) = 1 THEN NULL ELSE expires_at END,
+                     source_app = COALESCE(source_app, ?4),
+                     selection = COALESCE(selection, ?5)
+                 WHERE item_type = ?6 AND content_hash = ?7",
+                params![
+                    item.created_at as i64,
+                    item.updated_at as i64,
+                    item.is_favorite,
+                    item.source_app,
+                    item.selection,
+                    item.item_type,
+                    lookup_hash(key, &item.content_hash)
+                ],
+            )
+            .map_err(|e| format!("合并历史失败: {e}"))?;
+        if merged > 0 {
+            report.merged += 1;
+            continue;
+        }
+
+        if insert_item(&tx, key, &item, "INSERT OR IGNORE")? == 0 {
+            let mut renamed = item.clone();
+            renamed.id = format!("{}-import-{}", item.id, report.inserted);
+            insert_item(&tx, key, &renamed, "INSERT")?;
+        }
+        report.inserted += 1;
+    }
+
+    let evicted = prune(&tx, history_limit)?;
+    tx.commit()
+        .map_err(|e| format!("提交数据库事务失败: {e}"))?;
+    Ok((report, evicted))
+}
+
+/// 把尚未加密的记录改写为加密格式，可重复执行，用于启用加密或继续被中断的迁移。
+pub(crate) fn encrypt_existing_rows(conn: &mut Connection, key: &Key) -> Result<usize, String> {
+    let tx = conn
+        .transaction()
+        .map_err(|e| format!("开启数据库事务失败: {e}"))?;
+    let plain_items = query_items(
+        &tx,
+        None,
+        &format!("SELECT {ITEM_COLUMNS} FROM items WHERE sealed IS NULL"),
+        [],
+    )?;
+    for item in &plain_items {
+        let (stored, sealed) = seal_for_storage(Some(key), item)?;
+        tx.execute(
+            "UPDATE items SET content_hash = ?1, text = NULL, html = NULL, files = NULL, sealed = ?2
+             WHERE id = ?3",
+            params![stored.content_hash, sealed, stored.id],
+        )
+        .map_err(|e| format!("加密历史记录失败: {e}"))?;
+    }
+    let plain_snippets: Vec<Snippet> = load_snippet_rows(&tx, Some(key))?
+        .into_iter()
+        .filter_map(|(snippet, sealed)| (!sealed).then_some(snippet))
+        .collect();
+    for snippet in &plain_snippets {
+        save_snippet(&tx, Some(key), snippet)?;
+    }
+    let plain_snapshots: Vec<(String, Vec<u8>)> = snapshot_rows(&tx)?
+        .into_iter()
+        .filter(|(_, data)| !crypto::is_sealed(data))
+        .collect();
+    for (item_id, json) in &plain_snapshots {
+        tx.execute(
+            "UPDATE snapshots SET data = ?1 WHERE item_id = ?2",
+            params![crypto::seal(key, json)?, item_id],
+        )
+        .map_err(|e| format!("加密剪贴板快照失败: {e}"))?;
+    }
+    tx.commit()
+        .map_err(|e| format!("提交数据库事务失败: {e}"))?;
+
+    let converted = plain_items.len() + plain_snippets.len() + plain_snapshots.len();
+    if converted > 0 {
+        conn.execute_batch("VACUUM; PRAGMA wal_checkpoint(TRUNCATE);")
+            .map_err(|e| format!("清理明文残留失败: {e}"))?;
+    }
+    Ok(converted)
+}
+
+/// 切换收藏；收藏数已达 `favorite_limit` 时拒绝新增收藏，取消收藏不受限制。
+pub(crate) fn toggle_favorite(
+    conn: &mut Connection,
+    key: Option<&Key>,
+    id: &str,
+    updated_at: u64,
+    favorite_limit: usize,
+    history_limit: usize,
+) -> Result<(Option<ClipboardItem>, Evicted), String> {
+    // 上限检查与更新放在同一事务中，两个并发的收藏请求不会都通过检查。
+    let tx = conn
+        .transaction_with_behavior(TransactionBehavior::Immediate)
+        .map_err(|e| format!("开启数据库事务失败: {e}"))?;
+    let is_favorite: Option<bool> = tx
+        .query_row(
+            "SELECT is_favorite FROM items WHERE id = ?1",
+            params![id],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(|e| format!("读取收藏状态失败: {e}"))?;
+    let Some(is_favorite) = is_favorite else {
+        return Ok((None, Evicted::default()));
+    };
+    if !is_favorite && favorite_count(&tx)? >= favorite_limit {
+        return Err(format!(
+            "收藏已达上限 {favorite_limit} 条，请先取消部分收藏"
+        ));
+    }
+
+    let changed = tx
+        .execute(
+            "UPDATE items SET is_favorite = NOT is_favorite, expires_at = NULL, updated_at = ?1
+             WHERE id = ?2",
+            params![updated_at as i64, id],
+        )
+        .map_err(|e| format!("更新收藏状态失败: {e}"))?;
+    if changed == 0 {
+        return Ok((None, Evicted::default()));
+    }
+    // 取消收藏的条目重新计入历史上限，超出时在同一事务里截断。
+    let evicted = if is_favorite {
+        prune(&tx, history_limit)?
+    } else {
+        Evicted::default()
+    };
+    let item = find_item(&tx, key, id)?;
+    tx.commit()
+        .map_err(|e| format!("提交数据库事务失败: {e}"))?;
+    Ok((item, evicted))
+}
+
+pub(crate) fn favorite_count(conn: &Connection) -> Result<usize, String> {
+    conn.query_row(
+        "SELECT COUNT(*) FROM items WHERE is_favorite = 1",
+        [],
+        |row| row.get::<_, i64>(0),
+    )
+    .map(|count| count as usize)
+    .map_err(|e| format!("统计收藏失败: {e}"))
+}
+
+/// 删除已过期且未收藏的条目（目前只有敏感内容会带过期时间），返回删除条数。
+pub(crate) fn purge_expired(conn: &Connection, now: u64) -> Result<usize, String> {
+    conn.execute(
+        "DELETE FROM items WHERE expires_at IS NOT NULL AND expires_at <= ?1 AND is_favorite = 0",
+        params![now as i64],
+    )
+    .map_err(|e| format!("清理过期历史失败: {e}"))
+}
+
+/// 删除 `updated_at` 早于 `cutoff` 的未收藏条目，可限定类型，返回删除条数。
+pub(crate) fn delete_unfavorited_before(
+    conn: &Connection,
+    item_type: Option<&str>,
+    cutoff: u64,
+) -> Result<usize, String> {
+    conn.execute(
+        "DELETE FROM items
+         WHERE is_favorite = 0 AND updated_at < ?1 AND (?2 IS NULL OR item_type = ?2)",
+        params![cutoff as i64, item_type],
+    )
+    .map_err(|e| format!("清理过期历史失败: {e}"))
+}
+
+pub(crate) struct ImageRow {
+    pub(crate) id: String,
+    pub(crate) image_path: String,
+    pub(crate) is_favorite: bool,
+}
+
+/// 所有引用了图片文件的条目，按 `updated_at` 从旧到新排列。
+pub(crate) fn image_rows(conn: &Connection) -> Result<Vec<ImageRow>, String> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT id, image_path, is_favorite FROM items
+             WHERE image_path IS NOT NULL ORDER BY updated_at ASC",
+        )
+        .map_err(|e| format!("读取图片记录失败: {e}"))?;
+    let rows = stmt
+        .query_map([], |row| {
+            Ok(ImageRow {
+                id: row.get(0)?,
+                image_path: row.get(1)?,
+                is_favorite: row.get(2)?,
+            })
+        })
+        .map_err(|e| format!("读取图片记录失败: {e}"))?;
+    rows.collect::<rusqlite::Result<Vec<_>>>()
+        .map_err(|e| format!("解析图片记录失败: {e}"))
+}
+
+/// 历史条目与片段共用图片文件，两者的引用都计入。
+pub(crate) fn image_ref_count(conn: &Connection, image_path: &str) -> Result<usize, String> {
+    conn.query_row(
+        "SELECT (SELECT COUNT(*) FROM items WHERE image_path = ?1)
+              + (SELECT COUNT(*) FROM snippets WHERE image_path = ?1)",
+        params![image_path],
+        |row| row.get::<_, i64>(0),
+    )
+    .map(|count| count.max(0) as usize)
+    .map_err(|e| format!("统计图片引用失败: {e}"))
+}
+
+pub(crate) struct AuditedRow {
+    pub(crate) id: String,
+    /// 存储锁定时加密记录无法解开，此时只含元数据列。
+    pub(crate) sealed_locked: bool,
+    pub(crate) item: Result<ClipboardItem, String>,
+}
+
+/// 逐行读取全部记录，单行解析或解密失败不会中断整体读取，供完整性检查使用。
+pub(crate) fn audit_items(conn: &Connection, key: Option<&Key>) -> Result<Vec<AuditedRow>, String> {
+    let mut stmt = conn
+        .prepare(&format!("SELECT {ITEM_COLUMNS} FROM items"))
+        .map_err(|e| format!("读取历史失败: {e}"))?;
+    let rows = stmt
+        .query_map([], |row| {
+            let id = row.get::<_, String>(0).unwrap_or_default();
+            Ok((id, row_to_stored(row)))
+        })
+        .map_err(|e| format!("读取历史失败: {e}"))?;
+
+    let mut audited = Vec::new();
+    for row in rows {
+        let (id, stored) = row.map_err(|e| format!("读取历史失败: {e}"))?;
+        let audited_row = match stored {
+            Err(e) => AuditedRow {
+                id,
+                sealed_locked: false,
+                item: Err(format!("字段格式错误: {e}")),
+            },
+            Ok(stored) if stored.sealed.is_some() && key.is_none() => AuditedRow {
+                id,
+                sealed_locked: true,
+                item: Ok(stored.item),
+            },
+            Ok(stored) => AuditedRow {
+                id,
+                sealed_locked: false,
+                item: unseal(key, stored),
+            },
+        };
+        audited.push(audited_row);
+    }
+    Ok(audited)
+}
+
+pub(crate) fn delete_by_id(conn: &Connection, id: &str) -> Result<usize, String> {
+    conn.execute("DELETE FROM items WHERE id = ?1", params![id])
+        .map_err(|e| format!("删除历史失败: {e}"))
+}
+
+pub(crate) fn delete_item(
+    conn: &Connection,
+    key: Option<&Key>,
+    id: &str,
+) -> Result<Option<ClipboardItem>, String> {
+    let Some(item) = find_item(conn, key, id)? else {
+        return Ok(None);
+    };
+    conn.execute("DELETE FROM items WHERE id = ?1", params![id])
+        .map_err(|e| format!("删除历史失败: {e}"))?;
+    Ok(Some(item))
+}
+
+pub(crate) fn clear(conn: &Connection) -> Result<(), String> {
+    conn.execute("DELETE FROM items", [])
+        .map_err(|e| format!("清空历史失败: {e}"))?;
+    Ok(())
+}
+
+/// 片段整条序列化为 JSON 存入 `data` 列，有密钥时整体加密；`image_path` 单独存放以便统计图片引用。
+pub(crate) fn save_snippet(
+    conn: &Connection,
+    key: Option<&Key>,
+    snippet: &Snippet,
+) -> Result<(), String> {
+    let json = serde_json::to_vec(snippet).map_err(|e| format!("序列化片段失败: {e}"))?;
+    let data = match key {
+        Some(key) => crypto::seal(key, &json)?,
+        None => json,
+    };
+    conn.execute(
+        "INSERT INTO snippets (id, image_path, data, updated_at) VALUES (?1, ?2, ?3, ?4)
+         ON CONFLICT(id) DO UPDATE SET
+             image_path = excluded.image_path, data = excluded.data, updated_at = excluded.updated_at",
+        params![
+            snippet.id,
+            snippet.content.image_path,
+            data,
+            snippet.updated_at as i64
+        ],
+    )
+    .map_err(|e| format!("写入片段失败: {e}"))?;
+    Ok(())
+}
+
+/// 读取全部片段并标明每条是否已加密；加密片段在没有密钥时无法读取。
+fn load_snippet_rows(conn: &Connection, key: Option<&Key>) -> Result<Vec<(Snippet, bool)>, String> {
+    let mut stmt = conn
+        .prepare("SELECT data FROM snippets ORDER BY updated_at DESC")
+        .map_err(|e| format!("读取片段失败: {e}"))?;
+    let rows = stmt
+        .query_map([], |row| row.get::<_, Vec<u8>>(0))
+        .map_err(|e| format!("读取片段失败: {e}"))?
+        .collect::<rusqlite::Result<Vec<_>>>()
+        .map_err(|e| format!("读取片段失败: {e}"))?;
+
+    rows.into_iter()
+        .map(|data| {
+            let sealed = crypto::is_sealed(&data);
+            Ok((decode_snippet(key, data)?, sealed))
+        })
+        .collect()
+}
+
+fn decode_snippet(key: Option<&Key>, data: Vec<u8>) -> Result<Snippet, String> {
+    let json = if crypto::is_sealed(&data) {
+        let key = key.ok_or_else(|| "存储已加密，请先解锁".to_string())?;
+        crypto::open(key, &data)?
+    } else {
+        data
+    };
+    serde_json::from_slice(&json).map_err(|e| format!("解析片段失败: {e}"))
+}
+
+pub(crate) fn load_snippets(conn: &Connection, key: Option<&Key>) -> Result<Vec<Snippet>, String> {
+    Ok(load_snippet_rows(conn, key)?
+        .into_iter()
+        .map(|(snippet, _)| snippet)
+        .collect())
+}
+
+pub(crate) fn delete_snippet(conn: &Connection, id: &str) -> Result<usize, String> {
+    conn.execute("DELETE FROM snippets WHERE id = ?1", params![id])
+        .map_err(|e| format!("删除片段失败: {e}"))
+}
+
+pub(crate) fn snippet_image_paths(conn: &Connection) -> Result<Vec<String>, String> {
+    let mut stmt = conn
+        .prepare("SELECT image_path FROM snippets WHERE image_path IS NOT NULL")
+        .map_err(|e| format!("读取片段图片失败: {e}"))?;
+    let rows = stmt
+        .query_map([], |row| row.get(0))
+        .map_err(|e| format!("读取片段图片失败: {e}"))?;
+    rows.collect::<rusqlite::Result<Vec<String>>>()
+        .map_err(|e| format!("读取片段图片失败: {e}"))
+}
+
+/// 图片文件改名后，在同一事务中改写条目与片段中的引用；快照不记录图片路径，无需改写。
+pub(crate) fn rename_image_path(
+    conn: &mut Connection,
+    key: Option<&Key>,
+    old_path: &str,
+    new_path: &str,
+) -> Result<(), String> {
+    let tx = conn
+        .transaction()
+        .map_err(|e| format!("开启数据库事务失败: {e}"))?;
+    tx.execute(
+        "UPDATE items SET image_path = ?2 WHERE REPLACE(image_path, '\\', '/') = ?1",
+        params![old_path, new_path],
+    )
+    .map_err(|e| format!("更新图片路径失败: {e}"))?;
+
+    let mut stmt = tx
+        .prepare("SELECT data FROM snippets WHERE REPLACE(image_path, '\\', '/') = ?1")
+        .map_err(|e| format!("读取片段失败: {e}"))?;
+    let rows = stmt
+        .query_map(params![old_path], |row| row.get::<_, Vec<u8>>(0))
+        .map_err(|e| format!("读取片段失败: {e}"))?
+        .collect::<rusqlite::Result<Vec<_>>>()
+        .map_err(|e| format!("读取片段失败: {e}"))?;
+    drop(stmt);
+    for data in rows {
+        let mut snippet = decode_snippet(key, data)?;
+        snippet.content.image_path = Some(new_path.to_string());
+        save_snippet(&tx, key, &snippet)?;
+    }
+    tx.commit().map_err(|e| format!("提交数据库事务失败: {e}"))
+}
+
+#[derive(Serialize, Deserialize)]
+struct StoredFormat {
+    mime: String,
+    /// Base64 编码的原始字节。
+    data: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    property_type: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    property_format: Option<u8>,
+}
+
+/// 条目的完整快照整体序列化为 JSON 存入 `snapshots` 表，有密钥时整体加密；条目删除时由触发器一并删除。
+pub(crate) fn save_snapshot(
+    conn: &Connection,
+    key: Option<&Key>,
+    item_id: &str,
+    formats: &[snapshot::Format],
+) -> Result<(), String> {
+    let stored: Vec<StoredFormat> = formats
+        .iter()
+        .map(|format| StoredFormat {
+            mime: format.mime.clone(),
+            data: STANDARD.encode(&format.data),
+            property_type: format.property_type.clone(),
+            property_format: format.property_format,
+        })
+        .collect();
+    let json = serde_json::to_vec(&stored).map_err(|e| format!("序列化剪贴板快照失败: {e}"))?;
+    let data = match key {
+        Some(key) => crypto::seal(key, &json)?,
+        None => json,
+    };
+    conn.execute(
+        "INSERT INTO snapshots (item_id, data) VALUES (?1, ?2)
+         ON CONFLICT(item_id) DO UPDATE SET data = excluded.data",
+        params![item_id, data],
+    )
+    .map_err(|e| format!("写入剪贴板快照失败: {e}"))?;
+    Ok(())
+}
+
+pub(crate) fn load_snapshot(
+    conn: &Connection,
+    key: Option<&Key>,
+    item_id: &str,
+) -> Result<Option<Vec<snapshot::Format>>, String> {
+    let data: Option<Vec<u8>> = conn
+        .query_row(
+            "SELECT data FROM snapshots WHERE item_id = ?1",
+            params![item_id],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(|e| format!("读取剪贴板快照失败: {e}"))?;
+    let Some(data) = data else {
+        return Ok(None);
+    };
+    let json = if crypto::is_sealed(&data) {
+        let key = key.ok_or_else(|| "存储已加密，请先解锁".to_string())?;
+        crypto::open(key, &data)?
+    } else {
+        data
+    };
+    let stored: Vec<StoredFormat> =
+        serde_json::from_slice(&json).map_err(|e| format!("解析剪贴板快照失败: {e}"))?;
+    stored
+        .into_iter()
+        .map(|format| {
+            Ok(snapshot::Format {
+                data: STANDARD
+                    .decode(&format.data)
+                    .map_err(|e| format!("解析剪贴板快照失败: {e}"))?,
+                mime: format.mime,
+                property_type: format.property_type,
+                property_format: format.property_format,
+            })
+        })
+        .collect::<Result<Vec<_>, String>>()
+        .map(Some)
+}
+
+fn snapshot_rows(conn: &Connection) -> Result<Vec<(String, Vec<u8>)>, String> {
+    let mut stmt = conn
+        .prepare("SELECT item_id, data FROM snapshots")
+        .map_err(|e| format!("读取剪贴板快照失败: {e}"))?;
+    let rows = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+        .map_err(|e| format!("读取剪贴板快照失败: {e}"))?;
+    rows.collect::<rusqlite::Result<Vec<_>>>()
+        .map_err(|e| format!("读取剪贴板快照失败: {e}"))
+}
@@ -0,0 +1,301 @@
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::OsRng;
+use chrono::{DateTime, Local};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fmt::Write;
+
+const OPEN: &str = "{{";
+const CLOSE: &str = "}}";
+/// `\{{` 输出字面量 `{{`，不作为占位符解析。
+const ESCAPED_OPEN: &str = "\\{{";
+const DEFAULT_DATE_FORMAT: &str = "%Y-%m-%d";
+const DEFAULT_TIME_FORMAT: &str = "%H:%M:%S";
+
+/// 需要用户填写的字段，对应 `{{input:名称}}` 或带默认值的 `{{input:名称|默认值}}`。
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct TemplateField {
+    pub(crate) name: String,
+    pub(crate) default: Option<String>,
+}
+
+/// 一次展开共用的取值：富文本的 HTML 与纯文本两份内容用同一个上下文展开，
+/// 其中的日期与 `{{uuid}}` 保持一致。
+pub(crate) struct Context<'a> {
+    now: DateTime<Local>,
+    uuid: String,
+    /// 展开前剪贴板中的文本，供 `{{clipboard}}` 使用。
+    clipboard: Option<&'a str>,
+    values: &'a HashMap<String, String>,
+}
+
+impl<'a> Context<'a> {
+    pub(crate) fn new(clipboard: Option<&'a str>, values: &'a HashMap<String, String>) -> Self {
+        Self {
+            now: Local::now(),
+            uuid: uuid_v4(),
+            clipboard,
+            values,
+        }
+    }
+}
+
+pub(crate) struct Expanded {
+    pub(crate) text: String,
+    /// `{{cursor}}` 所在位置距文本末尾的字符数；没有光标标记时为 `None`。
+    pub(crate) cursor_from_end: Option<usize>,
+}
+
+enum Segment<'a> {
+    Literal(&'a str),
+    Placeholder {
+        raw: &'a str,
+        name: &'a str,
+        arg: Option<&'a str>,
+    },
+}
+
+/// 逐段切分文本；未闭合的 `{{` 按普通文本处理。
+fn segments(text: &str) -> Vec<Segment<'_>> {
+    let mut out = Vec::new();
+    let mut rest = text;
+    while !rest.is_empty() {
+        let escape = rest.find(ESCAPED_OPEN);
+        let open = rest.find(OPEN);
+        match (escape, open) {
+            (Some(e), Some(o)) if e < o => {
+                out.push(Segment::Literal(&rest[..e]));
+                out.push(Segment::Literal(OPEN));
+                rest = &rest[e + ESCAPED_OPEN.len()..];
+            }
+            (_, Some(o)) => {
+                let Some(len) = rest[o + OPEN.len()..].find(CLOSE) else {
+                    out.push(Segment::Literal(rest));
+                    break;
+                };
+                let end = o + OPEN.len() + len + CLOSE.len();
+                let inner = rest[o + OPEN.len()..end - CLOSE.len()].trim();
+                let (name, arg) = match inner.split_once(':') {
+                    Some((name, arg)) => (name.trim(), Some(arg)),
+                    None => (inner, None),
+                };
+                out.push(Segment::Literal(&rest[..o]));
+                out.push(Segment::Placeholder {
+                    raw: &rest[o..end],
+                    name,
+                    arg,
+                });
+                rest = &rest[end..];
+            }
+            _ => {
+                out.push(Segment::Literal(rest));
+                break;
+            }
+        }
+    }
+    out
+}
+
+fn parse_input(arg: Option<&str>) -> Option<TemplateField> {
+    let (name, default) = match arg?.split_once('|') {
+        Some((name, default)) => (name, Some(default.trim().to_string())),
+        None => (arg?, None),
+    };
+    let name = name.trim();
+    (!name.is_empty()).then(|| TemplateField {
+        name: name.to_string(),
+        default,
+    })
+}
+
+/// 文本中需要用户填写的字段，按首次出现的顺序去重。
+pub(crate) fn fields(text: &str) -> Vec<TemplateField> {
+    let mut fields: Vec<TemplateField> = Vec::new();
+    for segment in segments(text) {
+        if let Segment::Placeholder {
+            name: "input", arg, ..
+        } = segment
+        {
+            if let Some(field) = parse_input(arg) {
+                if !fields.iter().any(|f| f.name == field.name) {
+                    fields.push(field);
+                }
+            }
+        }
+    }
+    fields
+}
+
+fn format_time(now: &DateTime<Local>, format: &str) -> Result<String, String> {
+    let mut out = String::new();
+    write!(out, "{}", now.format(format)).map_err(|_| format!("无效的时间格式: {format}"))?;
+    Ok(out)
+}
+
+fn uuid_v4() -> String {
+    let mut bytes = [0u8; 16];
+    OsRng.fill_bytes(&mut bytes);
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    let hex: String = bytes.iter().map(|b| format!("{b:02x}")).collect();
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[0..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..32]
+    )
+}
+
+/// 展开占位符；`escape` 用于对替换进来的值做转义（例如写入 HTML 时）。
+/// 不认识的占位符原样保留，避免破坏本身含有 `{{ }}` 语法的内容。
+pub(crate) fn expand_with(
+    text: &str,
+    ctx: &Context<'_>,
+    escape: fn(&str) -> String,
+) -> Result<Expanded, String> {
+    let mut out = String::with_capacity(text.len());
+    let mut cursor = None;
+    for segment in segments(text) {
+        let (raw, name, arg) = match segment {
+            Segment::Literal(literal) => {
+                out.push_str(literal);
+                continue;
+            }
+            Segment::Placeholder { raw, name, arg } => (raw, name, arg),
+        };
+        let value = match name {
+            "date" => format_time(&ctx.now, arg.unwrap_or(DEFAULT_DATE_FORMAT))?,
+            "time" => format_time(&ctx.now, arg.unwrap_or(DEFAULT_TIME_FORMAT))?,
+            "clipboard" => ctx.clipboard.unwrap_or_default().to_string(),
+            "uuid" => ctx.uuid.clone(),
+            "cursor" => {
+                cursor.get_or_insert(out.chars().count());
+                continue;
+            }
+            "input" => {
+                let Some(field) = parse_input(arg) else {
+                    out.push_str(raw);
+                    continue;
+                };
+                ctx.values
+                    .get(&field.name)
+                    .cloned()
+                    .or(field.default)
+                    .ok_or_else(|| format!("缺少模板字段: {}", field.name))?
+            }
+            _ => {
+                out.push_str(raw);
+                continue;
+            }
+        };
+        out.push_str(&escape(&value));
+    }
+    let cursor_from_end = cursor.map(|at| out.chars().count() - at);
+    Ok(Expanded {
+        text: out,
+        cursor_from_end,
+    })
+}
+
+pub(crate) fn expand(text: &str, ctx: &Context<'_>) -> Result<Expanded, String> {
+    expand_with(text, ctx, str::to_string)
+}
+
+pub(crate) fn escape_html(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+pub(crate) fn has_placeholders(text: &str) -> bool {
+    segments(text).iter().any(|segment| {
+        matches!(
+            segment,
+            Segment::Placeholder {
+                name: "date" | "time" | "clipboard" | "uuid" | "cursor" | "input",
+                ..
+            }
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn expand_plain(text: &str, values: &[(&str, &str)]) -> Result<Expanded, String> {
+        let values: HashMap<String, String> = values
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect();
+        expand(text, &Context::new(Some("剪贴板"), &values))
+    }
+
+    #[test]
+    fn escaped_open_is_literal() {
+        assert!(!has_placeholders(r"\{{date}}"));
+        let expanded = expand_plain(r"\{{date}} {{clipboard}}", &[]).unwrap();
+        assert_eq!(expanded.text, "{{date}} 剪贴板");
+    }
+
+    #[test]
+    fn unclosed_and_unknown_placeholders_are_kept() {
+        assert!(!has_placeholders("a {{date"));
+        assert!(!has_placeholders("{{ item.id }}"));
+        let expanded = expand_plain("{{ item.id }} {{uuid", &[]).unwrap();
+        assert_eq!(expanded.text, "{{ item.id }} {{uuid");
+        assert_eq!(expanded.cursor_from_end, None);
+    }
+
+    #[test]
+    fn input_fields_use_values_then_defaults() {
+        let text = "{{input:name}} {{ input: greeting | 早上好 }} {{input:name|忽略}}";
+        assert_eq!(
+            fields(text),
+            [
+                TemplateField {
+                    name: "name".to_string(),
+                    default: None,
+                },
+                TemplateField {
+                    name: "greeting".to_string(),
+                    default: Some("早上好".to_string()),
+                },
+            ]
+        );
+        let expanded = expand_plain(text, &[("name", "Bob")]).unwrap();
+        assert_eq!(expanded.text, "Bob 早上好 Bob");
+        assert_eq!(
+            expand_plain(text, &[]).err().as_deref(),
+            Some("缺少模板字段: name")
+        );
+    }
+
+    #[test]
+    fn cursor_offset_counts_characters() {
+        let expanded = expand_plain("你好{{cursor}}世界🙂{{cursor}}", &[]).unwrap();
+        assert_eq!(expanded.text, "你好世界🙂");
+        assert_eq!(expanded.cursor_from_end, Some(3));
+
+        let expanded = expand_plain("{{clipboard}}{{cursor}}", &[]).unwrap();
+        assert_eq!(expanded.cursor_from_end, Some(0));
+    }
+
+    #[test]
+    fn date_uuid_and_html_escaping() {
+        let values = HashMap::from([("x".to_string(), "<a&b>".to_string())]);
+        let ctx = Context::new(None, &values);
+        let html = expand_with("<i>{{input:x}}</i>{{uuid}}", &ctx, escape_html).unwrap();
+        assert_eq!(html.text, format!("<i>&lt;a&amp;b&gt;</i>{}", ctx.uuid));
+        assert_eq!(ctx.uuid.len(), 36);
+        assert_eq!(&ctx.uuid[14..15], "4");
+
+        let date = expand("{{date:%Y}}-{{clipboard}}", &ctx).unwrap();
+        assert_eq!(date.text, format!("{}-", ctx.now.format("%Y")));
+    }
+}
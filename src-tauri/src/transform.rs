@@ -0,0 +1,237 @@
+use crate::ClipboardItem;
+use base64::engine::general_purpose::{STANDARD, STANDARD_NO_PAD};
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+
+const UPPER_CASE: &str = "upper-case";
+const LOWER_CASE: &str = "lower-case";
+const TITLE_CASE: &str = "title-case";
+const TRIM_LINES: &str = "trim-lines";
+const JSON_PRETTY: &str = "json-pretty";
+const JSON_MINIFY: &str = "json-minify";
+const URL_ENCODE: &str = "url-encode";
+const URL_DECODE: &str = "url-decode";
+const BASE64_ENCODE: &str = "base64-encode";
+const BASE64_DECODE: &str = "base64-decode";
+const STRIP_FORMATTING: &str = "strip-formatting";
+const SORT_LINES: &str = "sort-lines";
+const UNIQUE_LINES: &str = "unique-lines";
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct TransformPayload {
+    pub(crate) id: String,
+    pub(crate) transform: String,
+    /// 为真时把结果另存为一条新的历史记录。
+    #[serde(default)]
+    pub(crate) save: bool,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct TransformResult {
+    pub(crate) text: String,
+    pub(crate) saved: Option<ClipboardItem>,
+}
+
+fn title_case(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut at_word_start = true;
+    for ch in text.chars() {
+        if ch.is_alphanumeric() {
+            if at_word_start {
+                out.extend(ch.to_uppercase());
+            } else {
+                out.extend(ch.to_lowercase());
+            }
+            at_word_start = false;
+        } else {
+            out.push(ch);
+            // 撇号不断词，避免 "don't" 变成 "Don'T"。
+            at_word_start = !matches!(ch, '\'' | '’');
+        }
+    }
+    out
+}
+
+fn trim_lines(text: &str) -> String {
+    text.lines()
+        .map(str::trim)
+        .collect::<Vec<_>>()
+        .join("\n")
+        .trim_matches('\n')
+        .to_string()
+}
+
+/// 按 RFC 3986 编码，只保留非保留字符。
+fn url_encode(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for byte in text.bytes() {
+        if byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'_' | b'.' | b'~') {
+            out.push(byte as char);
+        } else {
+            out.push_str(&format!("%{byte:02X}"));
+        }
+    }
+    out
+}
+
+/// 解码 `%XX`，同时把表单编码中的 `+` 视为空格。
+fn url_decode(text: &str) -> Result<String, String> {
+    let bytes = text.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'%' => {
+                // `from_str_radix` 接受前导 `+`，先确认两位都是十六进制数字。
+                let hex = bytes
+                    .get(i + 1..i + 3)
+                    .filter(|hex| hex.iter().all(u8::is_ascii_hexdigit))
+                    .and_then(|hex| std::str::from_utf8(hex).ok())
+                    .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                    .ok_or_else(|| format!("无效的 URL 编码，位置 {i}"))?;
+                out.push(hex);
+                i += 3;
+            }
+            b'+' => {
+                out.push(b' ');
+                i += 1;
+            }
+            byte => {
+                out.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8(out).map_err(|_| "URL 解码结果不是有效的 UTF-8 文本".to_string())
+}
+
+/// 兼容标准与 URL 安全两种字母表，忽略空白与末尾的填充。
+fn base64_decode(text: &str) -> Result<String, String> {
+    let normalized: String = text
+        .chars()
+        .filter(|ch| !ch.is_whitespace())
+        .map(|ch| match ch {
+            '-' => '+',
+            '_' => '/',
+            other => other,
+        })
+        .collect();
+    let bytes = STANDARD_NO_PAD
+        .decode(normalized.trim_end_matches('='))
+        .map_err(|e| format!("Base64 解码失败: {e}"))?;
+    String::from_utf8(bytes).map_err(|_| "Base64 解码结果不是文本".to_string())
+}
+
+/// 去掉零宽字符、软连字符等不可见格式字符，并把各种特殊空格换成普通空格。
+fn strip_formatting(text: &str) -> String {
+    let cleaned: String = text
+        .chars()
+        .filter(|ch| {
+            !matches!(
+                ch,
+                '\u{200B}'..='\u{200F}' | '\u{202A}'..='\u{202E}' | '\u{2060}'..='\u{2064}' | '\u{FEFF}' | '\u{00AD}'
+            )
+        })
+        .map(|ch| match ch {
+            '\u{00A0}' | '\u{2000}'..='\u{200A}' | '\u{202F}' | '\u{205F}' | '\u{3000}' => ' ',
+            other => other,
+        })
+        .collect();
+    cleaned
+        .lines()
+        .map(str::trim_end)
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn unique_lines(text: &str) -> String {
+    let mut seen = std::collections::HashSet::new();
+    text.lines()
+        .filter(|line| seen.insert(*line))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// 对纯文本应用一个内置变换；富文本条目由调用方先取其纯文本。
+pub(crate) fn apply(transform: &str, text: &str) -> Result<String, String> {
+    let result = match transform {
+        UPPER_CASE => text.to_uppercase(),
+        LOWER_CASE => text.to_lowercase(),
+        TITLE_CASE => title_case(text),
+        TRIM_LINES => trim_lines(text),
+        JSON_PRETTY | JSON_MINIFY => {
+            let value: serde_json::Value =
+                serde_json::from_str(text).map_err(|e| format!("不是有效的 JSON: {e}"))?;
+            let formatted = if transform == JSON_PRETTY {
+                serde_json::to_string_pretty(&value)
+            } else {
+                serde_json::to_string(&value)
+            };
+            formatted.map_err(|e| format!("格式化 JSON 失败: {e}"))?
+        }
+        URL_ENCODE => url_encode(text),
+        URL_DECODE => url_decode(text.trim())?,
+        BASE64_ENCODE => STANDARD.encode(text.as_bytes()),
+        BASE64_DECODE => base64_decode(text)?,
+        STRIP_FORMATTING => strip_formatting(text),
+        SORT_LINES => {
+            let mut lines: Vec<&str> = text.lines().collect();
+            lines.sort_unstable();
+            lines.join("\n")
+        }
+        UNIQUE_LINES => unique_lines(text),
+        other => return Err(format!("未知的变换: {other}")),
+    };
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn url_decode_handles_plus_and_utf8() {
+        assert_eq!(url_decode("a%20b+c").unwrap(), "a b c");
+        assert_eq!(url_decode("%E4%BD%A0%e5%a5%bd").unwrap(), "你好");
+        let text = "a b/你?&=";
+        assert_eq!(url_decode(&url_encode(text)).unwrap(), text);
+    }
+
+    #[test]
+    fn url_decode_rejects_bad_escapes() {
+        assert!(url_decode("%zz").is_err());
+        assert!(url_decode("%4").is_err());
+        assert!(url_decode("abc%").is_err());
+        assert!(url_decode("%+1").is_err());
+        assert!(url_decode("%FF").is_err());
+    }
+
+    #[test]
+    fn base64_decode_accepts_url_safe_and_missing_padding() {
+        assert_eq!(base64_decode("Pz8/Pj4+").unwrap(), "???>>>");
+        assert_eq!(base64_decode("Pz8_Pj4-").unwrap(), "???>>>");
+        assert_eq!(base64_decode("aGk=").unwrap(), "hi");
+        assert_eq!(base64_decode("aGk").unwrap(), "hi");
+        assert_eq!(base64_decode("aG\nk =").unwrap(), "hi");
+        assert!(base64_decode("a").is_err());
+        assert!(base64_decode("/w==").is_err());
+    }
+
+    #[test]
+    fn title_case_keeps_apostrophes_inside_words() {
+        assert_eq!(title_case("don't STOP"), "Don't Stop");
+        assert_eq!(title_case("it’s o'neil"), "It’s O'neil");
+        assert_eq!(
+            title_case("hello-world foo_bar 2nd"),
+            "Hello-World Foo_Bar 2nd"
+        );
+    }
+
+    #[test]
+    fn apply_rejects_unknown_transform() {
+        assert_eq!(apply(TRIM_LINES, "  a  \n\n b ").unwrap(), "a\n\nb");
+        assert!(apply("rot13", "abc").is_err());
+    }
+}
@@ -0,0 +1,297 @@
+use crate::{append_log, capture_clipboard, load_settings, AppState};
+use serde::Serialize;
+use std::thread;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager};
+
+#[cfg(target_os = "linux")]
+use crate::wayland;
+
+pub(crate) const CAPTURED_EVENT: &str = "clipboard://captured";
+
+#[cfg(target_os = "linux")]
+pub(crate) const BACKEND_WAYLAND: &str = "wayland";
+#[cfg(target_os = "linux")]
+pub(crate) const BACKEND_X11: &str = "x11";
+pub(crate) const BACKEND_POLLING: &str = "polling";
+
+/// 当前生效的监听方式，供设置页诊断。
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct BackendStatus {
+    /// `wayland`、`x11` 或 `polling`，监听线程启动前为空。
+    pub(crate) backend: String,
+    /// Wayland 下为使用的协议，退回轮询时为前面各方式失败的原因。
+    pub(crate) detail: String,
+}
+
+/// 一轮事件中两个选区的变化。
+#[cfg(target_os = "linux")]
+#[derive(Debug, Default, Clone, Copy)]
+pub(crate) struct Changes {
+    pub(crate) clipboard: bool,
+    /// 剪贴板已被清空或没有所有者，通常是内容的提供者退出了。
+    pub(crate) cleared: bool,
+    pub(crate) primary: bool,
+}
+
+#[cfg(target_os = "linux")]
+impl Changes {
+    pub(crate) fn any(&self) -> bool {
+        self.clipboard || self.primary
+    }
+}
+
+/// 所有权变更通知到达后，源程序可能还没准备好数据，稍等再读取。
+const SETTLE_DELAY: Duration = Duration::from_millis(60);
+/// 拖选文本时 PRIMARY 会连续变化，等选区稳定下来再读取。
+#[cfg(target_os = "linux")]
+const PRIMARY_SETTLE: Duration = Duration::from_millis(300);
+
+pub(crate) fn spawn(app: AppHandle) {
+    let spawned = thread::Builder::new()
+        .name("clipboard-watcher".to_string())
+        .spawn(move || run(app));
+    if let Err(err) = spawned {
+        eprintln!("clipboard watcher spawn failed: {err}");
+    }
+}
+
+fn run(app: AppHandle) {
+    capture_and_emit(&app);
+
+    #[cfg(target_os = "linux")]
+    let _owner = match crate::ownership::Owner::new() {
+        Ok(owner) => Some(owner),
+        Err(err) => {
+            append_log(&app, "WARN", &format!("clipboard owner unavailable: {err}"));
+            None
+        }
+    };
+
+    #[cfg(target_os = "linux")]
+    let failures = watch_linux(&app);
+    #[cfg(not(target_os = "linux"))]
+    let failures: Vec<String> = Vec::new();
+
+    set_backend(&app, BACKEND_POLLING, failures.join("；"));
+    watch_polling(&app);
+}
+
+/// 依次尝试 Wayland data-control 与 X11 XFixes；只在都不可用时返回各自失败的原因。
+#[cfg(target_os = "linux")]
+fn watch_linux(app: &AppHandle) -> Vec<String> {
+    let mut failures = Vec::new();
+    // Wayland 会话优先用 data-control：经 XWayland 只能在本程序窗口获得焦点时读到剪贴板。
+    if std::env::var_os("WAYLAND_DISPLAY").is_some() {
+        if let Err(err) = watch_wayland(app) {
+            append_log(
+                app,
+                "WARN",
+                &format!("wayland clipboard watcher stopped, fallback to x11: {err}"),
+            );
+            failures.push(format!("Wayland: {err}"));
+        }
+    }
+    if std::env::var_os("DISPLAY").is_some() {
+        if let Err(err) = watch_x11(app) {
+            append_log(
+                app,
+                "WARN",
+                &format!("x11 clipboard watcher stopped, fallback to polling: {err}"),
+            );
+            failures.push(format!("X11: {err}"));
+        }
+    }
+    failures
+}
+
+fn set_backend(app: &AppHandle, backend: &str, detail: String) {
+    append_log(
+        app,
+        "INFO",
+        &format!("clipboard watcher using {backend} backend {detail}"),
+    );
+    if let Ok(mut status) = app.state::<AppState>().watcher_backend.lock() {
+        *status = BackendStatus {
+            backend: backend.to_string(),
+            detail,
+        };
+    }
+}
+
+fn capture_and_emit(app: &AppHandle) {
+    let state = app.state::<AppState>();
+    match capture_clipboard(app, &state) {
+        Ok(Some(item)) => {
+            let _ = app.emit(CAPTURED_EVENT, &item);
+        }
+        Ok(None) => {}
+        Err(err) => append_log(app, "WARN", &format!("clipboard capture failed: {err}")),
+    }
+}
+
+fn watch_polling(app: &AppHandle) {
+    loop {
+        let interval = load_settings(app)
+            .map(|settings| settings.poll_interval_ms)
+            .unwrap_or(800);
+        thread::sleep(Duration::from_millis(interval));
+        capture_and_emit(app);
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn watch_x11(app: &AppHandle) -> Result<(), String> {
+    use x11rb::connection::Connection;
+    use x11rb::protocol::xfixes::{ConnectionExt as _, SelectionEventMask};
+    use x11rb::protocol::xproto::{ConnectionExt as _, CreateWindowAux, WindowClass};
+    use x11rb::protocol::Event;
+    use x11rb::COPY_DEPTH_FROM_PARENT;
+
+    let (conn, screen_num) = x11rb::connect(None).map_err(|e| format!("连接 X11 失败: {e}"))?;
+    conn.xfixes_query_version(5, 0)
+        .map_err(|e| format!("查询 XFixes 失败: {e}"))?
+        .reply()
+        .map_err(|e| format!("XFixes 不可用: {e}"))?;
+
+    let screen = &conn.setup().roots[screen_num];
+    let window = conn
+        .generate_id()
+        .map_err(|e| format!("分配 X11 窗口失败: {e}"))?;
+    conn.create_window(
+        COPY_DEPTH_FROM_PARENT,
+        window,
+        screen.root,
+        0,
+        0,
+        1,
+        1,
+        0,
+        WindowClass::INPUT_ONLY,
+        0,
+        &CreateWindowAux::new(),
+    )
+    .map_err(|e| format!("创建 X11 窗口失败: {e}"))?;
+
+    let mut selections = crate::selection::Selections::new()?;
+    let mut atoms = Vec::new();
+    for name in ["CLIPBOARD", "PRIMARY"] {
+        let atom = conn
+            .intern_atom(false, name.as_bytes())
+            .map_err(|e| format!("获取 {name} atom 失败: {e}"))?
+            .reply()
+            .map_err(|e| format!("获取 {name} atom 失败: {e}"))?
+            .atom;
+        conn.xfixes_select_selection_input(
+            window,
+            atom,
+            SelectionEventMask::SET_SELECTION_OWNER
+                | SelectionEventMask::SELECTION_WINDOW_DESTROY
+                | SelectionEventMask::SELECTION_CLIENT_CLOSE,
+        )
+        .map_err(|e| format!("订阅剪贴板事件失败: {e}"))?;
+        atoms.push(atom);
+    }
+    let (clipboard_atom, primary_atom) = (atoms[0], atoms[1]);
+    conn.flush()
+        .map_err(|e| format!("刷新 X11 连接失败: {e}"))?;
+
+    set_backend(app, BACKEND_X11, "xfixes".to_string());
+    loop {
+        let event = conn
+            .wait_for_event()
+            .map_err(|e| format!("读取 X11 事件失败: {e}"))?;
+        let Event::XfixesSelectionNotify(notify) = event else {
+            continue;
+        };
+        let mut clipboard_changed = notify.selection == clipboard_atom;
+        let mut primary_changed = notify.selection == primary_atom;
+        let mut delay = if primary_changed {
+            PRIMARY_SETTLE
+        } else {
+            SETTLE_DELAY
+        };
+        // 等待期间又有 PRIMARY 变化说明还在拖选，继续等到安静下来。
+        loop {
+            thread::sleep(delay);
+            let mut more_primary = false;
+            while let Some(event) = conn.poll_for_event().ok().flatten() {
+                if let Event::XfixesSelectionNotify(notify) = event {
+                    clipboard_changed |= notify.selection == clipboard_atom;
+                    more_primary |= notify.selection == primary_atom;
+                }
+            }
+            if !more_primary {
+                break;
+            }
+            primary_changed = true;
+            delay = PRIMARY_SETTLE;
+        }
+        let owner_gone = clipboard_changed
+            && conn
+                .get_selection_owner(clipboard_atom)
+                .map_err(|e| format!("查询剪贴板所有者失败: {e}"))?
+                .reply()
+                .map_err(|e| format!("查询剪贴板所有者失败: {e}"))?
+                .owner
+                == x11rb::NONE;
+        handle_changes(
+            app,
+            &mut selections,
+            Changes {
+                clipboard: clipboard_changed,
+                cleared: owner_gone,
+                primary: primary_changed,
+            },
+        );
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn watch_wayland(app: &AppHandle) -> Result<(), String> {
+    let mut listener = wayland::Listener::connect()?;
+    let mut selections = crate::selection::Selections::new()?;
+    set_backend(app, BACKEND_WAYLAND, listener.protocol().to_string());
+    loop {
+        let mut changes = listener.wait()?;
+        let mut delay = if changes.primary {
+            PRIMARY_SETTLE
+        } else {
+            SETTLE_DELAY
+        };
+        loop {
+            thread::sleep(delay);
+            let more = listener.drain()?;
+            if more.clipboard {
+                changes.clipboard = true;
+                changes.cleared = more.cleared;
+            }
+            if !more.primary {
+                break;
+            }
+            changes.primary = true;
+            delay = PRIMARY_SETTLE;
+        }
+        handle_changes(app, &mut selections, changes);
+    }
+}
+
+/// 剪贴板被清空（内容的提供者退出）时先接管，再照常采集与同步。
+#[cfg(target_os = "linux")]
+fn handle_changes(
+    app: &AppHandle,
+    selections: &mut crate::selection::Selections,
+    changes: Changes,
+) {
+    if changes.clipboard {
+        if changes.cleared {
+            crate::ownership::take_over(app);
+        }
+        capture_and_emit(app);
+        selections.clipboard_changed(app);
+    }
+    if changes.primary {
+        selections.primary_changed(app);
+    }
+}
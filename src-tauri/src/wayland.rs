@@ -0,0 +1,196 @@
+use crate::watcher::Changes;
+use wayland_client::globals::{registry_queue_init, GlobalListContents};
+use wayland_client::protocol::wl_registry::WlRegistry;
+use wayland_client::protocol::wl_seat::WlSeat;
+use wayland_client::{event_created_child, Connection, Dispatch, EventQueue, QueueHandle};
+use wayland_protocols::ext::data_control::v1::client::{
+    ext_data_control_device_v1::{self, ExtDataControlDeviceV1},
+    ext_data_control_manager_v1::ExtDataControlManagerV1,
+    ext_data_control_offer_v1::ExtDataControlOfferV1,
+};
+use wayland_protocols_wlr::data_control::v1::client::{
+    zwlr_data_control_device_v1::{self, ZwlrDataControlDeviceV1},
+    zwlr_data_control_manager_v1::ZwlrDataControlManagerV1,
+    zwlr_data_control_offer_v1::ZwlrDataControlOfferV1,
+};
+
+pub(crate) const EXT_PROTOCOL: &str = "ext-data-control-v1";
+pub(crate) const WLR_PROTOCOL: &str = "wlr-data-control-unstable-v1";
+
+#[derive(Default)]
+struct State {
+    changes: Changes,
+    finished: bool,
+}
+
+impl State {
+    fn selection(&mut self, present: bool) {
+        self.changes.clipboard = true;
+        self.changes.cleared = !present;
+    }
+}
+
+/// 通过 data-control 协议接收选区变化，不需要窗口获得焦点。
+pub(crate) struct Listener {
+    queue: EventQueue<State>,
+    state: State,
+    protocol: &'static str,
+}
+
+impl Listener {
+    /// 连接 Wayland 合成器，优先使用 ext-data-control，其次 wlr-data-control；都不支持时返回错误。
+    pub(crate) fn connect() -> Result<Self, String> {
+        let conn = Connection::connect_to_env().map_err(|e| format!("连接 Wayland 失败: {e}"))?;
+        let (globals, queue) = registry_queue_init::<State>(&conn)
+            .map_err(|e| format!("读取 Wayland 全局对象失败: {e}"))?;
+        let qh = queue.handle();
+        let seat: WlSeat = globals
+            .bind(&qh, 1..=1, ())
+            .map_err(|_| "Wayland 会话中没有输入座位".to_string())?;
+
+        let protocol = if let Ok(manager) =
+            globals.bind::<ExtDataControlManagerV1, _, _>(&qh, 1..=1, ())
+        {
+            manager.get_data_device(&seat, &qh, ());
+            EXT_PROTOCOL
+        } else if let Ok(manager) = globals.bind::<ZwlrDataControlManagerV1, _, _>(&qh, 1..=2, ()) {
+            manager.get_data_device(&seat, &qh, ());
+            WLR_PROTOCOL
+        } else {
+            return Err("合成器不支持 data-control 协议".to_string());
+        };
+
+        let mut listener = Self {
+            queue,
+            state: State::default(),
+            protocol,
+        };
+        // 创建设备后合成器会先报告当前选区，那不是新的变化。
+        listener.drain()?;
+        Ok(listener)
+    }
+
+    pub(crate) fn protocol(&self) -> &'static str {
+        self.protocol
+    }
+
+    /// 阻塞到任一选区发生变化。
+    pub(crate) fn wait(&mut self) -> Result<Changes, String> {
+        while !self.state.changes.any() {
+            self.queue
+                .blocking_dispatch(&mut self.state)
+                .map_err(|e| format!("读取 Wayland 事件失败: {e}"))?;
+            self.check_finished()?;
+        }
+        Ok(std::mem::take(&mut self.state.changes))
+    }
+
+    /// 收取目前已经到达的事件，不阻塞等待新的变化。
+    pub(crate) fn drain(&mut self) -> Result<Changes, String> {
+        self.queue
+            .roundtrip(&mut self.state)
+            .map_err(|e| format!("读取 Wayland 事件失败: {e}"))?;
+        self.check_finished()?;
+        Ok(std::mem::take(&mut self.state.changes))
+    }
+
+    fn check_finished(&self) -> Result<(), String> {
+        if self.state.finished {
+            return Err("data-control 设备已失效".to_string());
+        }
+        Ok(())
+    }
+}
+
+// 只关心选区是否变化，内容由 arboard 另行读取，收到的 offer 直接销毁。
+impl Dispatch<ExtDataControlDeviceV1, ()> for State {
+    fn event(
+        state: &mut Self,
+        _: &ExtDataControlDeviceV1,
+        event: ext_data_control_device_v1::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        match event {
+            ext_data_control_device_v1::Event::Selection { id } => {
+                state.selection(id.is_some());
+                if let Some(offer) = id {
+                    offer.destroy();
+                }
+            }
+            ext_data_control_device_v1::Event::PrimarySelection { id } => {
+                state.changes.primary = true;
+                if let Some(offer) = id {
+                    offer.destroy();
+                }
+            }
+            ext_data_control_device_v1::Event::Finished => state.finished = true,
+            _ => {}
+        }
+    }
+
+    event_created_child!(State, ExtDataControlDeviceV1, [
+        ext_data_control_device_v1::EVT_DATA_OFFER_OPCODE => (ExtDataControlOfferV1, ()),
+    ]);
+}
+
+impl Dispatch<ZwlrDataControlDeviceV1, ()> for State {
+    fn event(
+        state: &mut Self,
+        _: &ZwlrDataControlDeviceV1,
+        event: zwlr_data_control_device_v1::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        match event {
+            zwlr_data_control_device_v1::Event::Selection { id } => {
+                state.selection(id.is_some());
+                if let Some(offer) = id {
+                    offer.destroy();
+                }
+            }
+            zwlr_data_control_device_v1::Event::PrimarySelection { id } => {
+                state.changes.primary = true;
+                if let Some(offer) = id {
+                    offer.destroy();
+                }
+            }
+            zwlr_data_control_device_v1::Event::Finished => state.finished = true,
+            _ => {}
+        }
+    }
+
+    event_created_child!(State, ZwlrDataControlDeviceV1, [
+        zwlr_data_control_device_v1::EVT_DATA_OFFER_OPCODE => (ZwlrDataControlOfferV1, ()),
+    ]);
+}
+
+/// 以下对象没有需要处理的事件。
+macro_rules! ignore_events {
+    ($($iface:ty => $udata:ty),* $(,)?) => {
+        $(
+            impl Dispatch<$iface, $udata> for State {
+                fn event(
+                    _: &mut Self,
+                    _: &$iface,
+                    _: <$iface as wayland_client::Proxy>::Event,
+                    _: &$udata,
+                    _: &Connection,
+                    _: &QueueHandle<Self>,
+                ) {
+                }
+            }
+        )*
+    };
+}
+
+ignore_events!(
+    WlRegistry => GlobalListContents,
+    WlSeat => (),
+    ExtDataControlManagerV1 => (),
+    ExtDataControlOfferV1 => (),
+    ZwlrDataControlManagerV1 => (),
+    ZwlrDataControlOfferV1 => (),
+);